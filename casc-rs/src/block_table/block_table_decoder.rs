@@ -0,0 +1,392 @@
+use crate::block_table::block_table_encoder_type::BlockTableEncoderType;
+use crate::crypto::{arc4::Arc4, salsa20::Salsa20};
+use crate::error::CascError;
+use crate::tact_key_registry::TactKeyRegistry;
+use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
+use flate2::read::ZlibDecoder;
+use std::io::{self, Cursor, Error, Read, Seek, SeekFrom};
+
+/// The signature of a BLTE blob ("BLTE").
+pub(crate) const BLTE_SIGNATURE: u32 = 0x45544C42;
+/// The encryption algorithm identifier for Salsa20.
+const ENCRYPTION_SALSA20: u8 = 0x53;
+/// The encryption algorithm identifier for ARC4.
+const ENCRYPTION_ARC4: u8 = 0x41;
+
+/// Decodes a single BLTE frame, including its leading encoder type byte.
+///
+/// # Arguments
+///
+/// * `encoded` - The encoded frame data, starting with the encoder type.
+/// * `frame_index` - The index of the frame within its BLTE blob, used to derive the IV of encrypted frames.
+/// * `content_size` - The expected decoded size of the frame. Frames decoding to more bytes are rejected.
+/// * `key_registry` - The keys available for decrypting encrypted frames.
+pub(crate) fn decode_frame(
+    encoded: &[u8],
+    frame_index: u32,
+    content_size: usize,
+    key_registry: Option<&TactKeyRegistry>,
+) -> io::Result<Vec<u8>> {
+    let (&type_byte, data) = encoded
+        .split_first()
+        .ok_or_else(|| Error::other("Empty Block Table frame"))?;
+
+    match BlockTableEncoderType::from(type_byte) {
+        BlockTableEncoderType::Raw => Ok(data.to_vec()),
+        BlockTableEncoderType::ZLib => {
+            // Inflate one byte past the frame size, so oversized frames are caught without
+            // inflating all of them
+            let mut decoder = ZlibDecoder::new(data).take(content_size as u64 + 1);
+            let mut decoded = Vec::with_capacity(content_size);
+            decoder.read_to_end(&mut decoded)?;
+            if decoded.len() > content_size {
+                return Err(Error::other(format!(
+                    "ZLib frame exceeds the frame size {content_size}"
+                )));
+            }
+            Ok(decoded)
+        }
+        BlockTableEncoderType::Encrypted => {
+            // The decrypted data is itself an encoded frame
+            let decrypted = decrypt_frame(data, frame_index, key_registry)?;
+            decode_frame(&decrypted, frame_index, content_size, key_registry)
+        }
+        BlockTableEncoderType::Lz4 => decode_lz4(data, content_size),
+        BlockTableEncoderType::Recursive => decode_blte(data, content_size, key_registry),
+        _ => Err(Error::other("Unsupported Block Table Type")),
+    }
+}
+
+/// Decodes a complete BLTE blob, such as the payload of a recursive frame, that decodes to at
+/// most `content_size` bytes.
+fn decode_blte(
+    data: &[u8],
+    content_size: usize,
+    key_registry: Option<&TactKeyRegistry>,
+) -> io::Result<Vec<u8>> {
+    let invalid = || Error::other("Invalid nested Block Table");
+    let mut reader = Cursor::new(data);
+
+    let signature = reader.read_u32::<LittleEndian>()?;
+    if signature != BLTE_SIGNATURE {
+        return Err(Error::other(format!(
+            "Invalid Block Table Header signature: {signature:#X}"
+        )));
+    }
+    let header_size = reader.read_u32::<BigEndian>()? as usize;
+
+    // Without a frame table the rest of the data is a single frame
+    if header_size == 0 {
+        return decode_frame(&data[8..], 0, content_size, key_registry);
+    }
+
+    let _table_format = reader.read_u8()?;
+    let frame_count = reader.read_u24::<BigEndian>()?;
+    let mut frames = Vec::new();
+    for _ in 0..frame_count {
+        let encoded_size = reader.read_u32::<BigEndian>()? as usize;
+        let frame_size = reader.read_u32::<BigEndian>()? as usize;
+        reader.seek(SeekFrom::Current(16))?;
+        frames.push((encoded_size, frame_size));
+    }
+    // The nested frames must fit in the frame they were found in
+    let total_size = frames.iter().map(|(_, size)| *size as u64).sum::<u64>();
+    if total_size > content_size as u64 {
+        return Err(invalid());
+    }
+
+    let mut offset = header_size;
+    let mut decoded = Vec::new();
+    for (index, (encoded_size, frame_size)) in frames.into_iter().enumerate() {
+        let encoded = data
+            .get(offset..offset + encoded_size)
+            .ok_or_else(invalid)?;
+        decoded.extend(decode_frame(
+            encoded,
+            index as u32,
+            frame_size,
+            key_registry,
+        )?);
+        offset += encoded_size;
+    }
+    Ok(decoded)
+}
+
+/// Decodes an LZ4 frame.
+///
+/// The frame starts with a header version, the big endian decoded size and a block shift.
+/// The data is split into chunks of `1 << block_shift` decoded bytes, each compressed as an
+/// independent LZ4 block. Frames claiming to decode to more than `content_size` bytes are
+/// rejected.
+fn decode_lz4(data: &[u8], content_size: usize) -> io::Result<Vec<u8>> {
+    let mut reader = Cursor::new(data);
+    let header_version = reader.read_u8()?;
+    if header_version != 1 {
+        return Err(Error::other(format!(
+            "Unsupported LZ4 header version: {header_version}"
+        )));
+    }
+    let decoded_size = reader.read_u64::<BigEndian>()?;
+    if decoded_size > content_size as u64 {
+        return Err(Error::other(format!(
+            "LZ4 decoded size {decoded_size} exceeds the frame size {content_size}"
+        )));
+    }
+    let decoded_size = decoded_size as usize;
+    let block_shift = reader.read_u8()?;
+    if block_shift >= 32 {
+        return Err(Error::other(format!(
+            "Invalid LZ4 block shift: {block_shift}"
+        )));
+    }
+    let chunk_size = 1usize << block_shift;
+
+    let mut input = &data[reader.position() as usize..];
+    let mut decoded = Vec::with_capacity(decoded_size);
+    while decoded.len() < decoded_size {
+        let expected = chunk_size.min(decoded_size - decoded.len());
+        let consumed = decode_lz4_block(input, expected, &mut decoded)?;
+        input = &input[consumed..];
+    }
+    Ok(decoded)
+}
+
+/// Decodes a single LZ4 block that decodes to `expected` bytes, appending it to `output`.
+///
+/// Blocks are not length prefixed, so decoding stops once the expected number of bytes has
+/// been produced. Returns the number of input bytes consumed.
+fn decode_lz4_block(input: &[u8], expected: usize, output: &mut Vec<u8>) -> io::Result<usize> {
+    let invalid = || Error::other("Invalid LZ4 block");
+    let start = output.len();
+    let end = start + expected;
+    let mut position = 0;
+
+    let read_length = |position: &mut usize, mut length: usize| -> io::Result<usize> {
+        if length == 15 {
+            loop {
+                let byte = *input.get(*position).ok_or_else(invalid)?;
+                *position += 1;
+                length += byte as usize;
+                if byte != 255 {
+                    break;
+                }
+            }
+        }
+        Ok(length)
+    };
+
+    while output.len() < end {
+        let token = *input.get(position).ok_or_else(invalid)?;
+        position += 1;
+
+        let literal_length = read_length(&mut position, (token >> 4) as usize)?;
+        let literals = input
+            .get(position..position + literal_length)
+            .ok_or_else(invalid)?;
+        output.extend_from_slice(literals);
+        position += literal_length;
+
+        // The last sequence of a block only carries literals
+        if output.len() >= end {
+            break;
+        }
+
+        let offset = input
+            .get(position..position + 2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]) as usize)
+            .ok_or_else(invalid)?;
+        position += 2;
+        if offset == 0 || offset > output.len() - start {
+            return Err(invalid());
+        }
+
+        let match_length = read_length(&mut position, (token & 0x0F) as usize)? + 4;
+        let match_start = output.len() - offset;
+        // Matches may overlap the bytes they produce, so copy byte by byte
+        for i in 0..match_length {
+            let byte = output[match_start + i];
+            output.push(byte);
+        }
+    }
+
+    if output.len() != end {
+        return Err(invalid());
+    }
+    Ok(position)
+}
+
+/// Decrypts the payload of an encrypted BLTE frame.
+///
+/// The frame starts with the key name, the IV and the encryption algorithm, followed by the
+/// encrypted data. The IV is extended to 8 bytes and mixed with the frame index.
+fn decrypt_frame(
+    data: &[u8],
+    frame_index: u32,
+    key_registry: Option<&TactKeyRegistry>,
+) -> io::Result<Vec<u8>> {
+    let invalid = || Error::other("Invalid encrypted Block Table frame");
+
+    let key_name_size = *data.first().ok_or_else(invalid)? as usize;
+    if key_name_size != 8 {
+        return Err(invalid());
+    }
+    let key_name_bytes = data.get(1..1 + key_name_size).ok_or_else(invalid)?;
+    let key_name = u64::from_le_bytes(key_name_bytes.try_into().map_err(|_| invalid())?);
+
+    let iv_offset = 1 + key_name_size;
+    let iv_size = *data.get(iv_offset).ok_or_else(invalid)? as usize;
+    if iv_size > 8 {
+        return Err(invalid());
+    }
+    let iv_bytes = data
+        .get(iv_offset + 1..iv_offset + 1 + iv_size)
+        .ok_or_else(invalid)?;
+    let algorithm = *data.get(iv_offset + 1 + iv_size).ok_or_else(invalid)?;
+    let mut payload = data[iv_offset + 2 + iv_size..].to_vec();
+
+    let mut iv = [0u8; 8];
+    iv[..iv_size].copy_from_slice(iv_bytes);
+    for (i, byte) in iv.iter_mut().take(4).enumerate() {
+        *byte ^= (frame_index >> (i * 8)) as u8;
+    }
+
+    let key = key_registry
+        .and_then(|registry| registry.get_key(key_name))
+        .ok_or_else(|| Error::other(CascError::MissingKey(key_name)))?;
+
+    match algorithm {
+        ENCRYPTION_SALSA20 => {
+            let mut cipher = Salsa20::new(key, &iv).ok_or_else(invalid)?;
+            cipher.apply_keystream(&mut payload);
+        }
+        ENCRYPTION_ARC4 => {
+            // ARC4 is keyed with the key followed by the IV
+            let mut arc4_key = key.to_vec();
+            arc4_key.extend_from_slice(&iv);
+            let mut cipher = Arc4::new(&arc4_key).ok_or_else(invalid)?;
+            cipher.apply_keystream(&mut payload);
+        }
+        other => {
+            return Err(Error::other(format!(
+                "Unsupported encryption algorithm: {other:#X}"
+            )))
+        }
+    }
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key_registry() -> TactKeyRegistry {
+        let mut key_registry = TactKeyRegistry::new();
+        let key = hex::decode("BDC51862ABED79B2DE48C8E7E66C6200").unwrap();
+        key_registry.add_key(0xFA505078126ACB3E, key.try_into().unwrap());
+        key_registry
+    }
+
+    /// An LZ4 frame of `ababababababababxyz` in 16 byte chunks: a literal followed by an
+    /// overlapping match, then a chunk of literals only.
+    const LZ4_FRAME: [u8; 20] = [
+        b'4', 1, 0, 0, 0, 0, 0, 0, 0, 19, 4, 0x2A, b'a', b'b', 2, 0, 0x30, b'x', b'y', b'z',
+    ];
+
+    /// Builds a BLTE blob with a frame table from already encoded frames.
+    fn blte(frames: &[(&[u8], usize)]) -> Vec<u8> {
+        let mut data = b"BLTE".to_vec();
+        data.extend_from_slice(&(12 + 24 * frames.len() as u32).to_be_bytes());
+        data.push(0x0F);
+        data.extend_from_slice(&(frames.len() as u32).to_be_bytes()[1..]);
+        for (encoded, content_size) in frames {
+            data.extend_from_slice(&(encoded.len() as u32).to_be_bytes());
+            data.extend_from_slice(&(*content_size as u32).to_be_bytes());
+            data.extend_from_slice(&[0; 16]);
+        }
+        for (encoded, _) in frames {
+            data.extend_from_slice(encoded);
+        }
+        data
+    }
+
+    #[test]
+    fn decodes_lz4_frames() {
+        let decoded = decode_frame(&LZ4_FRAME, 0, 19, None).unwrap();
+        assert_eq!(decoded, b"ababababababababxyz");
+
+        // The decoded size may not exceed the size recorded for the frame
+        assert!(decode_frame(&LZ4_FRAME, 0, 18, None).is_err());
+        let mut huge = LZ4_FRAME;
+        huge[2] = 0xFF;
+        assert!(decode_frame(&huge, 0, 19, None).is_err());
+        // Nor may the blocks end early
+        assert!(decode_frame(&LZ4_FRAME[..18], 0, 19, None).is_err());
+    }
+
+    #[test]
+    fn bounds_zlib_frames() {
+        use flate2::{write::ZlibEncoder, Compression};
+        use std::io::Write;
+
+        let mut encoder = ZlibEncoder::new(vec![b'Z'], Compression::default());
+        encoder.write_all(&[0; 0x10000]).unwrap();
+        let frame = encoder.finish().unwrap();
+        assert_eq!(
+            decode_frame(&frame, 0, 0x10000, None).unwrap().len(),
+            0x10000
+        );
+
+        // The decoded size may not exceed the size recorded for the frame, also when nested
+        assert!(decode_frame(&frame, 0, 0xFFFF, None).is_err());
+        let mut nested = vec![b'F'];
+        nested.extend(blte(&[(&frame, 16)]));
+        assert!(decode_frame(&nested, 0, 0x10000, None).is_err());
+    }
+
+    #[test]
+    fn decodes_nested_blte_frames() {
+        let mut frame = vec![b'F'];
+        frame.extend(blte(&[(b"Nhello, ", 7), (&LZ4_FRAME, 19)]));
+        let decoded = decode_frame(&frame, 0, 26, None).unwrap();
+        assert_eq!(decoded, b"hello, ababababababababxyz");
+        // The nested frames may not decode to more than the outer frame
+        assert!(decode_frame(&frame, 0, 25, None).is_err());
+
+        // Without a frame table the nested blob is a single frame
+        let mut frame = b"FBLTE\0\0\0\0".to_vec();
+        frame.extend_from_slice(&LZ4_FRAME);
+        let decoded = decode_frame(&frame, 0, 19, None).unwrap();
+        assert_eq!(decoded, b"ababababababababxyz");
+    }
+
+    #[test]
+    fn decrypts_encrypted_frames() {
+        // Key name FA505078126ACB3E, IV 01020304, wrapping the raw frame
+        // `Nencrypted frame contents`
+        let salsa20 = hex::decode(
+            "45083ecb6a12785050fa0401020304538ccc4072ed958aa1c9dfd72eccb414b3578f0d21932def029a",
+        )
+        .unwrap();
+        let arc4 = hex::decode(
+            "45083ecb6a12785050fa040102030441e84e749de068e1427a800b9200019c1eaf09faaef58ab9bc45",
+        )
+        .unwrap();
+        let registry = key_registry();
+
+        // The Salsa20 frame was encrypted as the second frame of its blob
+        let decoded = decode_frame(&salsa20, 1, 24, Some(&registry)).unwrap();
+        assert_eq!(decoded, b"encrypted frame contents");
+        assert_ne!(
+            decode_frame(&salsa20, 0, 24, Some(&registry)).ok(),
+            Some(decoded)
+        );
+        let decoded = decode_frame(&arc4, 0, 24, Some(&registry)).unwrap();
+        assert_eq!(decoded, b"encrypted frame contents");
+
+        let error = decode_frame(&arc4, 0, 24, None).unwrap_err();
+        match error.downcast::<CascError>() {
+            Ok(CascError::MissingKey(name)) => assert_eq!(name, 0xFA505078126ACB3E),
+            other => panic!("Expected a missing key error, got {other:?}"),
+        }
+    }
+}
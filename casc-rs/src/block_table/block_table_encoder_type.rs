@@ -3,13 +3,17 @@
 /// This enum describes how the data in a block table entry is stored or compressed
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
-pub(crate) enum BlockTableEncoderType {
+pub enum BlockTableEncoderType {
     /// Plain raw data, uncompressed and unencrypted.
     Raw = 0x4E,
     /// Zlib compressed data.
     ZLib = 0x5A,
     /// Encrypted data.
     Encrypted = 0x45,
+    /// LZ4 compressed data, split into fixed size chunks.
+    Lz4 = 0x34,
+    /// Nested BLTE data, with its own frame table.
+    Recursive = 0x46,
     /// Unknown or unsupported type, stores the raw byte value.
     Unknown(u8),
 }
@@ -20,6 +24,8 @@ impl From<u8> for BlockTableEncoderType {
             0x4E => BlockTableEncoderType::Raw,
             0x5A => BlockTableEncoderType::ZLib,
             0x45 => BlockTableEncoderType::Encrypted,
+            0x34 => BlockTableEncoderType::Lz4,
+            0x46 => BlockTableEncoderType::Recursive,
             other => BlockTableEncoderType::Unknown(other),
         }
     }
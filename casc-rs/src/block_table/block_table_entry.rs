@@ -12,3 +12,13 @@ pub(crate) struct BlockTableEntry {
     /// Upper 64 bits of the block's hash.
     pub(crate) hash_upper: u64,
 }
+
+impl BlockTableEntry {
+    /// Returns the MD5 hash of the encoded block, in the byte order it is stored in.
+    pub(crate) fn hash(&self) -> [u8; 16] {
+        let mut hash = [0u8; 16];
+        hash[..8].copy_from_slice(&self.hash_lower.to_ne_bytes());
+        hash[8..].copy_from_slice(&self.hash_upper.to_ne_bytes());
+        hash
+    }
+}
@@ -41,6 +41,11 @@ impl CascConfig {
         self.variables.get(var_name)
     }
 
+    /// Returns an iterator over all variables in the configuration.
+    pub(crate) fn variables(&self) -> impl Iterator<Item = &Variable> {
+        self.variables.values()
+    }
+
     /// Loads configuration variables from a file.
     ///
     /// # Arguments
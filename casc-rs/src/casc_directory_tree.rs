@@ -0,0 +1,375 @@
+//! Module for browsing the files of a CASC storage as a directory tree.
+//!
+//! Roots such as TVFS store their files in a nested path table, but the storage lists them as
+//! flat names. [`CascDirectoryTree`] rebuilds the folders from those names, splitting on both
+//! `\` and `/`, so a storage can be browsed folder by folder.
+use crate::casc_file_info::CascFileInfo;
+use crate::casc_path::PathNormalizer;
+use std::collections::HashMap;
+
+/// The kind of an entry in a [`CascDirectoryTree`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CascDirEntryKind {
+    /// A folder, containing other entries.
+    Directory,
+    /// A file, which can be opened by its path.
+    File,
+}
+
+/// An entry in a [`CascDirectoryTree`], either a folder or a file.
+#[derive(Debug, Clone)]
+pub struct CascDirEntry {
+    /// The name of the entry, without its parent folders.
+    name: String,
+    /// The full path of the entry, as stored by the root.
+    path: String,
+    /// Whether the entry is a folder or a file.
+    kind: CascDirEntryKind,
+    /// The decoded size of the file, or of all files below the folder, in bytes.
+    content_size: u64,
+    /// The encoded size of the file, or of all files below the folder, in bytes.
+    encoded_size: u64,
+    /// The number of files below the folder, or one for a file.
+    file_count: usize,
+    /// The index of the parent folder, or `None` for the root.
+    parent: Option<usize>,
+    /// The indices of the entries directly within the folder, folders first and then sorted by
+    /// name.
+    children: Vec<usize>,
+}
+
+impl CascDirEntry {
+    fn new(name: &str, path: &str, kind: CascDirEntryKind, parent: Option<usize>) -> Self {
+        Self {
+            name: name.to_string(),
+            path: path.to_string(),
+            kind,
+            content_size: 0,
+            encoded_size: 0,
+            file_count: 0,
+            parent,
+            children: Vec::new(),
+        }
+    }
+
+    /// Returns the name of the entry, without its parent folders.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the full path of the entry.
+    ///
+    /// For files this is the name the file is opened by. The root folder has an empty path.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns whether the entry is a folder or a file.
+    pub fn kind(&self) -> CascDirEntryKind {
+        self.kind
+    }
+
+    /// Returns whether the entry is a folder.
+    pub fn is_dir(&self) -> bool {
+        self.kind == CascDirEntryKind::Directory
+    }
+
+    /// Returns whether the entry is a file.
+    pub fn is_file(&self) -> bool {
+        self.kind == CascDirEntryKind::File
+    }
+
+    /// Returns the decoded size of the file, or the total decoded size of all files below the
+    /// folder, in bytes.
+    pub fn content_size(&self) -> u64 {
+        self.content_size
+    }
+
+    /// Returns the encoded size of the file, or the total encoded size of all files below the
+    /// folder, in bytes.
+    pub fn encoded_size(&self) -> u64 {
+        self.encoded_size
+    }
+
+    /// Returns the number of files below the folder, or one for a file.
+    pub fn file_count(&self) -> usize {
+        self.file_count
+    }
+}
+
+/// The files of a storage, arranged into folders.
+#[derive(Debug, Clone)]
+pub struct CascDirectoryTree {
+    /// All entries, with the root folder first.
+    entries: Vec<CascDirEntry>,
+    /// Folder indices, keyed by normalised path.
+    directories: HashMap<String, usize>,
+    /// File indices, keyed by normalised path.
+    ///
+    /// Folders and files are kept apart, as a root may hold a file with the same path as a
+    /// folder.
+    files: HashMap<String, usize>,
+    /// The normaliser used to build the path keys.
+    normalizer: PathNormalizer,
+}
+
+impl CascDirectoryTree {
+    /// The index of the root folder.
+    const ROOT: usize = 0;
+
+    /// Builds the tree from the files of a storage.
+    pub(crate) fn new(files: &[CascFileInfo], normalizer: PathNormalizer) -> Self {
+        let mut tree = Self {
+            entries: vec![CascDirEntry::new("", "", CascDirEntryKind::Directory, None)],
+            directories: HashMap::new(),
+            files: HashMap::new(),
+            normalizer,
+        };
+        tree.directories.insert(String::new(), Self::ROOT);
+
+        for file in files {
+            tree.add_file(file);
+        }
+
+        // Sort children by name, with folders before files
+        let mut entries = std::mem::take(&mut tree.entries);
+        for index in 0..entries.len() {
+            let mut children = std::mem::take(&mut entries[index].children);
+            children.sort_by(|a, b| {
+                let (a, b) = (&entries[*a], &entries[*b]);
+                (a.is_file(), &a.name).cmp(&(b.is_file(), &b.name))
+            });
+            entries[index].children = children;
+        }
+        tree.entries = entries;
+        tree
+    }
+
+    fn add_file(&mut self, file: &CascFileInfo) {
+        let file_name = file.file_name();
+        let mut parent = Self::ROOT;
+        let mut key = String::with_capacity(file_name.len());
+        let mut offset = 0;
+        let mut ancestors = vec![Self::ROOT];
+
+        let mut components = file_name
+            .split(['\\', '/'])
+            .filter(|component| !component.is_empty())
+            .peekable();
+        while let Some(component) = components.next() {
+            // Find the component within the stored name, to keep its separators
+            offset += file_name[offset..].find(component).unwrap_or(0) + component.len();
+            if !key.is_empty() {
+                key.push(self.normalizer.separator);
+            }
+            key.push_str(&self.normalizer.normalize(component));
+
+            let is_file = components.peek().is_none();
+            let index = match self.directories.get(&key) {
+                Some(&index) if !is_file => index,
+                _ => {
+                    let (kind, path, paths) = if is_file {
+                        (CascDirEntryKind::File, file_name, &mut self.files)
+                    } else {
+                        let path = &file_name[..offset];
+                        (CascDirEntryKind::Directory, path, &mut self.directories)
+                    };
+                    let index = self.entries.len();
+                    self.entries
+                        .push(CascDirEntry::new(component, path, kind, Some(parent)));
+                    self.entries[parent].children.push(index);
+                    paths.insert(key.clone(), index);
+                    index
+                }
+            };
+            if !is_file {
+                ancestors.push(index);
+            }
+            parent = index;
+        }
+
+        if parent == Self::ROOT {
+            return;
+        }
+        for index in ancestors.into_iter().chain(std::iter::once(parent)) {
+            let entry = &mut self.entries[index];
+            entry.content_size += file.content_size();
+            entry.encoded_size += file.encoded_size();
+            entry.file_count += 1;
+        }
+    }
+
+    /// Returns the root folder of the tree.
+    pub fn root(&self) -> &CascDirEntry {
+        &self.entries[Self::ROOT]
+    }
+
+    /// Returns the entry at the given path, if any.
+    ///
+    /// The path is normalised with the storage's [`PathNormalizer`], so both `\` and `/` are
+    /// accepted as separators and case is ignored by default. An empty path names the root folder.
+    /// If a folder and a file share the path, the folder is returned; see
+    /// [`CascDirectoryTree::get_file`].
+    pub fn get(&self, path: &str) -> Option<&CascDirEntry> {
+        let key = self.normalizer.normalize(path);
+        self.directories
+            .get(&key)
+            .or_else(|| self.files.get(&key))
+            .map(|&index| &self.entries[index])
+    }
+
+    /// Returns the file at the given path, if any, even if a folder shares the path.
+    pub fn get_file(&self, path: &str) -> Option<&CascDirEntry> {
+        let key = self.normalizer.normalize(path);
+        self.files.get(&key).map(|&index| &self.entries[index])
+    }
+
+    /// Returns the parent folder of an entry, or `None` for the root.
+    pub fn parent(&self, entry: &CascDirEntry) -> Option<&CascDirEntry> {
+        entry.parent.map(|index| &self.entries[index])
+    }
+
+    /// Returns the entries directly within a folder, folders first and then sorted by name.
+    ///
+    /// Files have no children.
+    pub fn children<'a>(
+        &'a self,
+        entry: &'a CascDirEntry,
+    ) -> impl Iterator<Item = &'a CascDirEntry> + 'a {
+        entry.children.iter().map(|&index| &self.entries[index])
+    }
+
+    /// Returns an iterator over every entry below the root, depth first, with each folder
+    /// before its contents.
+    pub fn walk(&self) -> CascWalk<'_> {
+        self.walk_from(self.root())
+    }
+
+    /// Returns an iterator over every entry below the given folder, depth first.
+    pub fn walk_from(&self, entry: &CascDirEntry) -> CascWalk<'_> {
+        CascWalk {
+            tree: self,
+            stack: entry.children.iter().rev().copied().collect(),
+        }
+    }
+
+    /// Returns the number of entries in the tree, including the root folder.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether the tree contains nothing but the root folder.
+    pub fn is_empty(&self) -> bool {
+        self.entries.len() == 1
+    }
+}
+
+/// A depth-first iterator over the entries of a [`CascDirectoryTree`].
+///
+/// Returned by [`CascDirectoryTree::walk`] and [`CascDirectoryTree::walk_from`].
+pub struct CascWalk<'a> {
+    /// The tree being walked.
+    tree: &'a CascDirectoryTree,
+    /// The entries still to visit, with the next one last.
+    stack: Vec<usize>,
+}
+
+impl<'a> Iterator for CascWalk<'a> {
+    type Item = &'a CascDirEntry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = &self.tree.entries[self.stack.pop()?];
+        self.stack.extend(entry.children.iter().rev());
+        Some(entry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree(names: &[&str]) -> CascDirectoryTree {
+        let files = names
+            .iter()
+            .map(|name| CascFileInfo::new(name.to_string(), 10, 4, true))
+            .collect::<Vec<_>>();
+        CascDirectoryTree::new(&files, PathNormalizer::new())
+    }
+
+    fn paths<'a>(entries: impl Iterator<Item = &'a CascDirEntry>) -> Vec<&'a str> {
+        entries.map(CascDirEntry::path).collect()
+    }
+
+    #[test]
+    fn nests_files_into_folders() {
+        let tree = tree(&[
+            "Data\\Maps/a.map",
+            "data/maps\\b.map",
+            "data\\c.txt",
+            "d.txt",
+        ]);
+
+        let data = tree.get("DATA").unwrap();
+        assert!(data.is_dir());
+        assert_eq!(data.path(), "Data");
+        assert_eq!(
+            (data.file_count(), data.content_size(), data.encoded_size()),
+            (3, 30, 12)
+        );
+        let maps = tree.get("data/maps").unwrap();
+        assert_eq!(maps.file_count(), 2);
+        assert_eq!(tree.parent(maps).unwrap().path(), "Data");
+
+        let file = tree.get("data\\maps\\B.MAP").unwrap();
+        assert!(file.is_file());
+        assert_eq!(file.name(), "b.map");
+        assert_eq!(file.path(), "data/maps\\b.map");
+        assert_eq!(tree.root().file_count(), 4);
+        assert_eq!(tree.len(), 7);
+    }
+
+    #[test]
+    fn keeps_files_and_folders_sharing_a_path() {
+        for names in [["a", "a\\b.txt"], ["a\\b.txt", "a"]] {
+            let tree = tree(&names);
+            assert_eq!(paths(tree.walk()), ["a", "a\\b.txt", "a"]);
+
+            let folder = tree.get("a").unwrap();
+            assert!(folder.is_dir());
+            assert_eq!(folder.file_count(), 1);
+            let file = tree.get_file("a").unwrap();
+            assert!(file.is_file());
+            assert!(tree.children(file).next().is_none());
+            let nested = tree.get("a\\b.txt").unwrap();
+            assert!(tree.parent(nested).unwrap().is_dir());
+        }
+    }
+
+    #[test]
+    fn lists_folders_before_files_by_name() {
+        let tree = tree(&["z.txt", "b\\x.txt", "a.txt", "c\\y.txt", "b\\a\\w.txt"]);
+
+        assert_eq!(
+            paths(tree.children(tree.root())),
+            ["b", "c", "a.txt", "z.txt"]
+        );
+        assert_eq!(
+            paths(tree.walk()),
+            [
+                "b",
+                "b\\a",
+                "b\\a\\w.txt",
+                "b\\x.txt",
+                "c",
+                "c\\y.txt",
+                "a.txt",
+                "z.txt"
+            ]
+        );
+        let b = tree.get("b").unwrap();
+        assert_eq!(
+            paths(tree.walk_from(b)),
+            ["b\\a", "b\\a\\w.txt", "b\\x.txt"]
+        );
+    }
+}
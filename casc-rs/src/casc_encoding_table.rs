@@ -0,0 +1,320 @@
+use crate::casc_key_mapping_table::encoding_key_to_base64;
+use crate::error::CascError;
+/// Module for handling the CASC ENCODING file, which maps content keys to encoding keys.
+///
+/// The ENCODING file is referenced by the `encoding` entry of the build config. It is made up
+/// of content key pages (CKey -> EKeys), an ESpec string table, and encoding key spec pages
+/// (EKey -> ESpec and encoded size). Every manifest other than TVFS addresses its files by
+/// content key, so this table is required to resolve them to data in the key mapping tables.
+use base64::prelude::*;
+use byteorder::{BigEndian, ReadBytesExt};
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Seek, SeekFrom};
+
+/// Represents the header of the ENCODING file.
+#[derive(Debug, Clone)]
+pub struct CascEncodingHeader {
+    /// The signature identifying the file (`EN`).
+    pub signature: u16,
+    /// The format version of the file.
+    pub version: u8,
+    /// The length in bytes of content keys.
+    pub content_key_size: u8,
+    /// The length in bytes of encoding keys.
+    pub encoding_key_size: u8,
+    /// The size of a content key page in kilobytes.
+    pub content_key_page_size_kb: u16,
+    /// The size of an encoding key spec page in kilobytes.
+    pub encoding_key_page_size_kb: u16,
+    /// The number of content key pages.
+    pub content_key_page_count: u32,
+    /// The number of encoding key spec pages.
+    pub encoding_key_page_count: u32,
+    /// The size in bytes of the ESpec string table.
+    pub espec_block_size: u32,
+}
+
+impl CascEncodingHeader {
+    fn read<R: Read>(reader: &mut R) -> Result<Self, CascError> {
+        let signature = reader.read_u16::<BigEndian>()?;
+        let version = reader.read_u8()?;
+        let content_key_size = reader.read_u8()?;
+        let encoding_key_size = reader.read_u8()?;
+        let content_key_page_size_kb = reader.read_u16::<BigEndian>()?;
+        let encoding_key_page_size_kb = reader.read_u16::<BigEndian>()?;
+        let content_key_page_count = reader.read_u32::<BigEndian>()?;
+        let encoding_key_page_count = reader.read_u32::<BigEndian>()?;
+        let _unknown = reader.read_u8()?;
+        let espec_block_size = reader.read_u32::<BigEndian>()?;
+
+        Ok(Self {
+            signature,
+            version,
+            content_key_size,
+            encoding_key_size,
+            content_key_page_size_kb,
+            encoding_key_page_size_kb,
+            content_key_page_count,
+            encoding_key_page_count,
+            espec_block_size,
+        })
+    }
+}
+
+/// Represents a content key entry in the ENCODING file.
+///
+/// Each entry maps a content key to one or more encoding keys, along with the decoded size.
+#[derive(Debug, Clone)]
+pub struct CascEncodingEntry {
+    /// The content key (MD5 of the decoded file data).
+    pub content_key: Vec<u8>,
+    /// The encoding keys the content is stored under.
+    pub encoding_keys: Vec<Vec<u8>>,
+    /// The decoded size of the content in bytes.
+    pub content_size: u64,
+}
+
+/// Represents an encoding key spec entry in the ENCODING file.
+///
+/// Each entry describes how the data stored under an encoding key was encoded.
+#[derive(Debug, Clone)]
+pub struct CascEncodingSpec {
+    /// The encoding key.
+    pub encoding_key: Vec<u8>,
+    /// The index of the ESpec string in the ESpec table.
+    pub espec_index: u32,
+    /// The encoded size of the data in bytes.
+    pub encoded_size: u64,
+}
+
+/// Represents the parsed ENCODING file of a CASC storage.
+///
+/// Provides lookups from content keys to encoding keys, and from encoding keys to their
+/// ESpec and encoded size.
+#[derive(Debug)]
+pub struct CascEncodingTable {
+    /// The header of the ENCODING file.
+    header: CascEncodingHeader,
+    /// The ESpec strings, indexed by ESpec index.
+    especs: Vec<String>,
+    /// Content key entries, keyed by the base64-encoded content key.
+    content_entries: HashMap<String, CascEncodingEntry>,
+    /// Encoding key spec entries, keyed by the base64-encoded truncated encoding key.
+    encoding_specs: HashMap<String, CascEncodingSpec>,
+    /// Base64-encoded content keys, keyed by the base64-encoded truncated encoding key.
+    content_keys: HashMap<String, String>,
+}
+
+impl CascEncodingTable {
+    /// Parses an ENCODING file from the given stream.
+    pub fn new<R: Read + Seek>(stream: &mut R) -> Result<Self, CascError> {
+        stream.seek(SeekFrom::Start(0))?;
+        let mut data = Vec::new();
+        stream.read_to_end(&mut data)?;
+        let mut reader = Cursor::new(data);
+
+        let header = CascEncodingHeader::read(&mut reader)?;
+        if header.signature != 0x454E {
+            return Err(CascError::InvalidData(format!(
+                "Invalid ENCODING signature: {:#X}",
+                header.signature
+            )));
+        }
+        if header.version != 1 {
+            return Err(CascError::UnsupportedFileType(format!(
+                "Unsupported ENCODING version: {}",
+                header.version
+            )));
+        }
+
+        // ESpec string table, a block of null terminated strings
+        let mut espec_block = vec![0u8; header.espec_block_size as usize];
+        reader.read_exact(&mut espec_block)?;
+        let mut especs = espec_block
+            .split(|b| *b == 0)
+            .map(|s| String::from_utf8_lossy(s).into_owned())
+            .collect::<Vec<_>>();
+        if especs.last().is_some_and(|s| s.is_empty()) {
+            especs.pop();
+        }
+
+        let ckey_size = header.content_key_size as usize;
+        let ekey_size = header.encoding_key_size as usize;
+
+        // Skip the content key page index (first key + page MD5 per page)
+        let ckey_pages_start =
+            reader.position() + (header.content_key_page_count as u64) * (ckey_size as u64 + 16);
+        let ckey_page_size = header.content_key_page_size_kb as u64 * 1024;
+        let mut content_entries = HashMap::new();
+        let mut content_keys = HashMap::new();
+
+        for page in 0..header.content_key_page_count as u64 {
+            let page_start = ckey_pages_start + page * ckey_page_size;
+            let page_end = page_start + ckey_page_size;
+            reader.set_position(page_start);
+
+            while reader.position() + 6 + ckey_size as u64 <= page_end {
+                let key_count = reader.read_u8()?;
+                if key_count == 0 {
+                    break;
+                }
+                let content_size = reader.read_uint::<BigEndian>(5)?;
+                let mut content_key = vec![0u8; ckey_size];
+                reader.read_exact(&mut content_key)?;
+                let mut encoding_keys = Vec::with_capacity(key_count as usize);
+                for _ in 0..key_count {
+                    let mut encoding_key = vec![0u8; ekey_size];
+                    reader.read_exact(&mut encoding_key)?;
+                    encoding_keys.push(encoding_key);
+                }
+                let entry = CascEncodingEntry {
+                    content_key,
+                    encoding_keys,
+                    content_size,
+                };
+                let base64_content_key = BASE64_STANDARD.encode(&entry.content_key);
+                for encoding_key in &entry.encoding_keys {
+                    content_keys.insert(
+                        encoding_key_to_base64(encoding_key),
+                        base64_content_key.clone(),
+                    );
+                }
+                content_entries.insert(base64_content_key, entry);
+            }
+        }
+
+        // Skip the encoding key page index in the same way
+        let ekey_index_start =
+            ckey_pages_start + header.content_key_page_count as u64 * ckey_page_size;
+        let ekey_pages_start =
+            ekey_index_start + (header.encoding_key_page_count as u64) * (ekey_size as u64 + 16);
+        let ekey_page_size = header.encoding_key_page_size_kb as u64 * 1024;
+        let ekey_entry_size = ekey_size as u64 + 4 + 5;
+        let mut encoding_specs = HashMap::new();
+
+        for page in 0..header.encoding_key_page_count as u64 {
+            let page_start = ekey_pages_start + page * ekey_page_size;
+            let page_end = page_start + ekey_page_size;
+            reader.set_position(page_start);
+
+            while reader.position() + ekey_entry_size <= page_end {
+                let mut encoding_key = vec![0u8; ekey_size];
+                reader.read_exact(&mut encoding_key)?;
+                let espec_index = reader.read_u32::<BigEndian>()?;
+                let encoded_size = reader.read_uint::<BigEndian>(5)?;
+                // Pages are padded out with empty entries
+                if espec_index == u32::MAX || encoding_key.iter().all(|b| *b == 0) {
+                    break;
+                }
+                let spec = CascEncodingSpec {
+                    encoding_key,
+                    espec_index,
+                    encoded_size,
+                };
+                encoding_specs.insert(encoding_key_to_base64(&spec.encoding_key), spec);
+            }
+        }
+
+        Ok(CascEncodingTable {
+            header,
+            especs,
+            content_entries,
+            encoding_specs,
+            content_keys,
+        })
+    }
+
+    /// Returns the header of the ENCODING file.
+    pub fn header(&self) -> &CascEncodingHeader {
+        &self.header
+    }
+
+    /// Returns the number of content keys in the table.
+    pub fn len(&self) -> usize {
+        self.content_entries.len()
+    }
+
+    /// Returns whether the table contains no content keys.
+    pub fn is_empty(&self) -> bool {
+        self.content_entries.is_empty()
+    }
+
+    /// Returns the entry for the given content key, if present.
+    pub fn get_content_entry(&self, content_key: &[u8]) -> Option<&CascEncodingEntry> {
+        self.content_entries
+            .get(&BASE64_STANDARD.encode(content_key))
+    }
+
+    /// Returns the encoding keys the given content key is stored under, if present.
+    pub fn get_encoding_keys(&self, content_key: &[u8]) -> Option<&[Vec<u8>]> {
+        self.get_content_entry(content_key)
+            .map(|entry| entry.encoding_keys.as_slice())
+    }
+
+    /// Returns the entry whose content is stored under the given encoding key, if present.
+    ///
+    /// This is the reverse of [`CascEncodingTable::get_encoding_keys`], and gives the content
+    /// key (MD5 of the decoded data) of an encoded blob.
+    pub fn get_entry_by_encoding_key(&self, encoding_key: &[u8]) -> Option<&CascEncodingEntry> {
+        self.content_keys
+            .get(&encoding_key_to_base64(encoding_key))
+            .and_then(|content_key| self.content_entries.get(content_key))
+    }
+
+    /// Returns the encoding key spec entry for the given encoding key, if present.
+    pub fn get_encoding_spec(&self, encoding_key: &[u8]) -> Option<&CascEncodingSpec> {
+        self.encoding_specs
+            .get(&encoding_key_to_base64(encoding_key))
+    }
+
+    /// Returns the ESpec string at the given index, if present.
+    pub fn get_espec(&self, index: u32) -> Option<&str> {
+        self.especs.get(index as usize).map(|s| s.as_str())
+    }
+
+    /// Returns an iterator over all content key entries in the table.
+    pub fn content_entries(&self) -> impl Iterator<Item = &CascEncodingEntry> {
+        self.content_entries.values()
+    }
+}
+
+#[cfg(test)]
+impl CascEncodingTable {
+    /// Builds a table from content keys, each stored under a single encoding key with the
+    /// given decoded size.
+    pub(crate) fn from_entries(entries: &[([u8; 16], [u8; 16], u64)]) -> Self {
+        let mut table = CascEncodingTable {
+            header: CascEncodingHeader {
+                signature: 0x454E,
+                version: 1,
+                content_key_size: 16,
+                encoding_key_size: 16,
+                content_key_page_size_kb: 4,
+                encoding_key_page_size_kb: 4,
+                content_key_page_count: 0,
+                encoding_key_page_count: 0,
+                espec_block_size: 0,
+            },
+            especs: Vec::new(),
+            content_entries: HashMap::new(),
+            encoding_specs: HashMap::new(),
+            content_keys: HashMap::new(),
+        };
+        for (content_key, encoding_key, content_size) in entries {
+            let base64_content_key = BASE64_STANDARD.encode(content_key);
+            table.content_keys.insert(
+                encoding_key_to_base64(encoding_key),
+                base64_content_key.clone(),
+            );
+            table.content_entries.insert(
+                base64_content_key,
+                CascEncodingEntry {
+                    content_key: content_key.to_vec(),
+                    encoding_keys: vec![encoding_key.to_vec()],
+                    content_size: *content_size,
+                },
+            );
+        }
+        table
+    }
+}
@@ -1,9 +1,12 @@
-use crate::block_table::block_table_encoder_type::BlockTableEncoderType;
+use crate::block_table::block_table_decoder::decode_frame;
 use crate::casc_file_span::CascFileSpan;
-use flate2::read::ZlibDecoder;
+use crate::error::CascError;
+use crate::tact_key_registry::TactKeyRegistry;
+use md5::{Digest, Md5};
 use std::{
     fs::File,
     io::{self, Error, ErrorKind, Read, Seek, SeekFrom},
+    sync::Arc,
 };
 
 /// This struct manages reading, seeking, and caching data from multiple file spans,
@@ -23,6 +26,10 @@ pub struct CascFile {
     cache_start_position: u64,
     /// The end position of the cache.
     cache_end_position: u64,
+    /// The keys used to decrypt encrypted frames.
+    key_registry: Option<Arc<TactKeyRegistry>>,
+    /// Whether to verify the MD5 hash of each frame before decoding it.
+    verify: bool,
 }
 
 impl CascFile {
@@ -36,9 +43,25 @@ impl CascFile {
             cache: None,
             cache_start_position: 0,
             cache_end_position: 0,
+            key_registry: None,
+            verify: false,
         }
     }
 
+    /// Sets the keys used to decrypt encrypted frames.
+    pub(crate) fn set_key_registry(&mut self, key_registry: Option<Arc<TactKeyRegistry>>) {
+        self.key_registry = key_registry;
+    }
+
+    /// Enables or disables frame verification.
+    ///
+    /// When enabled, each encoded frame is checked against the MD5 hash stored in its block
+    /// table before it is decoded. A mismatch fails the read with an [`io::Error`] wrapping
+    /// [`CascError::FrameHashMismatch`], naming the frame index and archive offset.
+    pub fn verify(&mut self, verify: bool) {
+        self.verify = verify;
+    }
+
     /// Returns the total size of the file.
     pub fn size(&self) -> u64 {
         self.internal_size
@@ -107,26 +130,33 @@ impl Read for CascFile {
             let mut span_reader = span.span_reader.try_clone()?;
             span_reader.seek(SeekFrom::Start(frame.archive_offset))?;
             self.cache_start_position = frame.virtual_start_offset;
-            self.cache_end_position = self.cache_start_position + frame.content_size as u64;
-            let mut type_buf = [0u8; 1];
-            span_reader.read_exact(&mut type_buf)?;
-            let block_type = BlockTableEncoderType::from(type_buf[0]);
-            self.cache = Some(match block_type {
-                BlockTableEncoderType::Raw => {
-                    let mut cache = vec![0u8; frame.content_size as usize];
-                    span_reader.read_exact(&mut cache)?;
-                    cache
-                }
-                BlockTableEncoderType::ZLib => {
-                    let mut encoded = vec![0u8; frame.encoded_size as usize - 1];
-                    span_reader.read_exact(&mut encoded)?;
-                    let mut decoder = ZlibDecoder::new(&encoded[..]);
-                    let mut cache = Vec::with_capacity(frame.content_size as usize);
-                    decoder.read_to_end(&mut cache)?;
-                    cache
-                }
-                _ => return Err(Error::other("Unsupported Block Table Type")),
-            });
+            self.cache_end_position = frame.virtual_end_offset;
+            let mut encoded = vec![0u8; frame.encoded_size as usize];
+            span_reader.read_exact(&mut encoded)?;
+            if self.verify && Md5::digest(&encoded).as_slice() != frame.hash {
+                // Drop the cache so the mismatch is reported again on the next read
+                self.cache = None;
+                return Err(Error::other(CascError::FrameHashMismatch {
+                    frame_index: frame.index,
+                    offset: frame.archive_offset,
+                }));
+            }
+            let mut decoded = decode_frame(
+                &encoded,
+                frame.index,
+                frame.content_size as usize,
+                self.key_registry.as_deref(),
+            )?;
+            // Only keep the part of the frame referenced by the span
+            let start = frame.content_offset as usize;
+            let end = start + (frame.virtual_end_offset - frame.virtual_start_offset) as usize;
+            if decoded.len() < end {
+                self.cache = None;
+                return Err(Error::other("Decoded frame is smaller than expected"));
+            }
+            decoded.truncate(end);
+            decoded.drain(..start);
+            self.cache = Some(decoded);
         }
         Ok(consumed)
     }
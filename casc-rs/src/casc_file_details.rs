@@ -0,0 +1,88 @@
+//! Module for detailed metadata about a file in a CASC storage.
+//!
+//! [`CascFileDetails`] is returned by [`CascFileInfo::details`](crate::casc_file_info::CascFileInfo::details)
+//! and [`CascStorage::file_details`](crate::casc_storage::CascStorage::file_details). Gathering
+//! it reads the BLTE frame table of every local span, so it is not part of
+//! [`CascFileInfo`](crate::casc_file_info::CascFileInfo) itself.
+use crate::block_table::block_table_encoder_type::BlockTableEncoderType;
+
+/// Detailed metadata about a single span of a file.
+#[derive(Debug, Clone)]
+pub struct CascSpanDetails {
+    /// The content key (MD5 of the decoded data) of the span, if known.
+    pub content_key: Option<Vec<u8>>,
+    /// The encoding key of the span, as recorded by the root.
+    pub encoding_key: Vec<u8>,
+    /// The decoded size of the span in bytes, read from its frame table if neither the root
+    /// nor the ENCODING file records it.
+    pub content_size: u64,
+    /// The decoded size of the whole blob the span references in bytes, if recorded by the
+    /// root. A span may reference only part of its blob.
+    pub blob_size: Option<u64>,
+    /// The size of the span's encoded data in the archive in bytes, or zero if it is not local.
+    pub encoded_size: u64,
+    /// The ESpec string describing how the span is encoded, from the root or the ENCODING
+    /// file, if known.
+    pub espec: Option<String>,
+    /// The index of the data file holding the span, if it is local.
+    pub archive_index: Option<u32>,
+    /// The offset of the span within its data file, if it is local.
+    pub archive_offset: Option<u64>,
+    /// The number of BLTE frames in the span, or zero if it is not local.
+    pub frame_count: u32,
+    /// The distinct BLTE encodings used by the span's frames, in the order first seen.
+    ///
+    /// Encrypted frames are reported as [`BlockTableEncoderType::Encrypted`], whatever
+    /// encoding they use once decrypted.
+    pub encodings: Vec<BlockTableEncoderType>,
+}
+
+/// Detailed metadata about a file in a CASC storage.
+#[derive(Debug, Clone)]
+pub struct CascFileDetails {
+    /// The name of the file.
+    pub file_name: String,
+    /// The content key of the file, if it is made of a single span with a known content key.
+    pub content_key: Option<Vec<u8>>,
+    /// The decoded size of the file in bytes.
+    pub content_size: u64,
+    /// The size of the file's encoded data in the archives in bytes.
+    pub encoded_size: u64,
+    /// The spans that make up the file, in order.
+    pub spans: Vec<CascSpanDetails>,
+}
+
+impl CascFileDetails {
+    /// Returns the number of spans that make up the file.
+    pub fn span_count(&self) -> usize {
+        self.spans.len()
+    }
+
+    /// Returns the total number of BLTE frames across all spans.
+    pub fn frame_count(&self) -> u32 {
+        self.spans.iter().map(|span| span.frame_count).sum()
+    }
+
+    /// Returns the distinct BLTE encodings used across all spans, in the order first seen.
+    pub fn encodings(&self) -> Vec<BlockTableEncoderType> {
+        let mut encodings = Vec::new();
+        for encoding in self.spans.iter().flat_map(|span| &span.encodings) {
+            if !encodings.contains(encoding) {
+                encodings.push(*encoding);
+            }
+        }
+        encodings
+    }
+
+    /// Returns whether any frame of the file is encrypted.
+    pub fn is_encrypted(&self) -> bool {
+        self.spans
+            .iter()
+            .any(|span| span.encodings.contains(&BlockTableEncoderType::Encrypted))
+    }
+
+    /// Returns whether all spans of the file are present in the local archives.
+    pub fn is_local(&self) -> bool {
+        self.spans.iter().all(|span| span.archive_index.is_some())
+    }
+}
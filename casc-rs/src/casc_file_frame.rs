@@ -1,5 +1,7 @@
 /// Represents a frame within a CASC file, describing a segment of file data.
 pub(crate) struct CascFileFrame {
+    /// The index of the frame within its BLTE blob.
+    pub(crate) index: u32,
     /// The virtual start offset of the frame within the file.
     pub(crate) virtual_start_offset: u64,
     /// The virtual end offset of the frame within the file.
@@ -10,4 +12,9 @@ pub(crate) struct CascFileFrame {
     pub(crate) encoded_size: u32,
     /// The decoded (original) content size of the frame.
     pub(crate) content_size: u32,
+    /// The offset within the decoded frame at which the frame's virtual range begins, for
+    /// frames only partially referenced by their span.
+    pub(crate) content_offset: u32,
+    /// The MD5 hash of the encoded frame, from the block table.
+    pub(crate) hash: [u8; 16],
 }
@@ -1,41 +1,81 @@
+use crate::casc_file_details::CascFileDetails;
+use crate::casc_storage::CascStorage;
+use crate::error::CascError;
+use std::borrow::Cow;
+
 /// Represents information about a file in the CASC storage.
 #[derive(Debug)]
 pub struct CascFileInfo {
     /// The name of the file.
     file_name: String,
-    /// The size of the file in bytes.
-    file_size: i64,
+    /// The decoded size of the file in bytes.
+    content_size: u64,
+    /// The size of the file's encoded data in the archives in bytes, including span headers
+    /// and BLTE framing.
+    encoded_size: u64,
     /// Whether the file is local to the storage.
     is_local: bool,
+    /// The FileDataID of the file, if the root identifies files numerically.
+    file_data_id: Option<u32>,
 }
 
 impl CascFileInfo {
-    pub(crate) fn new(file_name: String, file_size: i64, is_local: bool) -> Self {
+    pub(crate) fn new(
+        file_name: String,
+        content_size: u64,
+        encoded_size: u64,
+        is_local: bool,
+    ) -> Self {
         Self {
             file_name,
-            file_size,
+            content_size,
+            encoded_size,
             is_local,
+            file_data_id: None,
         }
     }
 
-    /// Returns the name of the file.
+    /// Returns the name of the file, exactly as stored by the root.
     pub fn file_name(&self) -> &str {
         &self.file_name
     }
 
+    /// Returns the name of the file with `/` separators, as used by listfiles and most tools.
+    pub fn file_path(&self) -> Cow<'_, str> {
+        if self.file_name.contains('\\') {
+            Cow::Owned(self.file_name.replace('\\', "/"))
+        } else {
+            Cow::Borrowed(&self.file_name)
+        }
+    }
+
     /// Sets the name of the file.
     pub(crate) fn set_file_name(&mut self, name: String) {
         self.file_name = name;
     }
 
-    /// Returns the size of the file in bytes.
+    /// Returns the decoded size of the file in bytes.
+    ///
+    /// This is the same as [`CascFileInfo::content_size`], and matches the size of the file
+    /// once opened.
     pub fn file_size(&self) -> i64 {
-        self.file_size
+        self.content_size as i64
+    }
+
+    /// Returns the decoded size of the file in bytes.
+    ///
+    /// This comes from the root or the ENCODING file, so listing a storage never reads its data
+    /// files. Spans described by neither count as zero; [`CascFileInfo::details`] reads their
+    /// frame tables to find the actual size.
+    pub fn content_size(&self) -> u64 {
+        self.content_size
     }
 
-    /// Sets the size of the file in bytes.
-    pub(crate) fn set_file_size(&mut self, size: i64) {
-        self.file_size = size;
+    /// Returns the size of the file's encoded data in the archives in bytes.
+    ///
+    /// This includes span headers and BLTE framing, and is zero if the file is not local.
+    pub fn encoded_size(&self) -> u64 {
+        self.encoded_size
     }
 
     /// Returns whether the file is local to the storage.
@@ -47,4 +87,21 @@ impl CascFileInfo {
     pub(crate) fn set_is_local(&mut self, is_local: bool) {
         self.is_local = is_local;
     }
+
+    /// Returns the FileDataID of the file, if the root identifies files numerically.
+    pub fn file_data_id(&self) -> Option<u32> {
+        self.file_data_id
+    }
+
+    /// Sets the FileDataID of the file.
+    pub(crate) fn set_file_data_id(&mut self, file_data_id: Option<u32>) {
+        self.file_data_id = file_data_id;
+    }
+
+    /// Returns detailed metadata about the file, read from the storage it belongs to.
+    ///
+    /// See [`CascStorage::file_details`].
+    pub fn details(&self, storage: &CascStorage) -> Result<CascFileDetails, CascError> {
+        storage.file_details(&self.file_name)
+    }
 }
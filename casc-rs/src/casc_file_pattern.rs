@@ -0,0 +1,109 @@
+//! Module for matching storage file names against glob patterns and regular expressions.
+//!
+//! [`CascFilePattern`] is used by [`CascStorage::find`](crate::casc_storage::CascStorage::find)
+//! and [`CascStorage::find_iter`](crate::casc_storage::CascStorage::find_iter). Patterns are
+//! matched against the `/`-separated form of each name, see
+//! [`CascFileInfo::file_path`](crate::casc_file_info::CascFileInfo::file_path), so they work the
+//! same whichever separator the root stores names with.
+use crate::error::CascError;
+use glob::{MatchOptions, Pattern};
+use regex::{Regex, RegexBuilder};
+use std::borrow::Cow;
+
+/// The matcher behind a [`CascFilePattern`].
+#[derive(Debug, Clone)]
+enum Matcher {
+    Glob(Pattern),
+    Regex(Regex),
+}
+
+/// A glob pattern or regular expression matched against storage file names.
+///
+/// Matching is case-insensitive by default, like path lookups in
+/// [`CascStorage::open_file`](crate::casc_storage::CascStorage::open_file).
+///
+/// ```rust
+/// use casc_rs::casc_file_pattern::CascFilePattern;
+///
+/// let pattern = CascFilePattern::glob("**/*.xpak").unwrap();
+/// assert!(pattern.matches("zone/pak_base_vol021.xpak"));
+/// assert!(pattern.matches("PAK_BASE.XPAK"));
+///
+/// let pattern = CascFilePattern::regex(r"^locale/enus/[^/]+\.csv$").unwrap();
+/// assert!(pattern.matches("Locale\\enUS\\strings.csv"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct CascFilePattern {
+    /// The compiled pattern.
+    matcher: Matcher,
+    /// Whether the pattern ignores case.
+    case_insensitive: bool,
+}
+
+impl CascFilePattern {
+    /// Compiles a glob pattern.
+    ///
+    /// `?` matches any single character and `*` any sequence of characters within a folder,
+    /// while `**` matches any number of folders, including none. `[...]` matches a set of
+    /// characters.
+    pub fn glob(pattern: &str) -> Result<Self, CascError> {
+        let pattern = Pattern::new(&pattern.replace('\\', "/"))
+            .map_err(|err| CascError::InvalidData(format!("Invalid glob pattern: {err}")))?;
+        Ok(Self {
+            matcher: Matcher::Glob(pattern),
+            case_insensitive: true,
+        })
+    }
+
+    /// Compiles a regular expression.
+    ///
+    /// The expression is searched for anywhere in the name, so anchor it with `^` and `$` to
+    /// match whole names. Folders in the name are separated by `/`.
+    pub fn regex(pattern: &str) -> Result<Self, CascError> {
+        Ok(Self {
+            matcher: Matcher::Regex(Self::build_regex(pattern, true)?),
+            case_insensitive: true,
+        })
+    }
+
+    fn build_regex(pattern: &str, case_insensitive: bool) -> Result<Regex, CascError> {
+        RegexBuilder::new(pattern)
+            .case_insensitive(case_insensitive)
+            .build()
+            .map_err(|err| CascError::InvalidData(format!("Invalid regular expression: {err}")))
+    }
+
+    /// Sets whether the pattern ignores case.
+    pub fn case_insensitive(mut self, case_insensitive: bool) -> Self {
+        if let Matcher::Regex(regex) = &self.matcher {
+            // The expression already compiled, so it compiles again with different flags
+            if let Ok(regex) = Self::build_regex(regex.as_str(), case_insensitive) {
+                self.matcher = Matcher::Regex(regex);
+            }
+        }
+        self.case_insensitive = case_insensitive;
+        self
+    }
+
+    /// Returns whether the pattern matches the given file name.
+    ///
+    /// Both `\` and `/` are accepted as separators in the name.
+    pub fn matches(&self, file_name: &str) -> bool {
+        let file_name = if file_name.contains('\\') {
+            Cow::Owned(file_name.replace('\\', "/"))
+        } else {
+            Cow::Borrowed(file_name)
+        };
+        match &self.matcher {
+            Matcher::Glob(pattern) => pattern.matches_with(
+                &file_name,
+                MatchOptions {
+                    case_sensitive: !self.case_insensitive,
+                    require_literal_separator: true,
+                    require_literal_leading_dot: false,
+                },
+            ),
+            Matcher::Regex(regex) => regex.is_match(&file_name),
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use crate::error::CascError;
+/// Module for describing the `.idx` files found in a CASC storage's data directory.
+///
+/// Index files are named `BBVVVVVVVV.idx`, where `BB` is the bucket and `VVVVVVVV` the
+/// generation (version) of the index, both in hex. A storage may hold several generations of
+/// each bucket, of which only the newest is current.
+use byteorder::ReadBytesExt;
+use std::fs::File;
+use std::io::{Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// Describes a single `.idx` key mapping table file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CascIndexFile {
+    /// The path to the `.idx` file.
+    pub path: PathBuf,
+    /// The bucket the index belongs to, as stored in its header.
+    pub bucket_index: u8,
+    /// The generation of the index, taken from its file name.
+    pub version: u32,
+}
+
+impl CascIndexFile {
+    /// The offset of the bucket index within an `.idx` file.
+    const BUCKET_INDEX_OFFSET: u64 = 10;
+
+    /// Describes the `.idx` file at the given path.
+    ///
+    /// Returns `Ok(None)` if the file name does not follow the `BBVVVVVVVV.idx` pattern.
+    pub(crate) fn new(path: &Path) -> Result<Option<Self>, CascError> {
+        let Some(stem) = path.file_stem().and_then(|s| s.to_str()) else {
+            return Ok(None);
+        };
+        if stem.len() != 10 {
+            return Ok(None);
+        }
+        let Ok(version) = u32::from_str_radix(&stem[2..], 16) else {
+            return Ok(None);
+        };
+
+        let mut file = File::open(path)?;
+        file.seek(SeekFrom::Start(Self::BUCKET_INDEX_OFFSET))?;
+        let bucket_index = file.read_u8()?;
+
+        Ok(Some(CascIndexFile {
+            path: path.to_path_buf(),
+            bucket_index,
+            version,
+        }))
+    }
+
+    /// Describes an `.idx` file whose header cannot be read, taking its bucket from its file
+    /// name instead.
+    ///
+    /// Returns `None` if the file name does not follow the `BBVVVVVVVV.idx` pattern.
+    pub(crate) fn from_file_name(path: &Path) -> Option<Self> {
+        let stem = path.file_stem()?.to_str()?;
+        if stem.len() != 10 {
+            return None;
+        }
+        Some(CascIndexFile {
+            path: path.to_path_buf(),
+            bucket_index: u8::from_str_radix(&stem[..2], 16).ok()?,
+            version: u32::from_str_radix(&stem[2..], 16).ok()?,
+        })
+    }
+}
@@ -1,4 +1,5 @@
 use crate::error::CascError;
+use crate::hash::hashlittle2;
 /// Module for handling CASC key mapping tables, which map encoding keys to file offsets and sizes.
 ///
 /// This module provides structures and functions for parsing and working with key mapping tables
@@ -7,9 +8,21 @@ use base64::prelude::*;
 use byteorder::{LittleEndian, ReadBytesExt};
 use std::collections::HashMap;
 use std::fs::File;
-use std::io::{Read, Seek, SeekFrom};
+use std::io::{Cursor, Read, Seek, SeekFrom};
 use std::path::PathBuf;
 
+/// The number of encoding key bytes stored in key mapping table entries.
+pub(crate) const KEY_MAPPING_KEY_SIZE: usize = 9;
+
+/// Returns the base64 lookup key used for the key mapping table entries.
+///
+/// Key mapping tables only store the first 9 bytes of each encoding key, so longer keys
+/// (such as those from the ENCODING file) are truncated before being encoded.
+pub(crate) fn encoding_key_to_base64(encoding_key: &[u8]) -> String {
+    let len = encoding_key.len().min(KEY_MAPPING_KEY_SIZE);
+    BASE64_STANDARD.encode(&encoding_key[..len])
+}
+
 /// Represents a CASC key mapping table, which maps encoding keys to file offsets and sizes.
 ///
 /// This struct is used to parse and store the metadata for a key mapping table in a CASC storage.
@@ -52,24 +65,43 @@ pub struct CascKeyMappingTableEntry {
 }
 
 impl CascKeyMappingTable {
+    /// Loads a key mapping table from an `.idx` file, adding its entries to `entries`.
+    ///
+    /// The header and table hashes are verified before any entries are added, and a
+    /// mismatch is reported as [`CascError::IndexHashMismatch`]. A truncated file fails
+    /// without adding any entries either.
     pub(crate) fn new(
         file_name: &PathBuf,
         entries: &mut HashMap<String, CascKeyMappingTableEntry>,
     ) -> Result<Self, CascError> {
         let mut file = File::open(file_name)?;
+        let file_length = file.metadata()?.len();
 
         let header_size = file.read_u32::<LittleEndian>()?;
         let header_hash = file.read_u32::<LittleEndian>()?;
+        if header_size as u64 > file_length.saturating_sub(8) {
+            return Err(CascError::FileCorrupted(format!(
+                "Key mapping table header runs past the end of the file: {}",
+                file_name.display()
+            )));
+        }
 
-        let version = file.read_u16::<LittleEndian>()?;
-        let bucket_index = file.read_u8()?;
-        let extra_byte = file.read_u8()?;
-        let encoded_size_length = file.read_u8()?;
-        let storage_offset_length = file.read_u8()?;
-        let encoding_key_length = file.read_u8()?;
-        let file_offset_bits = file.read_u8()?;
+        let mut header_buffer = vec![0u8; header_size as usize];
+        file.read_exact(&mut header_buffer)?;
+        if hashlittle2(&header_buffer, 0, 0).0 != header_hash {
+            return Err(CascError::IndexHashMismatch(file_name.clone()));
+        }
+        let mut header = Cursor::new(header_buffer);
+
+        let version = header.read_u16::<LittleEndian>()?;
+        let bucket_index = header.read_u8()?;
+        let extra_byte = header.read_u8()?;
+        let encoded_size_length = header.read_u8()?;
+        let storage_offset_length = header.read_u8()?;
+        let encoding_key_length = header.read_u8()?;
+        let file_offset_bits = header.read_u8()?;
         let file_offset_mask = (1 << file_offset_bits) - 1;
-        let file_size = file.read_u64::<LittleEndian>()?;
+        let file_size = header.read_u64::<LittleEndian>()?;
 
         if encoded_size_length != 4 && encoding_key_length != 9 && storage_offset_length != 5 {
             return Err(CascError::FileCorrupted(
@@ -99,10 +131,20 @@ impl CascKeyMappingTable {
             file_size,
         };
 
+        // The table hash is computed incrementally over each entry
+        let mut table_entries = Vec::new();
         let mut entry_buffer = vec![0u8; entry_size];
+        let (mut hash_high, mut hash_low) = (0u32, 0u32);
         for _ in (0..table_size).step_by(entry_size) {
             file.read_exact(&mut entry_buffer)?;
-            let entry = CascKeyMappingTableEntry::new(&entry_buffer, &table);
+            (hash_high, hash_low) = hashlittle2(&entry_buffer, hash_high, hash_low);
+            table_entries.push(CascKeyMappingTableEntry::new(&entry_buffer, &table));
+        }
+        if hash_high != table_hash {
+            return Err(CascError::IndexHashMismatch(file_name.clone()));
+        }
+
+        for entry in table_entries {
             let encoded = BASE64_STANDARD.encode(&entry.encoding_key);
             entries.insert(encoded, entry);
         }
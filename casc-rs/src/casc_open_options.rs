@@ -0,0 +1,88 @@
+//! Module for the options used when opening a CASC storage.
+//!
+//! [`CascOpenOptions`] controls how [`CascStorage::open_with_options`](crate::casc_storage::CascStorage::open_with_options)
+//! reacts to damaged storages, which keys it decrypts files with, whether it verifies
+//! the data it reads, and how it matches the paths files are opened by.
+use crate::casc_path::PathNormalizer;
+use crate::cmf_key_provider::CmfKeyProvider;
+use crate::tact_key_registry::TactKeyRegistry;
+use std::sync::Arc;
+
+/// Determines what happens when an `.idx` key mapping table fails hash validation or cannot
+/// be read, such as when it is truncated.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CorruptIndexPolicy {
+    /// Fail to open the storage with [`CascError::IndexHashMismatch`](crate::error::CascError::IndexHashMismatch),
+    /// or the error the index could not be read with.
+    #[default]
+    Fail,
+    /// Skip the corrupt index and continue opening the storage.
+    ///
+    /// When the newest generation of a bucket is corrupt, the whole bucket is skipped rather
+    /// than falling back to an older generation, which is only listed among the
+    /// [stale index files](crate::casc_storage::CascStorage::stale_index_files).
+    Skip,
+}
+
+/// Options used when opening a CASC storage.
+///
+/// ```rust
+/// use casc_rs::casc_open_options::{CascOpenOptions, CorruptIndexPolicy};
+///
+/// let options = CascOpenOptions::new().corrupt_index_policy(CorruptIndexPolicy::Skip);
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct CascOpenOptions {
+    /// What to do when an `.idx` file fails hash validation or cannot be read.
+    pub(crate) corrupt_index_policy: CorruptIndexPolicy,
+    /// The keys used to decrypt encrypted files.
+    pub(crate) key_registry: TactKeyRegistry,
+    /// Whether files opened from the storage verify the MD5 hash of each frame.
+    pub(crate) verify_frames: bool,
+    /// How paths are normalised when looking files up by name.
+    pub(crate) path_normalizer: PathNormalizer,
+    /// The procedures used to decrypt Overwatch CMF manifests, one per build.
+    pub(crate) cmf_key_providers: Vec<Arc<dyn CmfKeyProvider>>,
+}
+
+impl CascOpenOptions {
+    /// Creates a new set of options with the default values.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets what to do when an `.idx` file fails hash validation or cannot be read.
+    pub fn corrupt_index_policy(mut self, policy: CorruptIndexPolicy) -> Self {
+        self.corrupt_index_policy = policy;
+        self
+    }
+
+    /// Sets the keys used to decrypt encrypted files.
+    pub fn key_registry(mut self, key_registry: TactKeyRegistry) -> Self {
+        self.key_registry = key_registry;
+        self
+    }
+
+    /// Sets whether files opened from the storage verify the MD5 hash of each encoded frame
+    /// before decoding it. See [`CascFile::verify`](crate::casc_file::CascFile::verify).
+    pub fn verify_frames(mut self, verify_frames: bool) -> Self {
+        self.verify_frames = verify_frames;
+        self
+    }
+
+    /// Sets how paths are normalised when looking files up by name.
+    ///
+    /// By default lookups accept both `\` and `/` as separators and ignore case.
+    pub fn path_normalizer(mut self, path_normalizer: PathNormalizer) -> Self {
+        self.path_normalizer = path_normalizer;
+        self
+    }
+
+    /// Adds the procedures used to decrypt the CMF manifests of an Overwatch build.
+    ///
+    /// Manifests of a build without a provider are skipped, so their assets are not listed.
+    pub fn cmf_key_provider(mut self, provider: Arc<dyn CmfKeyProvider>) -> Self {
+        self.cmf_key_providers.push(provider);
+        self
+    }
+}
@@ -0,0 +1,123 @@
+//! Module for normalising the paths files are looked up by.
+//!
+//! Roots store file names in their own form: TVFS joins path components with `\`, while
+//! listfile names use `/` and mixed case. [`PathNormalizer`] maps both forms, and any mix of
+//! separators and case, to a single key so callers do not need to know the stored form.
+use std::collections::HashMap;
+
+/// Normalises paths so that names differing only in separators or case compare equal.
+///
+/// ```rust
+/// use casc_rs::casc_path::PathNormalizer;
+///
+/// let normalizer = PathNormalizer::new().separator('/');
+/// assert_eq!(normalizer.normalize("DATA\\Foo.bin"), "data/foo.bin");
+/// assert_eq!(normalizer.normalize("/data//foo.bin"), "data/foo.bin");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathNormalizer {
+    /// The separator placed between path components.
+    pub(crate) separator: char,
+    /// Whether paths are folded to lowercase.
+    pub(crate) case_insensitive: bool,
+}
+
+impl Default for PathNormalizer {
+    fn default() -> Self {
+        Self {
+            separator: '\\',
+            case_insensitive: true,
+        }
+    }
+}
+
+impl PathNormalizer {
+    /// Creates a normaliser that joins components with `\` and folds case.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the separator placed between path components.
+    ///
+    /// Both `\` and `/` are always accepted as separators in the input.
+    pub fn separator(mut self, separator: char) -> Self {
+        self.separator = separator;
+        self
+    }
+
+    /// Sets whether paths are folded to lowercase, making lookups case-insensitive.
+    pub fn case_insensitive(mut self, case_insensitive: bool) -> Self {
+        self.case_insensitive = case_insensitive;
+        self
+    }
+
+    /// Returns the normalised form of a path.
+    ///
+    /// The path is split on `\` and `/`, empty components are dropped, and the rest are joined
+    /// with the configured separator and folded to lowercase if configured.
+    pub fn normalize(&self, path: &str) -> String {
+        let mut normalized = String::with_capacity(path.len());
+        for component in path
+            .split(['\\', '/'])
+            .filter(|component| !component.is_empty())
+        {
+            if !normalized.is_empty() {
+                normalized.push(self.separator);
+            }
+            if self.case_insensitive {
+                normalized.extend(component.chars().flat_map(char::to_lowercase));
+            } else {
+                normalized.push_str(component);
+            }
+        }
+        normalized
+    }
+}
+
+/// Maps normalised paths to the names the root stores files under.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CascPathIndex {
+    /// The normaliser used to build the keys.
+    normalizer: PathNormalizer,
+    /// Stored names, keyed by normalised path.
+    names: HashMap<String, String>,
+}
+
+impl CascPathIndex {
+    /// Builds the index from the names stored by the root.
+    ///
+    /// When several names normalise to the same path, the lowest one is kept, so lookups
+    /// do not depend on the order the root lists its files in.
+    pub(crate) fn new<'a>(
+        normalizer: PathNormalizer,
+        names: impl IntoIterator<Item = &'a String>,
+    ) -> Self {
+        let mut index: HashMap<String, String> = HashMap::new();
+        for name in names {
+            index
+                .entry(normalizer.normalize(name))
+                .and_modify(|existing| {
+                    if name < existing {
+                        existing.clone_from(name);
+                    }
+                })
+                .or_insert_with(|| name.clone());
+        }
+        Self {
+            normalizer,
+            names: index,
+        }
+    }
+
+    /// Returns the normaliser used to build the index.
+    pub(crate) fn normalizer(&self) -> &PathNormalizer {
+        &self.normalizer
+    }
+
+    /// Returns the stored name matching the given path, if any.
+    pub(crate) fn get(&self, path: &str) -> Option<&str> {
+        self.names
+            .get(&self.normalizer.normalize(path))
+            .map(String::as_str)
+    }
+}
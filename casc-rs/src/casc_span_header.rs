@@ -1,6 +1,8 @@
 /// This module defines the `CascSpanHeader` struct, which represents the header for a span of data
 /// in a CASC archive. The header contains metadata such as encoding key, content size, flags,
 /// hash, and checksum.
+use crate::hash::hashlittle;
+
 #[repr(C, packed)]
 #[derive(Debug, Default, Clone, Copy)]
 /// Represents the header for a span of data in a CASC archive.
@@ -20,6 +22,11 @@ pub(crate) struct CascSpanHeader {
 }
 
 impl CascSpanHeader {
+    /// The seed used for the Jenkins hash of the span header.
+    const JENKINS_HASH_SEED: u32 = 0x3D6BE971;
+    /// The number of leading header bytes covered by the Jenkins hash.
+    const JENKINS_HASH_LENGTH: usize = 0x16;
+
     pub(crate) fn new() -> Self {
         Self {
             encoding_key: [0; 16],
@@ -29,4 +36,15 @@ impl CascSpanHeader {
             checksum: 0,
         }
     }
+
+    /// Computes the Jenkins hash of the encoding key, content size and flags of the header.
+    ///
+    /// For a valid header this matches `jenkins_hash`.
+    pub(crate) fn compute_jenkins_hash(&self) -> u32 {
+        let mut bytes = [0u8; Self::JENKINS_HASH_LENGTH];
+        bytes[..16].copy_from_slice(&self.encoding_key);
+        bytes[16..20].copy_from_slice(&{ self.content_size }.to_le_bytes());
+        bytes[20..22].copy_from_slice(&{ self.flags }.to_le_bytes());
+        hashlittle(&bytes, Self::JENKINS_HASH_SEED)
+    }
 }
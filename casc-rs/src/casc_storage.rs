@@ -1,28 +1,56 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs::{self, File};
-use std::io::{Read, Seek, SeekFrom};
+use std::io::{Cursor, Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 
 use base64::{engine::general_purpose, prelude::*};
 use glob::glob;
 use hex;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
 
 use crate::{
-    block_table::{block_table_entry::BlockTableEntry, block_table_header::BlockTableHeader},
+    block_table::{
+        block_table_decoder::BLTE_SIGNATURE, block_table_encoder_type::BlockTableEncoderType,
+        block_table_entry::BlockTableEntry, block_table_header::BlockTableHeader,
+    },
     casc_build_info::CascBuildInfo,
     casc_config::CascConfig,
+    casc_directory_tree::{CascDirEntry, CascDirectoryTree, CascWalk},
+    casc_encoding_table::CascEncodingTable,
     casc_file::CascFile,
+    casc_file_details::{CascFileDetails, CascSpanDetails},
     casc_file_frame::CascFileFrame,
     casc_file_info::CascFileInfo,
+    casc_file_pattern::CascFilePattern,
     casc_file_span::CascFileSpan,
-    casc_key_mapping_table::{CascKeyMappingTable, CascKeyMappingTableEntry},
+    casc_index_file::CascIndexFile,
+    casc_key_mapping_table::{
+        encoding_key_to_base64, CascKeyMappingTable, CascKeyMappingTableEntry,
+    },
+    casc_open_options::{CascOpenOptions, CorruptIndexPolicy},
+    casc_path::{CascPathIndex, PathNormalizer},
     casc_span_header::CascSpanHeader,
+    casc_verification::{SpanFailure, SpanIssue, VerificationReport, VerifyOptions},
+    casc_verifying_reader::CascVerifyingReader,
     entry::Entry,
     error::CascError,
     ext::io_ext::{ArrayReadExt, StructReadExt},
+    install_manifest::InstallManifest,
+    listfile::Listfile,
     root_handler::{RootHandler, RootHandlerTrait},
-    root_handlers::tvfs_root_handler::TVFSRootHandler,
+    root_handlers::{
+        diablo3_root_handler::Diablo3RootHandler,
+        install_root_handler::InstallRootHandler,
+        mndx_root_handler::MndxRootHandler,
+        overwatch_root_handler::OverwatchRootHandler,
+        text_root_handler::TextRootHandler,
+        tvfs_root_handler::{TVFSRootHandler, VfsManifest},
+        wow_root_handler::WowRootHandler,
+    },
+    span_info::SpanInfo,
+    tact_key_registry::TactKeyRegistry,
 };
 
 // Type aliases for complex types
@@ -49,7 +77,7 @@ type FilePaths = Vec<PathBuf>;
 ///
 /// // List all files
 /// for file_info in &storage.files {
-///     println!("File: {} ({} bytes)", file_info.file_name(), file_info.file_size());
+///     println!("File: {} ({} bytes)", file_info.file_name(), file_info.content_size());
 /// }
 ///
 /// // Extract a file by name
@@ -68,19 +96,32 @@ type FilePaths = Vec<PathBuf>;
 /// - Other fields are internal and subject to change.
 ///
 /// # Note
-/// This implementation currently only supports CASC storages that use the TVFS root file format.
+/// This implementation currently supports CASC storages that use the TVFS, World of Warcraft,
+/// MNDX, Diablo III, Overwatch or text (StarCraft: Remastered and Warcraft III) root file formats.
 #[derive(Debug)]
 pub struct CascStorage {
     /// Internal mapping of file names to key mapping table entries.
     entries: HashMap<String, CascKeyMappingTableEntry>,
     /// All loaded key mapping tables from the storage.
     key_mapping_tables: Vec<CascKeyMappingTable>,
-    /// Handler for the root file system (currently only TVFS supported).
+    /// The current generation of `.idx` file for each bucket, as loaded.
+    index_files: Vec<CascIndexFile>,
+    /// Older generations of `.idx` files, which are not loaded.
+    stale_index_files: Vec<CascIndexFile>,
+    /// Paths of `.idx` files skipped because they failed hash validation or could not be read.
+    corrupt_indexes: Vec<PathBuf>,
+    /// Handler for the root file system (TVFS, World of Warcraft, MNDX, Diablo III, Overwatch or text).
     root_handler: RootHandler,
     /// Parsed build information from `.build.info`.
     build_info: CascBuildInfo,
     /// Parsed configuration information from the storage.
     config: CascConfig,
+    /// Parsed ENCODING file, mapping content keys to encoding keys.
+    encoding: Option<CascEncodingTable>,
+    /// Keys used to decrypt encrypted frames.
+    key_registry: Arc<TactKeyRegistry>,
+    /// Whether files opened from the storage verify the MD5 hash of each frame.
+    verify_frames: bool,
     /// Path to the root of the storage directory.
     storage_path: String,
     /// Path to the storage's data directory.
@@ -89,10 +130,23 @@ pub struct CascStorage {
     data_file_paths: FilePaths,
     /// List of files discovered in the storage, with metadata.
     pub files: Vec<CascFileInfo>,
+    /// The files arranged into folders.
+    directory_tree: CascDirectoryTree,
+    /// Stored file names, keyed by normalised path.
+    path_index: CascPathIndex,
 }
 
 impl CascStorage {
+    /// Opens the CASC storage in the given directory with the default [`CascOpenOptions`].
     pub fn open<P: AsRef<Path>>(folder: P) -> Result<Self, CascError> {
+        Self::open_with_options(folder, CascOpenOptions::default())
+    }
+
+    /// Opens the CASC storage in the given directory with the given options.
+    pub fn open_with_options<P: AsRef<Path>>(
+        folder: P,
+        options: CascOpenOptions,
+    ) -> Result<Self, CascError> {
         let f = folder.as_ref();
         let data_path = f.join("Data").join("data");
 
@@ -111,30 +165,127 @@ impl CascStorage {
             })
             .collect::<Vec<_>>();
 
+        // Group the index files by bucket, newest generation first
+        let mut buckets: BTreeMap<u8, Vec<CascIndexFile>> = BTreeMap::new();
+        let mut unversioned_idx_files = Vec::new();
+        let mut corrupt_indexes = Vec::new();
+        for idx_file in idx_files {
+            let path = idx_file.path();
+            match CascIndexFile::new(&path) {
+                Ok(Some(index_file)) => buckets
+                    .entry(index_file.bucket_index)
+                    .or_default()
+                    .push(index_file),
+                Ok(None) => unversioned_idx_files.push(path),
+                // Index files whose bucket cannot be read fall under the corrupt index policy,
+                // but still count as a generation of the bucket in their name
+                Err(_) if options.corrupt_index_policy == CorruptIndexPolicy::Skip => {
+                    if let Some(index_file) = CascIndexFile::from_file_name(&path) {
+                        buckets
+                            .entry(index_file.bucket_index)
+                            .or_default()
+                            .push(index_file);
+                    }
+                    corrupt_indexes.push(path);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
         let mut entries = HashMap::new();
         let mut key_mapping_tables = Vec::new();
-        for idx_file in idx_files {
-            let key_table = CascKeyMappingTable::new(&idx_file.path(), &mut entries)?;
-            key_mapping_tables.push(key_table);
+        let mut index_files = Vec::new();
+        let mut stale_index_files = Vec::new();
+        for (_, mut generations) in buckets {
+            generations.sort_by_key(|index_file| std::cmp::Reverse(index_file.version));
+            let mut generations = generations.into_iter();
+            let Some(newest) = generations.next() else {
+                continue;
+            };
+            // Only the newest generation is current, older ones are kept for diagnostics. When
+            // the newest is corrupt the whole bucket is skipped, as the older generations may
+            // map encoding keys to offsets that have since been reused.
+            if !corrupt_indexes.contains(&newest.path) {
+                if let Some(key_table) = Self::load_key_mapping_table(
+                    &newest.path,
+                    &mut entries,
+                    &options,
+                    &mut corrupt_indexes,
+                )? {
+                    key_mapping_tables.push(key_table);
+                    index_files.push(newest);
+                }
+            }
+            stale_index_files.extend(generations);
+        }
+        for path in unversioned_idx_files {
+            if let Some(key_table) =
+                Self::load_key_mapping_table(&path, &mut entries, &options, &mut corrupt_indexes)?
+            {
+                key_mapping_tables.push(key_table);
+            }
         }
         // Load data files with thread safety
         let data_file_paths = Self::load_data_files(&data_path_str)?;
-        let root_handler = Self::load_root_handler(&config, &data_file_paths, &entries)?;
-        let files = Self::load_files(&root_handler, &entries)?;
+        // TVFS roots reference their files by encoding key, so they can be read without ENCODING
+        let encoding = match Self::load_encoding(&config, &data_file_paths, &entries) {
+            Err(_) if config.get("vfs-root").is_some() => None,
+            result => result?,
+        };
+        let root_handler = Self::load_root_handler(
+            &config,
+            &data_file_paths,
+            &entries,
+            encoding.as_ref(),
+            &options,
+        )?;
+        let files = Self::load_files(&root_handler, &entries, encoding.as_ref())?;
+        let path_index = CascPathIndex::new(
+            options.path_normalizer,
+            root_handler.get_file_entries()?.keys(),
+        );
 
         Ok(CascStorage {
             entries,
             key_mapping_tables,
+            index_files,
+            stale_index_files,
+            corrupt_indexes,
             root_handler,
             build_info,
             config,
+            encoding,
+            key_registry: Arc::new(options.key_registry),
+            verify_frames: options.verify_frames,
             storage_path,
             data_path: data_path_str,
             data_file_paths,
+            directory_tree: CascDirectoryTree::new(&files, path_index.normalizer().clone()),
             files,
+            path_index,
         })
     }
 
+    /// Loads a single key mapping table, applying the corrupt index policy.
+    ///
+    /// Returns `Ok(None)` if the index failed hash validation or could not be read, and was
+    /// skipped.
+    fn load_key_mapping_table(
+        path: &PathBuf,
+        entries: &mut HashMap<String, CascKeyMappingTableEntry>,
+        options: &CascOpenOptions,
+        corrupt_indexes: &mut Vec<PathBuf>,
+    ) -> Result<Option<CascKeyMappingTable>, CascError> {
+        match CascKeyMappingTable::new(path, entries) {
+            Ok(key_table) => Ok(Some(key_table)),
+            Err(_) if options.corrupt_index_policy == CorruptIndexPolicy::Skip => {
+                corrupt_indexes.push(path.clone());
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
     fn load_build_info(storage_path: &str) -> Result<CascBuildInfo, CascError> {
         fn find_build_info<P: AsRef<Path>>(dir: P) -> Option<PathBuf> {
             for entry in fs::read_dir(dir).ok()? {
@@ -223,28 +374,100 @@ impl CascStorage {
         Ok(paths)
     }
 
-    //TODO: Determine which root handler to use from ROOT key
-    fn load_root_handler(
+    fn load_encoding(
         config: &CascConfig,
         data_file_paths: &[PathBuf],
         entries: &HashMap<String, CascKeyMappingTableEntry>,
-    ) -> Result<RootHandler, CascError> {
-        // Get the "vfs-root" key from config
-        // This is only for virtual casc file systems
+    ) -> Result<Option<CascEncodingTable>, CascError> {
+        // The "encoding" key holds the content key and the encoding key of the ENCODING file
+        let Some(key) = config.get("encoding") else {
+            return Ok(None);
+        };
+        let encoding_key = key
+            .values
+            .get(1)
+            .ok_or_else(|| CascError::InvalidData("Missing encoding key in config".to_string()))?;
+
+        let hex_bytes = hex::decode(encoding_key)
+            .map_err(|_| CascError::InvalidData("Invalid hex in encoding".to_string()))?;
+
+        let entry = Self::get_entry_by_encoding_key(entries, &hex_bytes).ok_or_else(|| {
+            CascError::FileNotFound(format!("ENCODING file not found: {encoding_key}"))
+        })?;
+
+        let mut stream = Self::open_file_from_entry(data_file_paths, entry)?;
+        Ok(Some(CascEncodingTable::new(&mut stream)?))
+    }
+
+    /// Loads the INSTALL manifest referenced by the `install` entry of the build config.
+    ///
+    /// The entry holds the content key of the manifest, followed by its encoding key in
+    /// newer builds. Without the encoding key, the content key is resolved through ENCODING.
+    fn load_install_manifest(
+        config: &CascConfig,
+        data_file_paths: &[PathBuf],
+        entries: &HashMap<String, CascKeyMappingTableEntry>,
+        encoding: Option<&CascEncodingTable>,
+    ) -> Result<InstallManifest, CascError> {
         let key = config
-            .get("vfs-root")
-            .ok_or_else(|| CascError::Other("vfs-root not in config".to_string()))?;
+            .get("install")
+            .ok_or_else(|| CascError::FileNotFound("INSTALL file not in config".to_string()))?;
+        let data = match key.values.get(1) {
+            Some(encoding_key) => {
+                let hex_bytes = hex::decode(encoding_key)
+                    .map_err(|_| CascError::InvalidData("Invalid hex in install".to_string()))?;
+                let entry =
+                    Self::get_entry_by_encoding_key(entries, &hex_bytes).ok_or_else(|| {
+                        CascError::FileNotFound(format!("INSTALL file not found: {encoding_key}"))
+                    })?;
+                let mut stream = Self::open_file_from_entry(data_file_paths, entry)?;
+                let mut data = Vec::new();
+                stream.read_to_end(&mut data)?;
+                data
+            }
+            None => {
+                let content_key = key.values.first().ok_or_else(|| {
+                    CascError::InvalidData("Missing install key in config".to_string())
+                })?;
+                let content_key = hex::decode(content_key)
+                    .map_err(|_| CascError::InvalidData("Invalid hex in install".to_string()))?;
+                Self::read_content(
+                    data_file_paths,
+                    entries,
+                    Self::require_encoding(encoding)?,
+                    &content_key,
+                )?
+            }
+        };
+        InstallManifest::new(&mut Cursor::new(data))
+    }
 
-        let hex_bytes = hex::decode(&key.values[1])
-            .map_err(|_| CascError::InvalidData("Invalid hex in vfs-root".to_string()))?;
+    /// Returns the path of the data file with the given archive index.
+    fn get_data_file_path(
+        data_file_paths: &[PathBuf],
+        archive_index: u32,
+    ) -> Result<&PathBuf, CascError> {
+        data_file_paths.get(archive_index as usize).ok_or_else(|| {
+            CascError::FileNotFound(format!("Data file not found: data.{archive_index:03}"))
+        })
+    }
 
-        let base64 = general_purpose::STANDARD.encode(&hex_bytes);
-        let base64_key = &base64[0..12];
+    /// Looks up a key mapping table entry by its (full or truncated) encoding key.
+    fn get_entry_by_encoding_key<'a>(
+        entries: &'a HashMap<String, CascKeyMappingTableEntry>,
+        encoding_key: &[u8],
+    ) -> Option<&'a CascKeyMappingTableEntry> {
+        entries.get(&encoding_key_to_base64(encoding_key))
+    }
 
-        // Look up entry by transformed key
-        let entry = entries.get(base64_key).ok_or_else(|| {
-            CascError::FileNotFound(format!("Entry not found in entries: {base64_key}"))
-        })?;
+    fn load_root_handler(
+        config: &CascConfig,
+        data_file_paths: &[PathBuf],
+        entries: &HashMap<String, CascKeyMappingTableEntry>,
+        encoding: Option<&CascEncodingTable>,
+        options: &CascOpenOptions,
+    ) -> Result<RootHandler, CascError> {
+        let entry = Self::find_root_entry(config, entries, encoding)?;
 
         // Open the stream
         let mut stream = Self::open_file_from_entry(data_file_paths, entry)
@@ -261,64 +484,830 @@ impl CascStorage {
         let header_magic = u32::from_le_bytes(header_buf);
         let root_handler = match header_magic {
             0x53465654 => {
-                let handler = TVFSRootHandler::new(&mut stream)?;
+                let mut handler = TVFSRootHandler::new(&mut stream)?;
+                let (manifests, skipped_manifests) =
+                    Self::load_vfs_manifests(config, data_file_paths, entries);
+                handler.mount_manifests(&manifests);
+                handler.skipped_manifests = skipped_manifests;
                 RootHandler::Tvfs(handler)
             }
-            //0x58444E4D - MDNX
-            //0x8007D0C4 - Diablo3
-            //0x4D465354 - WOW
+            MndxRootHandler::MNDX_MAGIC => {
+                let encoding = Self::require_encoding(encoding)?;
+                let handler = MndxRootHandler::new(&mut stream, encoding)?;
+                RootHandler::Mndx(handler)
+            }
+            Diablo3RootHandler::DIABLO3_MAGIC => {
+                let encoding = Self::require_encoding(encoding)?;
+                let handler = Diablo3RootHandler::new(&mut stream, encoding, |content_key| {
+                    Self::read_content(data_file_paths, entries, encoding, content_key)
+                })?;
+                RootHandler::Diablo3(handler)
+            }
+            WowRootHandler::MFST_MAGIC => {
+                let encoding = Self::require_encoding(encoding)?;
+                let handler = WowRootHandler::new(&mut stream, encoding)?;
+                RootHandler::Wow(handler)
+            }
+            // Roots without a magic are recognised by their contents instead
             _ => {
-                return Err(CascError::InvalidData(format!(
-                    "Invalid VFS header {header_magic}",
-                )))
+                let mut data = Vec::new();
+                stream.read_to_end(&mut data)?;
+                let mut stream = Cursor::new(data);
+                if OverwatchRootHandler::is_overwatch_root(stream.get_ref()) {
+                    let encoding = Self::require_encoding(encoding)?;
+                    let handler = OverwatchRootHandler::new(
+                        &mut stream,
+                        encoding,
+                        |content_key| {
+                            Self::read_content(data_file_paths, entries, encoding, content_key)
+                        },
+                        &options.cmf_key_providers,
+                    )?;
+                    RootHandler::Overwatch(handler)
+                } else if TextRootHandler::is_text_root(stream.get_ref()) {
+                    let encoding = Self::require_encoding(encoding)?;
+                    RootHandler::TextRoot(TextRootHandler::new(&mut stream, encoding)?)
+                } else {
+                    // Every remaining root resolves its files through ENCODING, so without it
+                    // the root is simply not recognised
+                    let encoding = encoding.ok_or_else(|| {
+                        CascError::InvalidData(format!("Invalid VFS header {header_magic}"))
+                    })?;
+                    // Legacy World of Warcraft roots have no magic, so they are only parsed as
+                    // such for World of Warcraft builds, or when their blocks look sound
+                    let is_legacy_wow = Self::is_wow_build(config)
+                        || WowRootHandler::is_legacy_root(stream.get_ref());
+                    let handler = is_legacy_wow
+                        .then(|| WowRootHandler::new(&mut stream, encoding).ok())
+                        .flatten();
+                    match handler {
+                        Some(handler) => RootHandler::Wow(handler),
+                        // Otherwise list the install files, so the storage is still usable
+                        None => match Self::load_install_manifest(
+                            config,
+                            data_file_paths,
+                            entries,
+                            Some(encoding),
+                        ) {
+                            Ok(manifest) => {
+                                RootHandler::Install(InstallRootHandler::new(manifest, encoding))
+                            }
+                            Err(err) => {
+                                return Err(CascError::InvalidData(format!(
+                                    "Invalid VFS header {header_magic}, and the INSTALL file \
+                                     could not be listed instead: {err}"
+                                )))
+                            }
+                        },
+                    }
+                }
             }
         };
 
         Ok(root_handler)
     }
 
+    /// Returns whether the build config names World of Warcraft as the product of the build.
+    fn is_wow_build(config: &CascConfig) -> bool {
+        ["build-product", "build-uid"].into_iter().any(|name| {
+            config
+                .get(name)
+                .and_then(|variable| variable.values.first())
+                .is_some_and(|value| value.to_ascii_lowercase().starts_with("wow"))
+        })
+    }
+
+    /// Loads the TVFS sub-manifests (`vfs-1`, `vfs-2`, ...) listed in the build config,
+    /// returning them with the names of those that could not be read.
+    ///
+    /// Manifests without local data are left out silently, as they are not installed.
+    fn load_vfs_manifests(
+        config: &CascConfig,
+        data_file_paths: &[PathBuf],
+        entries: &HashMap<String, CascKeyMappingTableEntry>,
+    ) -> (Vec<VfsManifest>, Vec<String>) {
+        let mut manifests = Vec::new();
+        let mut skipped_manifests = Vec::new();
+        for variable in config.variables() {
+            let is_manifest = variable
+                .name
+                .strip_prefix("vfs-")
+                .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()));
+            if !is_manifest {
+                continue;
+            }
+            let Some(encoding_key) = variable.values.get(1) else {
+                continue;
+            };
+            let Ok(hex_bytes) = hex::decode(encoding_key) else {
+                skipped_manifests.push(variable.name.clone());
+                continue;
+            };
+            let Some(entry) = Self::get_entry_by_encoding_key(entries, &hex_bytes) else {
+                continue;
+            };
+
+            // A damaged manifest only loses its own files, so the storage still opens
+            let handler = Self::open_file_from_entry(data_file_paths, entry)
+                .and_then(|mut stream| TVFSRootHandler::new(&mut stream));
+            match handler {
+                Ok(handler) => manifests.push(VfsManifest {
+                    name: variable.name.clone(),
+                    base64_encoding_key: encoding_key_to_base64(&hex_bytes),
+                    file_entries: handler.file_entries,
+                }),
+                Err(_) => skipped_manifests.push(variable.name.clone()),
+            }
+        }
+        // Keep the mount order stable
+        manifests.sort_by(|a, b| a.name.cmp(&b.name));
+        skipped_manifests.sort();
+        (manifests, skipped_manifests)
+    }
+
+    /// Finds the key mapping table entry of the root file.
+    ///
+    /// TVFS storages reference their root directly by encoding key through "vfs-root", while
+    /// all other storages reference it by content key through "root", resolved via ENCODING.
+    fn find_root_entry<'a>(
+        config: &CascConfig,
+        entries: &'a HashMap<String, CascKeyMappingTableEntry>,
+        encoding: Option<&CascEncodingTable>,
+    ) -> Result<&'a CascKeyMappingTableEntry, CascError> {
+        // Get the "vfs-root" key from config
+        // This is only for virtual casc file systems
+        if let Some(key) = config.get("vfs-root") {
+            let hex_bytes = hex::decode(&key.values[1])
+                .map_err(|_| CascError::InvalidData("Invalid hex in vfs-root".to_string()))?;
+
+            // Look up entry by transformed key
+            return Self::get_entry_by_encoding_key(entries, &hex_bytes).ok_or_else(|| {
+                CascError::FileNotFound(format!(
+                    "Entry not found in entries: {}",
+                    encoding_key_to_base64(&hex_bytes)
+                ))
+            });
+        }
+
+        let key = config
+            .get("root")
+            .and_then(|key| key.values.first())
+            .ok_or_else(|| CascError::Other("root not in config".to_string()))?;
+
+        let content_key = hex::decode(key)
+            .map_err(|_| CascError::InvalidData("Invalid hex in root".to_string()))?;
+
+        Self::require_encoding(encoding)?
+            .get_encoding_keys(&content_key)
+            .into_iter()
+            .flatten()
+            .find_map(|encoding_key| Self::get_entry_by_encoding_key(entries, encoding_key))
+            .ok_or_else(|| CascError::FileNotFound(format!("Root file not found: {key}")))
+    }
+
+    /// Reads the whole decoded contents of a file by content key, resolved through ENCODING.
+    fn read_content(
+        data_file_paths: &[PathBuf],
+        entries: &HashMap<String, CascKeyMappingTableEntry>,
+        encoding: &CascEncodingTable,
+        content_key: &[u8],
+    ) -> Result<Vec<u8>, CascError> {
+        let entry = encoding
+            .get_encoding_keys(content_key)
+            .into_iter()
+            .flatten()
+            .find_map(|encoding_key| Self::get_entry_by_encoding_key(entries, encoding_key))
+            .ok_or_else(|| {
+                CascError::FileNotFound(format!(
+                    "Content key not found: {}",
+                    hex::encode(content_key)
+                ))
+            })?;
+
+        let mut stream = Self::open_file_from_entry(data_file_paths, entry)?;
+        let mut data = Vec::new();
+        stream.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    fn require_encoding(
+        encoding: Option<&CascEncodingTable>,
+    ) -> Result<&CascEncodingTable, CascError> {
+        encoding.ok_or_else(|| CascError::FileNotFound("ENCODING file not in config".to_string()))
+    }
+
     fn load_files(
         handler: &RootHandler,
         entries: &HashMap<String, CascKeyMappingTableEntry>,
+        encoding: Option<&CascEncodingTable>,
     ) -> Result<Vec<CascFileInfo>, CascError> {
         let mut files = Vec::new();
         for (name, entry) in handler.get_file_entries()? {
-            let mut info = CascFileInfo::new(name.clone(), 0, true);
+            let mut content_size = 0u64;
+            let mut encoded_size = 0u64;
+            let mut is_local = true;
 
             for span_info in &entry.spans {
-                match entries.get(&span_info.base64_encoding_key) {
-                    Some(entry1) => info.set_file_size(info.file_size() + entry1.size as i64),
-                    None => {
-                        info.set_is_local(false);
-                        info.set_file_size(0);
-                        break;
-                    }
+                let key_entry = entries.get(&span_info.base64_encoding_key);
+                match key_entry {
+                    Some(key_entry) => encoded_size += key_entry.size as u64,
+                    None => is_local = false,
                 }
+
+                content_size += Self::get_span_content_size(span_info, encoding).unwrap_or(0);
+            }
+            if !is_local {
+                encoded_size = 0;
             }
+
+            let mut info = CascFileInfo::new(name.clone(), content_size, encoded_size, is_local);
+            info.set_file_data_id(entry.file_data_id);
             files.push(info);
         }
         Ok(files)
     }
 
+    /// Returns the decoded size of a span from the size recorded by the root, or else from the
+    /// ENCODING file.
+    ///
+    /// This never touches the data files, so it is `None` for spans neither of them describes.
+    fn get_span_content_size(
+        span_info: &SpanInfo,
+        encoding: Option<&CascEncodingTable>,
+    ) -> Option<u64> {
+        span_info.size.map(|size| size as u64).or_else(|| {
+            encoding?
+                .get_entry_by_encoding_key(&span_info.encoding_key)
+                .map(|entry| entry.content_size)
+        })
+    }
+
+    /// Names the storage's files using the listfile at the given path.
+    ///
+    /// See [`CascStorage::apply_listfile`]. Returns the number of files that were named.
+    pub fn load_listfile<P: AsRef<Path>>(&mut self, file_name: P) -> Result<usize, CascError> {
+        let listfile = Listfile::from_file(file_name)?;
+        self.apply_listfile(&listfile)
+    }
+
+    /// Names the storage's files using the given listfile.
+    ///
+    /// This is used for roots that only identify files by FileDataID or name hash, such as
+    /// World of Warcraft. Matched files can then be opened by path with [`CascStorage::open_file`],
+    /// while unmatched files keep their synthetic `unknown/<id>.dat` name.
+    /// Returns the number of files that were named.
+    pub fn apply_listfile(&mut self, listfile: &Listfile) -> Result<usize, CascError> {
+        let renamed = self.root_handler.apply_listfile(listfile);
+        if renamed > 0 {
+            self.files =
+                Self::load_files(&self.root_handler, &self.entries, self.encoding.as_ref())?;
+            let normalizer = self.path_index.normalizer().clone();
+            self.path_index = CascPathIndex::new(
+                normalizer.clone(),
+                self.root_handler.get_file_entries()?.keys(),
+            );
+            self.directory_tree = CascDirectoryTree::new(&self.files, normalizer);
+        }
+        Ok(renamed)
+    }
+
+    /// Returns the `.idx` files that were loaded, one per bucket.
+    ///
+    /// Only the newest generation of each bucket is loaded, as older generations may map
+    /// encoding keys to stale offsets.
+    pub fn index_files(&self) -> &[CascIndexFile] {
+        &self.index_files
+    }
+
+    /// Returns the older generations of `.idx` files that were found but not loaded.
+    ///
+    /// This is intended for diagnosing storages that were not cleaned up after an update.
+    pub fn stale_index_files(&self) -> &[CascIndexFile] {
+        &self.stale_index_files
+    }
+
+    /// Returns the paths of the `.idx` files that were skipped because they failed hash
+    /// validation or could not be read, such as when truncated.
+    ///
+    /// This is only ever non-empty when the storage was opened with [`CorruptIndexPolicy::Skip`].
+    pub fn corrupt_indexes(&self) -> &[PathBuf] {
+        &self.corrupt_indexes
+    }
+
+    /// Returns the names of files listed by the root that cannot be opened, because their
+    /// content keys are missing from the ENCODING file.
+    ///
+    /// These files are left out of [`CascStorage::files`]. Only MNDX roots report them.
+    pub fn unresolved_files(&self) -> &[String] {
+        self.root_handler.get_unresolved_files()
+    }
+
+    /// Returns the names of the manifests that could not be read, so whose assets are not
+    /// listed, such as damaged TVFS sub-manifests, or encrypted Overwatch CMF manifests of a
+    /// build with no [`CmfKeyProvider`](crate::cmf_key_provider::CmfKeyProvider).
+    ///
+    /// Opening the storage succeeds without them, so check this when assets are missing.
+    pub fn skipped_manifests(&self) -> &[String] {
+        self.root_handler.get_skipped_manifests()
+    }
+
+    /// Returns the keys used to decrypt encrypted files.
+    pub fn key_registry(&self) -> &TactKeyRegistry {
+        &self.key_registry
+    }
+
+    /// Sets the keys used to decrypt encrypted files.
+    ///
+    /// Files opened after this call use the new keys; files that are already open keep the old ones.
+    pub fn set_key_registry(&mut self, key_registry: TactKeyRegistry) {
+        self.key_registry = Arc::new(key_registry);
+    }
+
+    /// Returns the parsed ENCODING file, if the storage has one.
+    ///
+    /// The ENCODING file maps content keys (MD5 of the decoded data) to the encoding keys
+    /// the data is stored under in the local archives. TVFS storages whose ENCODING file
+    /// cannot be read are still opened, without it.
+    pub fn encoding_table(&self) -> Option<&CascEncodingTable> {
+        self.encoding.as_ref()
+    }
+
+    /// Returns the encoding keys for the given content key, if it is present in the ENCODING file.
+    pub fn get_encoding_keys(&self, content_key: &[u8]) -> Option<&[Vec<u8>]> {
+        self.encoding.as_ref()?.get_encoding_keys(content_key)
+    }
+
+    /// Returns the normaliser used to match paths to stored file names.
+    pub fn path_normalizer(&self) -> &PathNormalizer {
+        self.path_index.normalizer()
+    }
+
+    /// Returns the name a file is stored under, matching the given path exactly or, failing
+    /// that, after normalising it with the storage's [`PathNormalizer`].
+    pub fn resolve_path(&self, path: &str) -> Option<&str> {
+        let (name, _) = self.find_entry(path).ok()?;
+        Some(name)
+    }
+
+    /// Looks up the root entry for a path, trying the exact stored name before the normalised one.
+    fn find_entry(&self, path: &str) -> Result<(&String, &Entry), CascError> {
+        let file_entries = self.root_handler.get_file_entries()?;
+        file_entries
+            .get_key_value(path)
+            .or_else(|| file_entries.get_key_value(self.path_index.get(path)?))
+            .ok_or_else(|| CascError::FileNotFound(format!("Entry not found: {path}")))
+    }
+
     /// Opens a file from the CASC storage by name, returning a new, independent handle.
     ///
+    /// The name is matched exactly first, then after normalising separators and case with the
+    /// storage's [`PathNormalizer`], so `Data/foo.bin` and `DATA\FOO.BIN` open the same file.
+    ///
     /// Each call returns a fresh `CascFile` with its own file position and cache,
     /// allowing safe, parallel reads from multiple threads, just like `std::fs::File::open` on Windows.
     ///
     /// This method is thread safe; all access to the underlying data files is synchronized internally.
     pub fn open_file(&self, entry: &str) -> Result<CascFile, CascError> {
-        let entry = self
+        let (_, entry) = self.find_entry(entry)?;
+
+        self.open_entry(entry)
+    }
+
+    /// Opens a file from the CASC storage by FileDataID, returning a new, independent handle.
+    ///
+    /// This is only supported for roots that identify files numerically, such as World of Warcraft.
+    /// Like [`CascStorage::open_file`], each call returns a fresh `CascFile`.
+    pub fn open_file_by_id(&self, file_data_id: u32) -> Result<CascFile, CascError> {
+        let name = self
+            .root_handler
+            .get_file_data_ids()
+            .ok_or_else(|| {
+                CascError::UnsupportedFileType("Root does not support FileDataIDs".to_string())
+            })?
+            .get(&file_data_id)
+            .ok_or_else(|| {
+                CascError::FileNotFound(format!("FileDataID not found: {file_data_id}"))
+            })?;
+
+        self.open_file(name)
+    }
+
+    /// Opens an asset from the CASC storage by GUID, returning a new, independent handle.
+    ///
+    /// This is only supported for roots that identify assets by GUID, such as Overwatch, and
+    /// only for assets listed in a manifest that could be read. If the GUID is not listed
+    /// while some manifests were skipped, [`CascError::UnlistedGuid`] names them.
+    /// Like [`CascStorage::open_file`], each call returns a fresh `CascFile`.
+    pub fn open_file_by_guid(&self, guid: u64) -> Result<CascFile, CascError> {
+        let name = self
             .root_handler
-            .get_file_entries()?
-            .get(entry)
-            .ok_or_else(|| CascError::FileNotFound(format!("Entry not found: {entry}")))?;
+            .get_guids()
+            .ok_or_else(|| {
+                CascError::UnsupportedFileType("Root does not support GUIDs".to_string())
+            })?
+            .get(&guid)
+            .ok_or_else(|| match self.skipped_manifests() {
+                [] => CascError::FileNotFound(format!("GUID not found: {guid:016X}")),
+                skipped_manifests => CascError::UnlistedGuid {
+                    guid,
+                    skipped_manifests: skipped_manifests.to_vec(),
+                },
+            })?;
+
+        self.open_file(name)
+    }
+
+    /// Reads the storage's INSTALL manifest, which lists the files installed outside of the
+    /// storage, with their content keys, sizes and tags.
+    pub fn install_manifest(&self) -> Result<InstallManifest, CascError> {
+        Self::load_install_manifest(
+            &self.config,
+            &self.data_file_paths,
+            &self.entries,
+            self.encoding.as_ref(),
+        )
+    }
+
+    /// Verifies that the decoded contents of a file hash to its content key.
+    ///
+    /// Each span of the file is decoded in full and its MD5 compared against the content key
+    /// recorded for it, taken from the root (such as the TVFS content file table) when present,
+    /// or from the ENCODING file otherwise. A mismatch is reported as
+    /// [`CascError::FileCorrupted`], and a damaged frame as [`CascError::FrameHashMismatch`]
+    /// when the storage verifies frames.
+    pub fn verify_file(&self, entry: &str) -> Result<(), CascError> {
+        let (_, entry) = self.find_entry(entry)?;
+
+        for span in &entry.spans {
+            if !self.entries.contains_key(&span.base64_encoding_key) {
+                return Err(CascError::FileNotFound(format!(
+                    "Encoding key not found: {}",
+                    hex::encode(&span.encoding_key)
+                )));
+            }
+            let content_key = self.get_span_content_key(span).ok_or_else(|| {
+                CascError::InvalidData(format!(
+                    "No content key for encoding key: {}",
+                    hex::encode(&span.encoding_key)
+                ))
+            })?;
+
+            // The content key covers the whole blob, even if the span only references part of it
+            let key_entry = &self.entries[&span.base64_encoding_key];
+            let mut file = Self::open_file_from_entry(&self.data_file_paths, key_entry)?;
+            file.set_key_registry(Some(self.key_registry.clone()));
+            file.verify(self.verify_frames);
+            let mut reader = CascVerifyingReader::new(file, content_key);
+            std::io::copy(&mut reader, &mut std::io::sink()).map_err(|err| {
+                match err.downcast::<CascError>() {
+                    Ok(err) => err,
+                    Err(err) => CascError::Io(err),
+                }
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Checks the integrity of the whole storage.
+    ///
+    /// Every span in the key mapping tables has its span header and BLTE signature checked,
+    /// and is decoded in full to check its frame hashes and content hash, as configured by
+    /// `options`. Every root entry is checked for local data. Problems are collected into the
+    /// returned [`VerificationReport`] rather than failing the scan.
+    pub fn verify_all(&self, options: &VerifyOptions) -> Result<VerificationReport, CascError> {
+        let mut report = VerificationReport::default();
+
+        // Content keys recorded by the root take precedence over the ENCODING file
+        let mut referenced: HashMap<&str, Option<[u8; 16]>> = HashMap::new();
+        for (name, entry) in self.root_handler.get_file_entries()? {
+            let mut is_local = true;
+            for span in &entry.spans {
+                is_local &= self.entries.contains_key(&span.base64_encoding_key);
+                let content_key = span
+                    .content_key
+                    .as_deref()
+                    .and_then(|key| key.try_into().ok());
+                let known = referenced
+                    .entry(span.base64_encoding_key.as_str())
+                    .or_default();
+                if known.is_none() {
+                    *known = content_key;
+                }
+            }
+            if !is_local {
+                report.missing_root_entries.push(name.clone());
+            }
+        }
+        report.missing_root_entries.sort();
+
+        // The ENCODING, root and TVFS manifest files are referenced by the build configuration
+        let mut config_keys = HashSet::new();
+        for variable in self.config.variables() {
+            if variable.name != "encoding" && !variable.name.starts_with("vfs-") {
+                continue;
+            }
+            if let Some(Ok(key)) = variable.values.get(1).map(hex::decode) {
+                config_keys.insert(encoding_key_to_base64(&key));
+            }
+        }
+        if let Ok(root) = Self::find_root_entry(&self.config, &self.entries, self.encoding.as_ref())
+        {
+            config_keys.insert(encoding_key_to_base64(&root.encoding_key));
+        }
+
+        let mut missing_data_files = BTreeSet::new();
+        let mut spans: Vec<(&CascKeyMappingTableEntry, Option<[u8; 16]>)> = Vec::new();
+        for (key, entry) in &self.entries {
+            let encoding_entry = self
+                .encoding
+                .as_ref()
+                .and_then(|encoding| encoding.get_entry_by_encoding_key(&entry.encoding_key));
+            if !referenced.contains_key(key.as_str())
+                && encoding_entry.is_none()
+                && !config_keys.contains(key)
+            {
+                report
+                    .orphaned_index_entries
+                    .push(entry.encoding_key.clone());
+            }
+
+            if entry.archive_index as usize >= self.data_file_paths.len() {
+                missing_data_files.insert(entry.archive_index);
+                continue;
+            }
+            let content_key = referenced.get(key.as_str()).copied().flatten().or_else(|| {
+                encoding_entry.and_then(|entry| entry.content_key.as_slice().try_into().ok())
+            });
+            spans.push((entry, content_key));
+        }
+        report.missing_data_files = missing_data_files.into_iter().collect();
+        report.orphaned_index_entries.sort();
+
+        let check = |(entry, content_key)| (entry, self.verify_span(entry, content_key, options));
+        #[cfg(feature = "rayon")]
+        let results: Vec<_> = if options.parallel {
+            spans.into_par_iter().map(check).collect()
+        } else {
+            spans.into_iter().map(check).collect()
+        };
+        #[cfg(not(feature = "rayon"))]
+        let results: Vec<_> = spans.into_iter().map(check).collect();
+
+        for (entry, result) in results {
+            report.checked_spans += 1;
+            let (issues, reason) = match result {
+                Ok(()) => continue,
+                Err(SpanFailure::Unreadable(reason)) => (&mut report.unreadable_spans, reason),
+                Err(SpanFailure::HashMismatch(reason)) => (&mut report.hash_mismatches, reason),
+            };
+            issues.push(SpanIssue {
+                encoding_key: entry.encoding_key.clone(),
+                archive_index: entry.archive_index,
+                offset: entry.offset,
+                reason,
+            });
+        }
+        let order = |issue: &SpanIssue| (issue.archive_index, issue.offset);
+        report.unreadable_spans.sort_by_key(order);
+        report.hash_mismatches.sort_by_key(order);
+
+        Ok(report)
+    }
 
+    /// Checks a single span from the key mapping tables.
+    fn verify_span(
+        &self,
+        entry: &CascKeyMappingTableEntry,
+        content_key: Option<[u8; 16]>,
+        options: &VerifyOptions,
+    ) -> Result<(), SpanFailure> {
+        let unreadable = |err: CascError| SpanFailure::Unreadable(err.to_string());
+
+        let path = Self::get_data_file_path(&self.data_file_paths, entry.archive_index)
+            .map_err(unreadable)?;
+        let mut reader = File::open(path).map_err(|err| unreadable(err.into()))?;
+        reader
+            .seek(SeekFrom::Start(entry.offset))
+            .map_err(|err| unreadable(err.into()))?;
+        let header = reader
+            .read_struct::<CascSpanHeader>()
+            .map_err(|err| unreadable(err.into()))?;
+
+        // The span header stores the encoding key byte reversed
+        if !header
+            .encoding_key
+            .iter()
+            .rev()
+            .take(entry.encoding_key.len())
+            .eq(entry.encoding_key.iter())
+        {
+            return Err(SpanFailure::Unreadable(
+                "Span header encoding key does not match the index".to_string(),
+            ));
+        }
+        if header.content_size as u32 != entry.size {
+            return Err(SpanFailure::Unreadable(format!(
+                "Span header size {} does not match the index size {}",
+                { header.content_size },
+                entry.size
+            )));
+        }
+        if header.jenkins_hash != 0 && header.jenkins_hash != header.compute_jenkins_hash() {
+            return Err(SpanFailure::HashMismatch(
+                "Span header hash mismatch".to_string(),
+            ));
+        }
+
+        let mut file =
+            Self::open_file_from_entry(&self.data_file_paths, entry).map_err(unreadable)?;
+        file.set_key_registry(Some(self.key_registry.clone()));
+        file.verify(options.frame_hashes);
+
+        let result = match content_key {
+            Some(content_key) if options.content_hashes => std::io::copy(
+                &mut CascVerifyingReader::new(file, content_key),
+                &mut std::io::sink(),
+            ),
+            _ => std::io::copy(&mut file, &mut std::io::sink()),
+        };
+        result
+            .map(|_| ())
+            .map_err(|err| match err.downcast::<CascError>() {
+                Ok(CascError::FileCorrupted(reason)) => SpanFailure::HashMismatch(reason),
+                Ok(err @ CascError::FrameHashMismatch { .. }) => {
+                    SpanFailure::HashMismatch(err.to_string())
+                }
+                Ok(err) => unreadable(err),
+                Err(err) => unreadable(err.into()),
+            })
+    }
+
+    /// Returns the content key of a span, from the root if it records one, or the ENCODING file.
+    fn get_span_content_key(&self, span: &SpanInfo) -> Option<[u8; 16]> {
+        let content_key = match &span.content_key {
+            Some(content_key) => content_key.as_slice(),
+            None => self
+                .encoding
+                .as_ref()?
+                .get_entry_by_encoding_key(&span.encoding_key)?
+                .content_key
+                .as_slice(),
+        };
+        content_key.try_into().ok()
+    }
+
+    /// Returns the files of the storage arranged into folders.
+    pub fn directory_tree(&self) -> &CascDirectoryTree {
+        &self.directory_tree
+    }
+
+    /// Returns the entries directly within the folder at the given path.
+    ///
+    /// An empty path names the root folder. Both `\` and `/` are accepted as separators.
+    pub fn read_dir(
+        &self,
+        path: &str,
+    ) -> Result<impl Iterator<Item = &CascDirEntry> + '_, CascError> {
+        let entry = self.metadata(path)?;
+        if !entry.is_dir() {
+            return Err(CascError::InvalidData(format!("Not a directory: {path}")));
+        }
+        Ok(self.directory_tree.children(entry))
+    }
+
+    /// Returns the folder or file at the given path, with its aggregate sizes.
+    pub fn metadata(&self, path: &str) -> Result<&CascDirEntry, CascError> {
+        self.directory_tree
+            .get(path)
+            .ok_or_else(|| CascError::FileNotFound(format!("Path not found: {path}")))
+    }
+
+    /// Returns an iterator over every folder and file in the storage, depth first.
+    pub fn walk(&self) -> CascWalk<'_> {
+        self.directory_tree.walk()
+    }
+
+    /// Returns detailed metadata about a file: its keys, sizes, archive locations and the BLTE
+    /// encodings of its frames.
+    ///
+    /// This reads the BLTE frame table of every local span of the file.
+    pub fn file_details(&self, entry: &str) -> Result<CascFileDetails, CascError> {
+        let (name, entry) = self.find_entry(entry)?;
+
+        let spans = entry
+            .spans
+            .iter()
+            .map(|span| self.get_span_details(span))
+            .collect::<Result<Vec<_>, _>>()?;
+        let content_key = match spans.as_slice() {
+            [span] => span.content_key.clone(),
+            _ => None,
+        };
+
+        Ok(CascFileDetails {
+            file_name: name.clone(),
+            content_key,
+            content_size: spans.iter().map(|span| span.content_size).sum(),
+            encoded_size: spans.iter().map(|span| span.encoded_size).sum(),
+            spans,
+        })
+    }
+
+    fn get_span_details(&self, span: &SpanInfo) -> Result<CascSpanDetails, CascError> {
+        let key_entry = self.entries.get(&span.base64_encoding_key);
+        let mut details = CascSpanDetails {
+            content_key: self.get_span_content_key(span).map(|key| key.to_vec()),
+            encoding_key: span.encoding_key.clone(),
+            content_size: Self::get_span_content_size(span, self.encoding.as_ref()).unwrap_or(0),
+            blob_size: span.blob_size,
+            encoded_size: 0,
+            espec: span.espec.clone().or_else(|| {
+                let encoding = self.encoding.as_ref()?;
+                let spec = encoding.get_encoding_spec(&span.encoding_key)?;
+                encoding.get_espec(spec.espec_index).map(str::to_string)
+            }),
+            archive_index: None,
+            archive_offset: None,
+            frame_count: 0,
+            encodings: Vec::new(),
+        };
+        let Some(key_entry) = key_entry else {
+            return Ok(details);
+        };
+        details.encoded_size = key_entry.size as u64;
+        details.archive_index = Some(key_entry.archive_index);
+        details.archive_offset = Some(key_entry.offset);
+
+        let mut file = Self::open_file_from_entry(&self.data_file_paths, key_entry)?;
+        if details.content_size == 0 {
+            details.content_size = file.size();
+        }
+        for file_span in &mut file.spans {
+            for frame in &file_span.frames {
+                file_span
+                    .span_reader
+                    .seek(SeekFrom::Start(frame.archive_offset))?;
+                let mut encoding = [0u8];
+                file_span.span_reader.read_exact(&mut encoding)?;
+                let encoding = BlockTableEncoderType::from(encoding[0]);
+                if !details.encodings.contains(&encoding) {
+                    details.encodings.push(encoding);
+                }
+            }
+            details.frame_count += file_span.frames.len() as u32;
+        }
+        Ok(details)
+    }
+
+    /// Returns the files whose names match the given pattern, in the order of
+    /// [`CascStorage::files`].
+    ///
+    /// ```rust,no_run
+    /// use casc_rs::{casc_file_pattern::CascFilePattern, casc_storage::CascStorage};
+    ///
+    /// let storage = CascStorage::open("path/to/casc/storage").unwrap();
+    /// let pattern = CascFilePattern::glob("**/*.xpak").unwrap();
+    /// for file_info in storage.find(&pattern) {
+    ///     println!("{}", file_info.file_name());
+    /// }
+    /// ```
+    pub fn find(&self, pattern: &CascFilePattern) -> Vec<&CascFileInfo> {
+        self.files
+            .iter()
+            .filter(|info| pattern.matches(info.file_name()))
+            .collect()
+    }
+
+    /// Returns an iterator over the files whose names match the given pattern.
+    ///
+    /// Like [`CascStorage::find`], but names are matched lazily as the iterator advances.
+    pub fn find_iter<'a>(
+        &'a self,
+        pattern: &'a CascFilePattern,
+    ) -> impl Iterator<Item = &'a CascFileInfo> + 'a {
+        self.files
+            .iter()
+            .filter(move |info| pattern.matches(info.file_name()))
+    }
+
+    /// Returns an iterator over the files that have a FileDataID, paired with that ID.
+    pub fn files_by_id(&self) -> impl Iterator<Item = (u32, &CascFileInfo)> {
+        self.files
+            .iter()
+            .filter_map(|info| info.file_data_id().map(|id| (id, info)))
+    }
+
+    fn open_entry(&self, entry: &Entry) -> Result<CascFile, CascError> {
+        self.open_spans(&entry.spans)
+    }
+
+    fn open_spans(&self, span_infos: &[SpanInfo]) -> Result<CascFile, CascError> {
         let mut virtual_offset = 0u64;
         let mut spans: Vec<CascFileSpan<File>> = Vec::new();
 
-        for span in &entry.spans {
+        for span in span_infos {
             if let Some(e) = self.entries.get(&span.base64_encoding_key) {
-                let path = &self.data_file_paths[e.archive_index as usize];
+                let path = Self::get_data_file_path(&self.data_file_paths, e.archive_index)?;
                 let mut reader = File::open(path)?;
                 reader.seek(SeekFrom::Start(e.offset))?;
 
@@ -326,7 +1315,7 @@ impl CascStorage {
                 let _ = reader.read_struct::<CascSpanHeader>()?;
                 let header = reader.read_struct::<BlockTableHeader>()?;
 
-                if header.signature != 0x45544C42 {
+                if header.signature != BLTE_SIGNATURE {
                     return Err(CascError::InvalidData(format!(
                         "Invalid Block Table Header signature: {:#X}",
                         header.signature
@@ -345,20 +1334,37 @@ impl CascStorage {
                 let span_virtual_start_offset = virtual_offset;
                 let mut frames = Vec::new();
 
-                for block_table_frame in block_table_frames {
+                // The span may only reference part of the decoded blob
+                let span_start = span.offset;
+                let span_end = span
+                    .size
+                    .map_or(u64::MAX, |size| span_start.saturating_add(size as u64));
+                let mut blob_offset = 0u64;
+
+                for (index, block_table_frame) in block_table_frames.into_iter().enumerate() {
                     //Swap from BE to LE
                     let encoded_size = i32::from_be(block_table_frame.encoded_size) as u32;
                     let content_size = i32::from_be(block_table_frame.content_size) as u32;
-                    let frame = CascFileFrame {
-                        archive_offset,
-                        encoded_size,
-                        content_size,
-                        virtual_start_offset: virtual_offset,
-                        virtual_end_offset: virtual_offset + content_size as u64,
-                    };
+                    let frame_start = blob_offset;
+                    let frame_end = blob_offset + content_size as u64;
+                    let start = frame_start.max(span_start);
+                    let end = frame_end.min(span_end);
+                    if start < end {
+                        let frame = CascFileFrame {
+                            index: index as u32,
+                            archive_offset,
+                            encoded_size,
+                            content_size,
+                            content_offset: (start - frame_start) as u32,
+                            hash: block_table_frame.hash(),
+                            virtual_start_offset: virtual_offset,
+                            virtual_end_offset: virtual_offset + (end - start),
+                        };
+                        virtual_offset += end - start;
+                        frames.push(frame);
+                    }
                     archive_offset += encoded_size as u64;
-                    virtual_offset += content_size as u64;
-                    frames.push(frame);
+                    blob_offset = frame_end;
                 }
 
                 let new_span = CascFileSpan::<File>::new(
@@ -371,7 +1377,10 @@ impl CascStorage {
                 spans.push(new_span);
             };
         }
-        Ok(CascFile::new(spans, virtual_offset))
+        let mut file = CascFile::new(spans, virtual_offset);
+        file.set_key_registry(Some(self.key_registry.clone()));
+        file.verify(self.verify_frames);
+        Ok(file)
     }
 
     pub(crate) fn open_file_from_entry(
@@ -382,14 +1391,17 @@ impl CascStorage {
         let mut spans: Vec<CascFileSpan<File>> = Vec::new();
 
         // Open a new file handle for independent reading
-        let mut reader = File::open(&data_file_paths[entry.archive_index as usize])?;
+        let mut reader = File::open(Self::get_data_file_path(
+            data_file_paths,
+            entry.archive_index,
+        )?)?;
         reader.seek(SeekFrom::Start(entry.offset))?;
 
         // Read and discard the span header
         let _ = reader.read_struct::<CascSpanHeader>()?;
         let header = reader.read_struct::<BlockTableHeader>()?;
 
-        if header.signature != 0x45544C42 {
+        if header.signature != BLTE_SIGNATURE {
             return Err(CascError::InvalidData(
                 "Invalid Block Table Header signature".to_string(),
             ));
@@ -406,14 +1418,17 @@ impl CascStorage {
         let span_virtual_start_offset = virtual_offset;
         let mut frames = Vec::new();
 
-        for block_table_frame in block_table_frames {
+        for (index, block_table_frame) in block_table_frames.into_iter().enumerate() {
             //Swap from BE to LE
             let encoded_size = i32::from_be(block_table_frame.encoded_size) as u32;
             let content_size = i32::from_be(block_table_frame.content_size) as u32;
             let frame = CascFileFrame {
+                index: index as u32,
                 archive_offset,
                 encoded_size,
                 content_size,
+                content_offset: 0,
+                hash: block_table_frame.hash(),
                 virtual_start_offset: virtual_offset,
                 virtual_end_offset: virtual_offset + content_size as u64,
             };
@@ -0,0 +1,116 @@
+//! Module for full-storage integrity scans.
+//!
+//! [`CascStorage::verify_all`](crate::casc_storage::CascStorage::verify_all) checks every
+//! span in the storage's key mapping tables and every root entry, and collects the problems it
+//! finds into a [`VerificationReport`].
+
+/// Options used when verifying a whole storage.
+///
+/// ```rust
+/// use casc_rs::casc_verification::VerifyOptions;
+///
+/// let options = VerifyOptions::new().parallel(true).content_hashes(false);
+/// ```
+#[derive(Debug, Clone)]
+pub struct VerifyOptions {
+    /// Whether spans are checked in parallel.
+    pub(crate) parallel: bool,
+    /// Whether the MD5 hash of each encoded frame is checked.
+    pub(crate) frame_hashes: bool,
+    /// Whether the decoded data of each span is checked against its content key.
+    pub(crate) content_hashes: bool,
+}
+
+impl Default for VerifyOptions {
+    fn default() -> Self {
+        Self {
+            parallel: false,
+            frame_hashes: true,
+            content_hashes: true,
+        }
+    }
+}
+
+impl VerifyOptions {
+    /// Creates a new set of options with the default values.
+    ///
+    /// By default spans are checked one at a time, with both frame and content hashes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether spans are checked in parallel on the rayon thread pool.
+    ///
+    /// This requires the `rayon` feature, without which spans are always checked one at a
+    /// time.
+    pub fn parallel(mut self, parallel: bool) -> Self {
+        self.parallel = parallel;
+        self
+    }
+
+    /// Sets whether the MD5 hash of each encoded frame is checked against its block table.
+    pub fn frame_hashes(mut self, frame_hashes: bool) -> Self {
+        self.frame_hashes = frame_hashes;
+        self
+    }
+
+    /// Sets whether the decoded data of each span is checked against its content key.
+    ///
+    /// Spans whose content key is unknown are only checked structurally.
+    pub fn content_hashes(mut self, content_hashes: bool) -> Self {
+        self.content_hashes = content_hashes;
+        self
+    }
+}
+
+/// Describes a problem found with a single span of data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpanIssue {
+    /// The (truncated) encoding key of the span, as stored in the key mapping table.
+    pub encoding_key: Vec<u8>,
+    /// The index of the data file holding the span.
+    pub archive_index: u32,
+    /// The offset of the span within the data file.
+    pub offset: u64,
+    /// A description of the problem.
+    pub reason: String,
+}
+
+/// The result of a full-storage integrity scan.
+#[derive(Debug, Default, Clone)]
+pub struct VerificationReport {
+    /// The number of spans that were checked.
+    pub checked_spans: usize,
+    /// Indices of data files that are referenced by the key mapping tables but do not exist.
+    pub missing_data_files: Vec<u32>,
+    /// Spans that could not be read or decoded, such as spans with an invalid span header or
+    /// BLTE signature, or frames encrypted with a missing key.
+    pub unreadable_spans: Vec<SpanIssue>,
+    /// Spans whose frame hashes or content hash did not match their data.
+    pub hash_mismatches: Vec<SpanIssue>,
+    /// Encoding keys in the key mapping tables that are not referenced by the root, the
+    /// ENCODING file or the build configuration.
+    pub orphaned_index_entries: Vec<Vec<u8>>,
+    /// Names of root entries with at least one span that has no local data.
+    pub missing_root_entries: Vec<String>,
+}
+
+impl VerificationReport {
+    /// Returns whether the scan found no problems.
+    ///
+    /// Orphaned index entries are not counted as problems, as they do not affect any file.
+    pub fn is_ok(&self) -> bool {
+        self.missing_data_files.is_empty()
+            && self.unreadable_spans.is_empty()
+            && self.hash_mismatches.is_empty()
+            && self.missing_root_entries.is_empty()
+    }
+}
+
+/// The ways in which checking a single span can fail.
+pub(crate) enum SpanFailure {
+    /// The span could not be read or decoded.
+    Unreadable(String),
+    /// The span was read, but a frame or content hash did not match.
+    HashMismatch(String),
+}
@@ -0,0 +1,83 @@
+//! Module for verifying decoded file contents against their content key.
+//!
+//! A content key is the MD5 hash of a file's decoded data. [`CascVerifyingReader`] hashes the
+//! data as it is read, so a file can be checked while it is being extracted.
+use crate::error::CascError;
+use md5::{Digest, Md5};
+use std::io::{self, Error, Read};
+
+/// Wraps a reader, computing the MD5 hash of everything read through it.
+///
+/// Once the inner reader reaches EOF, the hash is compared against the expected content key.
+/// A mismatch fails that final read with an [`io::Error`] wrapping
+/// [`CascError::FileCorrupted`].
+///
+/// ```no_run
+/// use casc_rs::casc_storage::CascStorage;
+/// use casc_rs::casc_verifying_reader::CascVerifyingReader;
+/// use std::io::Read;
+///
+/// let storage = CascStorage::open("path/to/storage").unwrap();
+/// let file = storage.open_file("example.txt").unwrap();
+/// let content_key = [0u8; 16];
+/// let mut reader = CascVerifyingReader::new(file, content_key);
+/// let mut data = Vec::new();
+/// reader.read_to_end(&mut data).unwrap();
+/// ```
+pub struct CascVerifyingReader<R: Read> {
+    /// The reader being verified.
+    inner: R,
+    /// The expected MD5 hash of the data.
+    content_key: [u8; 16],
+    /// The hash of the data read so far.
+    hasher: Md5,
+    /// Whether the hash has been compared, once EOF is reached.
+    finished: bool,
+}
+
+impl<R: Read> CascVerifyingReader<R> {
+    /// Creates a new `CascVerifyingReader` that checks the data read from `inner` against
+    /// `content_key`.
+    pub fn new(inner: R, content_key: [u8; 16]) -> Self {
+        Self {
+            inner,
+            content_key,
+            hasher: Md5::new(),
+            finished: false,
+        }
+    }
+
+    /// Returns the content key the data is checked against.
+    pub fn content_key(&self) -> &[u8; 16] {
+        &self.content_key
+    }
+
+    /// Consumes the wrapper, returning the inner reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for CascVerifyingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.finished {
+            return Ok(0);
+        }
+        let n = self.inner.read(buf)?;
+        if n > 0 || buf.is_empty() {
+            self.hasher.update(&buf[..n]);
+            return Ok(n);
+        }
+
+        self.finished = true;
+        let hash = std::mem::take(&mut self.hasher).finalize();
+        if hash.as_slice() != self.content_key {
+            return Err(Error::other(CascError::FileCorrupted(format!(
+                "Content hash mismatch: expected {}, got {}",
+                hex::encode(self.content_key),
+                hex::encode(hash)
+            ))));
+        }
+        Ok(0)
+    }
+}
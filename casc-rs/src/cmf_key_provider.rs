@@ -0,0 +1,171 @@
+//! Module for decrypting the content manifests (CMF) of Overwatch storages.
+//!
+//! Overwatch lists its assets in CMF manifests, which newer builds encrypt with AES-256 in CBC
+//! mode. The key and IV are generated by procedures that change with every build, and are
+//! built around a key table shipped in the game client. They are not part of the storage, so
+//! they are supplied by implementing [`CmfKeyProvider`] for each build and registering it with
+//! [`CascOpenOptions::cmf_key_provider`](crate::casc_open_options::CascOpenOptions::cmf_key_provider).
+//!
+//! Most builds only differ in their key table and in the arithmetic of their procedures, so a
+//! build can also be described by a [`CmfKeyTable`] holding its table and procedures.
+//!
+//! ```rust
+//! use casc_rs::cmf_key_provider::{procedures, CmfKeyTable};
+//!
+//! // The key table of the build, copied from the game client
+//! let table = [0u8; CmfKeyTable::SIZE];
+//! let provider = CmfKeyTable::new(50000, table, procedures::key_by_build, procedures::iv_by_digest);
+//! ```
+use std::fmt::Debug;
+
+/// The header of a CMF manifest.
+#[derive(Debug, Clone)]
+pub struct CmfHeader {
+    /// The build of the game the manifest belongs to.
+    pub build_version: u32,
+    /// The number of asset records in the manifest.
+    pub data_count: u32,
+    /// The number of entries in the manifest.
+    pub entry_count: u32,
+    /// The magic, `cmf` followed by the format version in the low byte.
+    pub magic: u32,
+    /// Every field of the header, in order, including those above.
+    ///
+    /// The layout of the header changes between builds, so procedures that use other fields
+    /// can read them from here.
+    pub fields: Vec<u32>,
+}
+
+impl CmfHeader {
+    /// The first format version that is encrypted.
+    pub const ENCRYPTED_VERSION: u8 = 0x14;
+
+    /// Returns the format version of the manifest.
+    pub fn version(&self) -> u8 {
+        self.magic as u8
+    }
+
+    /// Returns whether the records following the header are encrypted.
+    pub fn is_encrypted(&self) -> bool {
+        self.version() >= Self::ENCRYPTED_VERSION
+    }
+}
+
+/// Generates the key and IV used to decrypt the CMF manifests of one Overwatch build.
+///
+/// Implementations usually port the key table procedures of a single build, indexing into
+/// that build's key table with values taken from the header and the digest.
+pub trait CmfKeyProvider: Send + Sync + Debug {
+    /// Returns the build the procedures apply to, matched against
+    /// [`CmfHeader::build_version`].
+    fn build_version(&self) -> u32;
+
+    /// Generates the AES key, `length` bytes long.
+    fn key(&self, header: &CmfHeader, length: usize) -> Vec<u8>;
+
+    /// Generates the AES IV, `length` bytes long.
+    ///
+    /// `digest` is the SHA-1 hash of the manifest's file name, without its folders or
+    /// extension, such as `Win_SPWin_RCN_LenUS_EExt`.
+    fn iv(&self, header: &CmfHeader, digest: &[u8; 20], length: usize) -> Vec<u8>;
+}
+
+/// A procedure generating a key from a build's key table and a manifest header.
+pub type CmfKeyProcedure = fn(&[u8; CmfKeyTable::SIZE], &CmfHeader, usize) -> Vec<u8>;
+
+/// A procedure generating an IV from a build's key table, a manifest header and the digest of
+/// the manifest's file name.
+pub type CmfIvProcedure = fn(&[u8; CmfKeyTable::SIZE], &CmfHeader, &[u8; 20], usize) -> Vec<u8>;
+
+/// The key table and procedures of one Overwatch build.
+#[derive(Debug, Clone)]
+pub struct CmfKeyTable {
+    /// The build the table belongs to.
+    build_version: u32,
+    /// The key table, indexed by the procedures.
+    table: Box<[u8; CmfKeyTable::SIZE]>,
+    /// The procedure generating the AES key.
+    key: CmfKeyProcedure,
+    /// The procedure generating the AES IV.
+    iv: CmfIvProcedure,
+}
+
+impl CmfKeyTable {
+    /// The size of a key table in bytes.
+    pub const SIZE: usize = 512;
+
+    /// Creates a provider for the given build from its key table and procedures.
+    pub fn new(
+        build_version: u32,
+        table: [u8; Self::SIZE],
+        key: CmfKeyProcedure,
+        iv: CmfIvProcedure,
+    ) -> Self {
+        Self {
+            build_version,
+            table: Box::new(table),
+            key,
+            iv,
+        }
+    }
+}
+
+impl CmfKeyProvider for CmfKeyTable {
+    fn build_version(&self) -> u32 {
+        self.build_version
+    }
+
+    fn key(&self, header: &CmfHeader, length: usize) -> Vec<u8> {
+        (self.key)(&self.table, header, length)
+    }
+
+    fn iv(&self, header: &CmfHeader, digest: &[u8; 20], length: usize) -> Vec<u8> {
+        (self.iv)(&self.table, header, digest, length)
+    }
+}
+
+/// Procedures for a [`CmfKeyTable`], for builds whose procedures take this shape.
+///
+/// All indices into the key table wrap around its size.
+pub mod procedures {
+    use super::{CmfHeader, CmfKeyTable};
+
+    /// Reads every third byte of the key table, starting at the index stored in the table at
+    /// the low bits of the build.
+    pub fn key_by_build(
+        table: &[u8; CmfKeyTable::SIZE],
+        header: &CmfHeader,
+        length: usize,
+    ) -> Vec<u8> {
+        let mut index = table[header.build_version as usize % CmfKeyTable::SIZE] as u32;
+        (0..length)
+            .map(|_| {
+                let byte = table[index as usize % CmfKeyTable::SIZE];
+                index = index.wrapping_add(3);
+                byte
+            })
+            .collect()
+    }
+
+    /// Walks the key table from the eighth byte of the digest plus the record count, stepping
+    /// by the entry count and a byte of the digest, and mixes each byte with the digest.
+    ///
+    /// The index is a 32-bit integer that wraps on overflow.
+    pub fn iv_by_digest(
+        table: &[u8; CmfKeyTable::SIZE],
+        header: &CmfHeader,
+        digest: &[u8; 20],
+        length: usize,
+    ) -> Vec<u8> {
+        let mut index = (digest[7] as u32 + header.data_count as u16 as u32) % 512;
+        (0..length as u32)
+            .map(|i| {
+                let byte = table[index as usize % CmfKeyTable::SIZE];
+                index = index
+                    .wrapping_add(header.entry_count)
+                    .wrapping_add(digest[index as usize % digest.len()] as u32);
+                byte ^ digest[index.wrapping_sub(i) as usize % digest.len()]
+            })
+            .collect()
+    }
+}
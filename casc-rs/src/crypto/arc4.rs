@@ -0,0 +1,59 @@
+//! Implementation of the ARC4 (RC4) stream cipher, as used by encrypted BLTE frames.
+
+/// An ARC4 cipher instance.
+pub(crate) struct Arc4 {
+    state: [u8; 256],
+    i: u8,
+    j: u8,
+}
+
+impl Arc4 {
+    /// Creates a new cipher from the given key, which must be 1 to 256 bytes long.
+    pub(crate) fn new(key: &[u8]) -> Option<Self> {
+        if key.is_empty() || key.len() > 256 {
+            return None;
+        }
+        let mut state = [0u8; 256];
+        for (i, value) in state.iter_mut().enumerate() {
+            *value = i as u8;
+        }
+        let mut j = 0u8;
+        for i in 0..256 {
+            j = j.wrapping_add(state[i]).wrapping_add(key[i % key.len()]);
+            state.swap(i, j as usize);
+        }
+        Some(Self { state, i: 0, j: 0 })
+    }
+
+    /// Encrypts or decrypts `data` in place.
+    pub(crate) fn apply_keystream(&mut self, data: &mut [u8]) {
+        for byte in data.iter_mut() {
+            self.i = self.i.wrapping_add(1);
+            self.j = self.j.wrapping_add(self.state[self.i as usize]);
+            self.state.swap(self.i as usize, self.j as usize);
+            let index = self.state[self.i as usize].wrapping_add(self.state[self.j as usize]);
+            *byte ^= self.state[index as usize];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_known_vectors() {
+        for (key, plaintext, ciphertext) in [
+            ("Key", "Plaintext", "BBF316E8D940AF0AD3"),
+            ("Wiki", "pedia", "1021BF0420"),
+            ("Secret", "Attack at dawn", "45A01F645FC35B383552544B9BF5"),
+        ] {
+            let mut data = plaintext.as_bytes().to_vec();
+            Arc4::new(key.as_bytes())
+                .unwrap()
+                .apply_keystream(&mut data);
+            assert_eq!(hex::encode_upper(data), ciphertext);
+        }
+        assert!(Arc4::new(&[]).is_none());
+    }
+}
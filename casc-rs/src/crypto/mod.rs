@@ -0,0 +1,2 @@
+pub(crate) mod arc4;
+pub(crate) mod salsa20;
@@ -0,0 +1,142 @@
+//! Implementation of the Salsa20 stream cipher, as used by encrypted BLTE frames.
+//!
+//! Both 128-bit and 256-bit keys are supported. TACT keys are 128-bit, which the common Rust
+//! implementations do not support, so the cipher is implemented here.
+
+/// The constants used with a 256-bit key ("expand 32-byte k").
+const SIGMA: [u32; 4] = [0x6170_7865, 0x3320_646E, 0x7962_2D32, 0x6B20_6574];
+/// The constants used with a 128-bit key ("expand 16-byte k").
+const TAU: [u32; 4] = [0x6170_7865, 0x3120_646E, 0x7962_2D36, 0x6B20_6574];
+
+/// A Salsa20 cipher instance with 20 rounds.
+pub(crate) struct Salsa20 {
+    state: [u32; 16],
+}
+
+impl Salsa20 {
+    /// Creates a new cipher from a 16 or 32 byte key and an 8 byte nonce.
+    pub(crate) fn new(key: &[u8], nonce: &[u8; 8]) -> Option<Self> {
+        let (constants, key_low, key_high) = match key.len() {
+            16 => (TAU, &key[..16], &key[..16]),
+            32 => (SIGMA, &key[..16], &key[16..]),
+            _ => return None,
+        };
+        let word = |bytes: &[u8], i: usize| {
+            u32::from_le_bytes([
+                bytes[i * 4],
+                bytes[i * 4 + 1],
+                bytes[i * 4 + 2],
+                bytes[i * 4 + 3],
+            ])
+        };
+
+        let mut state = [0u32; 16];
+        state[0] = constants[0];
+        for i in 0..4 {
+            state[1 + i] = word(key_low, i);
+            state[11 + i] = word(key_high, i);
+        }
+        state[5] = constants[1];
+        state[6] = word(nonce, 0);
+        state[7] = word(nonce, 1);
+        state[8] = 0;
+        state[9] = 0;
+        state[10] = constants[2];
+        state[15] = constants[3];
+
+        Some(Self { state })
+    }
+
+    /// Encrypts or decrypts `data` in place.
+    pub(crate) fn apply_keystream(&mut self, data: &mut [u8]) {
+        for chunk in data.chunks_mut(64) {
+            let block = self.next_block();
+            for (byte, key) in chunk.iter_mut().zip(block.iter()) {
+                *byte ^= key;
+            }
+        }
+    }
+
+    /// Produces the next 64 byte keystream block and advances the block counter.
+    fn next_block(&mut self) -> [u8; 64] {
+        let mut x = self.state;
+        for _ in 0..10 {
+            // Column round
+            quarter_round(&mut x, 0, 4, 8, 12);
+            quarter_round(&mut x, 5, 9, 13, 1);
+            quarter_round(&mut x, 10, 14, 2, 6);
+            quarter_round(&mut x, 15, 3, 7, 11);
+            // Row round
+            quarter_round(&mut x, 0, 1, 2, 3);
+            quarter_round(&mut x, 5, 6, 7, 4);
+            quarter_round(&mut x, 10, 11, 8, 9);
+            quarter_round(&mut x, 15, 12, 13, 14);
+        }
+
+        let mut block = [0u8; 64];
+        for (i, word) in x.iter().enumerate() {
+            let value = word.wrapping_add(self.state[i]);
+            block[i * 4..i * 4 + 4].copy_from_slice(&value.to_le_bytes());
+        }
+
+        let counter = ((self.state[9] as u64) << 32 | self.state[8] as u64).wrapping_add(1);
+        self.state[8] = counter as u32;
+        self.state[9] = (counter >> 32) as u32;
+
+        block
+    }
+}
+
+fn quarter_round(x: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    x[b] ^= x[a].wrapping_add(x[d]).rotate_left(7);
+    x[c] ^= x[b].wrapping_add(x[a]).rotate_left(9);
+    x[d] ^= x[c].wrapping_add(x[b]).rotate_left(13);
+    x[a] ^= x[d].wrapping_add(x[c]).rotate_left(18);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keystream(key: &[u8], length: usize) -> Vec<u8> {
+        let mut data = vec![0u8; length];
+        Salsa20::new(key, &[0; 8])
+            .unwrap()
+            .apply_keystream(&mut data);
+        data
+    }
+
+    #[test]
+    fn matches_ecrypt_vectors() {
+        // Set 1, vector 0 of the eSTREAM test vectors, for both key sizes
+        let mut key = [0u8; 32];
+        key[0] = 0x80;
+        assert_eq!(
+            hex::encode_upper(keystream(&key[..16], 64)),
+            "4DFA5E481DA23EA09A31022050859936DA52FCEE218005164F267CB65F5CFD7F\
+             2B4F97E0FF16924A52DF269515110A07F9E460BC65EF95DA58F740B7D1DBB0AA"
+        );
+        assert_eq!(
+            hex::encode_upper(keystream(&key, 64)),
+            "E3BE8FDD8BECA2E3EA8EF9475B29A6E7003951E1097A5C38D23B7A5FAD9F6844\
+             B22C97559E2723C7CBBD3FE4FC8D9A0744652A83E72A9C461876AF4D7EF1A117"
+        );
+    }
+
+    #[test]
+    fn continues_the_keystream_across_calls() {
+        let key = [0x42u8; 16];
+        let mut cipher = Salsa20::new(&key, &[7; 8]).unwrap();
+        let mut data = vec![0u8; 128];
+        let (first, second) = data.split_at_mut(64);
+        cipher.apply_keystream(first);
+        cipher.apply_keystream(second);
+
+        let mut expected = vec![0u8; 128];
+        Salsa20::new(&key, &[7; 8])
+            .unwrap()
+            .apply_keystream(&mut expected);
+        assert_eq!(data, expected);
+        assert!(Salsa20::new(&[0; 24], &[0; 8]).is_none());
+    }
+}
@@ -7,16 +7,34 @@ use crate::span_info::SpanInfo;
 /// Represents a file entry in the CASC storage.
 ///
 /// Each `Entry` contains the file's name and a list of spans describing the file's data segments.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Entry {
     /// The name of the file entry.
     pub name: String,
     /// The spans associated with this entry, describing segments of the file's data.
     pub(crate) spans: Vec<SpanInfo>,
+    /// The FileDataID of the entry, for roots that identify files numerically.
+    pub(crate) file_data_id: Option<u32>,
 }
 
 impl Entry {
     pub(crate) fn new_with_spans(name: String, spans: Vec<SpanInfo>) -> Self {
-        Self { name, spans }
+        Self {
+            name,
+            spans,
+            file_data_id: None,
+        }
+    }
+
+    pub(crate) fn new_with_file_data_id(
+        name: String,
+        spans: Vec<SpanInfo>,
+        file_data_id: u32,
+    ) -> Self {
+        Self {
+            name,
+            spans,
+            file_data_id: Some(file_data_id),
+        }
     }
 }
@@ -12,6 +12,21 @@ pub enum CascError {
     InvalidData(String),
     /// Represents an error that occurs when a file is not supported by the CASC storage.
     UnsupportedFileType(String),
+    /// Represents an error that occurs when the header or table hash of an `.idx` key mapping
+    /// table does not match its contents, naming the offending file.
+    IndexHashMismatch(std::path::PathBuf),
+    /// Represents an error that occurs when data is encrypted with a TACT key that is not
+    /// in the key registry, naming the missing key.
+    MissingKey(u64),
+    /// Represents an error that occurs when an encoded BLTE frame does not match the MD5 hash
+    /// stored in its block table, naming the frame and its offset within the data file.
+    FrameHashMismatch { frame_index: u32, offset: u64 },
+    /// Represents an error that occurs when an asset GUID is not listed by any manifest that
+    /// could be read, while other manifests could not be, naming those manifests.
+    UnlistedGuid {
+        guid: u64,
+        skipped_manifests: Vec<String>,
+    },
     /// Represents an error that occurs during I/O operations.
     Io(std::io::Error),
     /// Represents an error that occurs for any other reason not covered by the above variants.
@@ -26,6 +41,25 @@ impl std::fmt::Display for CascError {
             CascError::FileNotFound(name) => write!(f, "File not found: {name}"),
             CascError::FileCorrupted(name) => write!(f, "File is corrupted: {name}"),
             CascError::UnsupportedFileType(name) => write!(f, "Unsupported file type: {name}"),
+            CascError::IndexHashMismatch(path) => {
+                write!(f, "Index hash mismatch: {}", path.display())
+            }
+            CascError::MissingKey(name) => write!(f, "Missing TACT key: {name:016X}"),
+            CascError::FrameHashMismatch {
+                frame_index,
+                offset,
+            } => write!(
+                f,
+                "Frame {frame_index} at archive offset {offset:#X} failed MD5 verification"
+            ),
+            CascError::UnlistedGuid {
+                guid,
+                skipped_manifests,
+            } => write!(
+                f,
+                "GUID {guid:016X} not found, and these manifests could not be read: {}",
+                skipped_manifests.join(", ")
+            ),
             CascError::Io(err) => write!(f, "I/O error: {err}"),
             CascError::Other(err) => write!(f, "CASC error: {err}"),
         }
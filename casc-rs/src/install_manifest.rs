@@ -0,0 +1,133 @@
+//! Module for the INSTALL manifest, which lists the files installed outside of the storage.
+//!
+//! The INSTALL manifest is referenced by the `install` entry of the build config. It names each
+//! loose file of the game's install, such as executables and libraries, with its content key
+//! and size. Tags group the files by platform, architecture and locale, each with a bitmask of
+//! the files it applies to.
+use crate::error::CascError;
+use byteorder::{BigEndian, ReadBytesExt};
+use std::io::{BufRead, Cursor, Read, Seek, SeekFrom};
+
+/// Represents a tag of the INSTALL manifest, such as `Windows`, `x86_64` or `enUS`.
+#[derive(Debug, Clone)]
+pub struct InstallTag {
+    /// The name of the tag.
+    pub name: String,
+    /// The kind of tag, such as platform or locale. The values differ between products.
+    pub tag_type: u16,
+    /// One bit per entry, most significant bit first, set for the entries the tag applies to.
+    pub bitmask: Vec<u8>,
+}
+
+impl InstallTag {
+    /// Returns whether the tag applies to the entry with the given index.
+    pub fn contains(&self, entry_index: usize) -> bool {
+        self.bitmask
+            .get(entry_index / 8)
+            .is_some_and(|byte| byte & (0x80 >> (entry_index % 8)) != 0)
+    }
+}
+
+/// Represents a file listed in the INSTALL manifest.
+#[derive(Debug, Clone)]
+pub struct InstallEntry {
+    /// The path of the file, relative to the install directory.
+    pub name: String,
+    /// The content key of the file.
+    pub content_key: Vec<u8>,
+    /// The decoded size of the file in bytes.
+    pub size: u32,
+}
+
+/// Represents the parsed INSTALL manifest of a CASC storage.
+///
+/// When the root of a storage is in a format that is not supported,
+/// [`CascStorage`](crate::casc_storage::CascStorage) lists the files of this manifest instead.
+#[derive(Debug, Clone)]
+pub struct InstallManifest {
+    /// The format version of the manifest.
+    pub version: u8,
+    /// The tags, in order.
+    pub tags: Vec<InstallTag>,
+    /// The files, in order. Tag bitmasks are indexed by position in this list.
+    pub entries: Vec<InstallEntry>,
+}
+
+impl InstallManifest {
+    /// The signature identifying the file (`IN`).
+    pub const SIGNATURE: u16 = 0x494E;
+
+    /// Parses an INSTALL manifest from the given stream.
+    pub fn new<R: Read + Seek>(stream: &mut R) -> Result<Self, CascError> {
+        stream.seek(SeekFrom::Start(0))?;
+        let mut data = Vec::new();
+        stream.read_to_end(&mut data)?;
+        let mut reader = Cursor::new(data);
+
+        let signature = reader.read_u16::<BigEndian>()?;
+        if signature != Self::SIGNATURE {
+            return Err(CascError::InvalidData(format!(
+                "Invalid INSTALL signature: {signature:#X}"
+            )));
+        }
+        let version = reader.read_u8()?;
+        if version != 1 {
+            return Err(CascError::UnsupportedFileType(format!(
+                "Unsupported INSTALL version: {version}"
+            )));
+        }
+        let content_key_size = reader.read_u8()?;
+        let tag_count = reader.read_u16::<BigEndian>()?;
+        let entry_count = reader.read_u32::<BigEndian>()?;
+
+        let bitmask_size = (entry_count as usize).div_ceil(8);
+        let mut tags = Vec::with_capacity(tag_count as usize);
+        for _ in 0..tag_count {
+            let name = Self::read_string(&mut reader)?;
+            let tag_type = reader.read_u16::<BigEndian>()?;
+            let mut bitmask = vec![0u8; bitmask_size];
+            reader.read_exact(&mut bitmask)?;
+            tags.push(InstallTag {
+                name,
+                tag_type,
+                bitmask,
+            });
+        }
+
+        let mut entries = Vec::new();
+        for _ in 0..entry_count {
+            let name = Self::read_string(&mut reader)?;
+            let mut content_key = vec![0u8; content_key_size as usize];
+            reader.read_exact(&mut content_key)?;
+            let size = reader.read_u32::<BigEndian>()?;
+            entries.push(InstallEntry {
+                name,
+                content_key,
+                size,
+            });
+        }
+
+        Ok(Self {
+            version,
+            tags,
+            entries,
+        })
+    }
+
+    /// Returns the tags that apply to the entry with the given index.
+    pub fn entry_tags(&self, entry_index: usize) -> impl Iterator<Item = &InstallTag> {
+        self.tags
+            .iter()
+            .filter(move |tag| tag.contains(entry_index))
+    }
+
+    /// Reads a NUL-terminated string.
+    fn read_string(reader: &mut Cursor<Vec<u8>>) -> Result<String, CascError> {
+        let mut bytes = Vec::new();
+        reader.read_until(0, &mut bytes)?;
+        if bytes.pop() != Some(0) {
+            return Err(CascError::InvalidData("Unterminated string".to_string()));
+        }
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+}
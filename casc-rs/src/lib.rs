@@ -3,12 +3,19 @@
 //! `casc-rs` is a pure Rust implementation of a Casc Storage Handler for Blizzard's CASC format.
 //! It enables reading, listing, and extracting files from Blizzard game data archives.
 //!
-//! > **Note:** This library currently only supports CASC storages that use the TVFS root file format.
+//! > **Note:** This library currently supports CASC storages that use the TVFS, World of Warcraft, MNDX (Heroes of the Storm and StarCraft II), Diablo III, Overwatch or text (StarCraft: Remastered and Warcraft III) root file formats. Storages with any other root format fall back to listing the files in their INSTALL manifest.
 //!
 //! ## Features
 //! - Read and parse CASC storages
-//! - List files and their metadata
-//! - Extract files by name
+//! - List files and their metadata, including keys, sizes and BLTE encodings
+//! - Browse files folder by folder with a [`CascDirectoryTree`](casc_directory_tree::CascDirectoryTree)
+//! - Look up files by path regardless of separators or case, with a [`PathNormalizer`](casc_path::PathNormalizer)
+//! - Find files by glob pattern or regular expression with a [`CascFilePattern`](casc_file_pattern::CascFilePattern)
+//! - Extract files by name, by FileDataID for World of Warcraft storages, or by asset GUID for Overwatch storages
+//! - Decrypt encrypted files with a [`TactKeyRegistry`](tact_key_registry::TactKeyRegistry), and Overwatch manifests with a [`CmfKeyProvider`](cmf_key_provider::CmfKeyProvider)
+//! - Optionally verify the MD5 hash of every frame as it is read
+//! - Verify extracted files against their content keys, or scan a whole storage for damage
+//! - Read the [`InstallManifest`](install_manifest::InstallManifest), which is also listed when the root format is not supported
 //!
 //! ## CascStorage
 //! The main entry point for interacting with CASC archives is the [`CascStorage`](casc_storage::CascStorage) struct. It provides methods to open a CASC storage directory, list available files, and extract file contents. `CascStorage` handles parsing the storage's metadata, configuration, and file tables, allowing you to work with Blizzard game data archives in a high-level, ergonomic way.
@@ -51,21 +58,38 @@
 //! ```
 
 #![allow(unused)]
-mod block_table;
+// `ArrayReadExt::read_array` shares its name with an unstable `Read` method
+#![allow(unstable_name_collisions)]
+pub mod block_table;
 mod casc_build_info;
 mod casc_config;
+pub mod casc_directory_tree;
+pub mod casc_encoding_table;
 pub mod casc_file;
+pub mod casc_file_details;
 mod casc_file_frame;
 pub mod casc_file_info;
+pub mod casc_file_pattern;
 mod casc_file_span;
+pub mod casc_index_file;
 mod casc_key_mapping_table;
+pub mod casc_open_options;
+pub mod casc_path;
 mod casc_span_header;
 pub mod casc_storage;
+pub mod casc_verification;
+pub mod casc_verifying_reader;
+pub mod cmf_key_provider;
+mod crypto;
 mod entry;
 pub mod error;
 mod ext;
+pub mod hash;
+pub mod install_manifest;
+pub mod listfile;
 mod path_table_node_flags;
 mod root_handler;
 mod root_handlers;
 mod span_info;
+pub mod tact_key_registry;
 mod utility;
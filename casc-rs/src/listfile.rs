@@ -0,0 +1,97 @@
+use crate::error::CascError;
+use crate::hash::file_name_hash;
+/// Module for loading community listfiles, which map FileDataIDs and name hashes to paths.
+///
+/// Some roots (such as World of Warcraft) only identify their files by FileDataID or by the
+/// Jenkins96 hash of their name. A listfile supplies the missing paths.
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+/// Represents a listfile of known file paths.
+///
+/// Two line formats are supported, and may be mixed within one file:
+/// - `id;path` (CSV), mapping a FileDataID to a path.
+/// - `path`, a plain path that is matched against name hashes.
+#[derive(Debug, Default)]
+pub struct Listfile {
+    /// Paths keyed by FileDataID.
+    by_id: HashMap<u32, String>,
+    /// Paths keyed by Jenkins96 name hash.
+    by_name_hash: HashMap<u64, String>,
+}
+
+impl Listfile {
+    /// Creates a new, empty `Listfile`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads a listfile from the specified file.
+    ///
+    /// # Arguments
+    ///
+    /// * `file_name` - The path to the listfile.
+    pub fn from_file<P: AsRef<Path>>(file_name: P) -> Result<Self, CascError> {
+        let file = File::open(file_name)?;
+        Self::from_reader(file)
+    }
+
+    /// Loads a listfile from a reader (e.g., File, BufReader, etc.)
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self, CascError> {
+        let mut listfile = Self::new();
+        for line in BufReader::new(reader).lines() {
+            listfile.add_line(&line?);
+        }
+        Ok(listfile)
+    }
+
+    /// Adds a single listfile line, in either the `id;path` or the plain path format.
+    pub fn add_line(&mut self, line: &str) {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return;
+        }
+        match line.split_once(';') {
+            Some((id, path)) if !path.is_empty() => match id.trim().parse::<u32>() {
+                Ok(id) => self.add_file_data_id(id, path),
+                Err(_) => self.add_path(line),
+            },
+            _ => self.add_path(line),
+        }
+    }
+
+    /// Adds a path for the given FileDataID.
+    pub fn add_file_data_id(&mut self, file_data_id: u32, path: &str) {
+        self.by_name_hash
+            .insert(file_name_hash(path), path.to_string());
+        self.by_id.insert(file_data_id, path.to_string());
+    }
+
+    /// Adds a plain path, to be matched by its name hash.
+    pub fn add_path(&mut self, path: &str) {
+        self.by_name_hash
+            .insert(file_name_hash(path), path.to_string());
+    }
+
+    /// Returns the path for the given FileDataID, if known.
+    pub fn get_by_id(&self, file_data_id: u32) -> Option<&str> {
+        self.by_id.get(&file_data_id).map(|s| s.as_str())
+    }
+
+    /// Returns the path with the given Jenkins96 name hash, if known.
+    pub fn get_by_name_hash(&self, name_hash: u64) -> Option<&str> {
+        self.by_name_hash.get(&name_hash).map(|s| s.as_str())
+    }
+
+    /// Returns the number of distinct paths in the listfile.
+    pub fn len(&self) -> usize {
+        self.by_name_hash.len()
+    }
+
+    /// Returns whether the listfile contains no paths.
+    pub fn is_empty(&self) -> bool {
+        self.by_name_hash.is_empty()
+    }
+}
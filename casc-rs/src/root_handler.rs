@@ -1,25 +1,82 @@
 use std::collections::HashMap;
 
-use crate::{entry::Entry, error::CascError, root_handlers::tvfs_root_handler::TVFSRootHandler};
+use crate::{
+    entry::Entry,
+    error::CascError,
+    listfile::Listfile,
+    root_handlers::{
+        diablo3_root_handler::Diablo3RootHandler, install_root_handler::InstallRootHandler,
+        mndx_root_handler::MndxRootHandler, overwatch_root_handler::OverwatchRootHandler,
+        text_root_handler::TextRootHandler, tvfs_root_handler::TVFSRootHandler,
+        wow_root_handler::WowRootHandler,
+    },
+};
 
 #[derive(Debug)]
 pub enum RootHandler {
     Tvfs(TVFSRootHandler),
-    // MNDX
-    // Diablo3
-    // WoW
-    // Overwatch
-    // Starcraft1
+    Wow(WowRootHandler),
+    Mndx(MndxRootHandler),
+    Diablo3(Diablo3RootHandler),
+    Overwatch(OverwatchRootHandler),
+    TextRoot(TextRootHandler),
+    Install(InstallRootHandler),
 }
 pub trait RootHandlerTrait {
     fn get_file_entries(&self) -> Result<&HashMap<String, Entry>, CascError>;
+    fn get_file_data_ids(&self) -> Option<&HashMap<u32, String>>;
+    fn get_guids(&self) -> Option<&HashMap<u64, String>>;
+    fn get_unresolved_files(&self) -> &[String];
+    fn get_skipped_manifests(&self) -> &[String];
+    fn apply_listfile(&mut self, listfile: &Listfile) -> usize;
 }
 impl RootHandlerTrait for RootHandler {
     fn get_file_entries(&self) -> Result<&HashMap<String, Entry>, CascError> {
         let file_entries = match self {
             RootHandler::Tvfs(handler) => &handler.file_entries,
-            _ => return Err(CascError::InvalidData("".to_string())),
+            RootHandler::Wow(handler) => &handler.file_entries,
+            RootHandler::Mndx(handler) => &handler.file_entries,
+            RootHandler::Diablo3(handler) => &handler.file_entries,
+            RootHandler::Overwatch(handler) => &handler.file_entries,
+            RootHandler::TextRoot(handler) => &handler.file_entries,
+            RootHandler::Install(handler) => &handler.file_entries,
         };
         Ok(file_entries)
     }
+
+    fn get_file_data_ids(&self) -> Option<&HashMap<u32, String>> {
+        match self {
+            RootHandler::Wow(handler) => Some(&handler.file_data_ids),
+            _ => None,
+        }
+    }
+
+    fn get_guids(&self) -> Option<&HashMap<u64, String>> {
+        match self {
+            RootHandler::Overwatch(handler) => Some(&handler.guids),
+            _ => None,
+        }
+    }
+
+    fn get_unresolved_files(&self) -> &[String] {
+        match self {
+            RootHandler::Mndx(handler) => &handler.unresolved_files,
+            _ => &[],
+        }
+    }
+
+    fn get_skipped_manifests(&self) -> &[String] {
+        match self {
+            RootHandler::Tvfs(handler) => &handler.skipped_manifests,
+            RootHandler::Overwatch(handler) => &handler.skipped_manifests,
+            _ => &[],
+        }
+    }
+
+    fn apply_listfile(&mut self, listfile: &Listfile) -> usize {
+        match self {
+            RootHandler::Wow(handler) => handler.apply_listfile(listfile),
+            _ => 0,
+        }
+    }
 }
@@ -0,0 +1,631 @@
+use crate::casc_encoding_table::CascEncodingTable;
+use crate::entry::Entry;
+use crate::error::CascError;
+use crate::span_info::SpanInfo;
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::collections::HashMap;
+use std::io::{BufRead, Cursor, Read, Seek, SeekFrom};
+
+/// The groups SNOs are sorted into, with the folder and extension used for their files.
+///
+/// Groups without an extension store files that are only ever referenced by SNO ID.
+const SNO_GROUPS: &[(i32, &str, &str)] = &[
+    (1, "Actor", ".acr"),
+    (2, "Adventure", ".adv"),
+    (3, "AiBehavior", ""),
+    (4, "AiState", ""),
+    (5, "AmbientSound", ".ams"),
+    (6, "Anim", ".ani"),
+    (7, "Anim2D", ".an2"),
+    (8, "AnimSet", ".ans"),
+    (9, "Appearance", ".app"),
+    (10, "Hero", ""),
+    (11, "Cloth", ".clt"),
+    (12, "Conversation", ".cnv"),
+    (13, "ConversationList", ""),
+    (14, "EffectGroup", ".efg"),
+    (15, "Encounter", ".enc"),
+    (17, "Explosion", ".xpl"),
+    (18, "FlagSet", ""),
+    (19, "Font", ".fnt"),
+    (20, "GameBalance", ".gam"),
+    (21, "Globals", ".glo"),
+    (22, "LevelArea", ".lvl"),
+    (23, "Light", ".lit"),
+    (24, "MarkerSet", ".mrk"),
+    (25, "Monster", ".mon"),
+    (26, "Observer", ".obs"),
+    (27, "Particle", ".prt"),
+    (28, "Physics", ".phy"),
+    (29, "Power", ".pow"),
+    (31, "Quest", ".qst"),
+    (32, "Rope", ".rop"),
+    (33, "Scene", ".scn"),
+    (34, "SceneGroup", ".scg"),
+    (35, "Script", ""),
+    (36, "ShaderMap", ".shm"),
+    (37, "Shader", ".shd"),
+    (38, "Shake", ".shk"),
+    (39, "SkillKit", ".skl"),
+    (40, "Sound", ".snd"),
+    (41, "SoundBank", ".sbk"),
+    (42, "StringList", ".stl"),
+    (43, "Surface", ".srf"),
+    (44, "Texture", ".tex"),
+    (45, "Trail", ".trl"),
+    (46, "UI", ".ui"),
+    (47, "Weather", ".wth"),
+    (48, "Worlds", ".wrl"),
+    (49, "Recipe", ".rcp"),
+    (51, "Condition", ".cnd"),
+    (52, "TreasureClass", ""),
+    (53, "Account", ""),
+    (54, "Conductor", ""),
+    (55, "TimedEvent", ""),
+    (56, "Act", ".act"),
+    (57, "Material", ".mat"),
+    (58, "QuestRange", ".qsr"),
+    (59, "Lore", ".lor"),
+    (60, "Reverb", ".rev"),
+    (61, "PhysMesh", ".phm"),
+    (62, "Music", ".mus"),
+    (63, "Tutorial", ".tut"),
+    (64, "BossEncounter", ".bos"),
+    (65, "ControlScheme", ""),
+    (66, "Accolade", ".aco"),
+    (67, "AnimTree", ".ant"),
+    (68, "Vibration", ""),
+    (69, "DungeonFinder", ""),
+];
+
+/// Represents an SNO (a game asset identified by number) listed in `CoreTOC.dat`.
+#[derive(Debug, Clone)]
+pub struct Diablo3Sno {
+    /// The group the SNO belongs to, such as actors or textures.
+    pub group: i32,
+    /// The name of the SNO, without its group folder or extension.
+    pub name: String,
+}
+
+impl Diablo3Sno {
+    /// Returns the folder files of this SNO are stored in.
+    pub fn group_name(&self) -> String {
+        SNO_GROUPS
+            .iter()
+            .find(|(group, _, _)| *group == self.group)
+            .map(|(_, name, _)| name.to_string())
+            .unwrap_or_else(|| self.group.to_string())
+    }
+
+    /// Returns the extension of the SNO's file, including the dot, or an empty string.
+    pub fn extension(&self) -> &'static str {
+        SNO_GROUPS
+            .iter()
+            .find(|(group, _, _)| *group == self.group)
+            .map(|(_, _, extension)| *extension)
+            .unwrap_or("")
+    }
+}
+
+/// Represents an entry of a Diablo III directory file.
+#[derive(Debug, Clone)]
+pub enum Diablo3DirEntry {
+    /// The main file of an SNO.
+    Asset { content_key: [u8; 16], sno_id: u32 },
+    /// One of several numbered files belonging to an SNO.
+    AssetIndex {
+        content_key: [u8; 16],
+        sno_id: u32,
+        file_index: u32,
+    },
+    /// A file stored by name rather than by SNO.
+    Named { content_key: [u8; 16], name: String },
+}
+
+/// Handles the Diablo III root structure.
+///
+/// The root lists directories (`Base`, and one per locale or platform), each with its own
+/// directory file. Most files are stored by SNO ID, and are named using the SNO names and
+/// groups in `CoreTOC.dat`, with the extensions of numbered files taken from `Packages.dat`.
+#[derive(Debug)]
+pub struct Diablo3RootHandler {
+    pub file_entries: HashMap<String, Entry>,
+    /// The SNOs listed in `CoreTOC.dat`, by SNO ID.
+    pub snos: HashMap<u32, Diablo3Sno>,
+    /// The names of directories whose directory file could not be read or parsed.
+    pub skipped_directories: Vec<String>,
+}
+
+impl Diablo3RootHandler {
+    /// The magic of the Diablo III root and directory files.
+    pub const DIABLO3_MAGIC: u32 = 0x8007D0C4;
+    /// The magic of newer `CoreTOC.dat` files.
+    const CORE_TOC_MAGIC: u32 = 0xBCDE6611;
+    /// The magic of `Packages.dat`.
+    const PACKAGES_MAGIC: u32 = 0xAABB0002;
+    /// The name of the table of SNO names, within the `Base` directory.
+    const CORE_TOC_NAME: &'static str = "CoreTOC.dat";
+    /// The name of the list of numbered file names, within the `Base` directory.
+    const PACKAGES_NAME: &'static str = "Data_D3\\PC\\Misc\\Packages.dat";
+
+    /// Parses the root, reading each directory file and the name tables with `read_content`,
+    /// which returns the decoded contents of a file by content key.
+    ///
+    /// Directories whose directory file cannot be read or parsed, such as those of locales
+    /// that are not installed, are skipped and recorded in `skipped_directories`. The `Base`
+    /// directory must hold a readable `CoreTOC.dat`, as no SNO could be named without it.
+    pub fn new<R, F>(
+        stream: &mut R,
+        encoding: &CascEncodingTable,
+        read_content: F,
+    ) -> Result<Self, CascError>
+    where
+        R: Read + Seek,
+        F: Fn(&[u8; 16]) -> Result<Vec<u8>, CascError>,
+    {
+        stream.seek(SeekFrom::Start(0))?;
+        let mut data = Vec::new();
+        stream.read_to_end(&mut data)?;
+        let mut reader = Cursor::new(data.as_slice());
+
+        if reader.read_u32::<LittleEndian>()? != Self::DIABLO3_MAGIC {
+            return Err(CascError::InvalidData(
+                "Invalid Diablo III root header".to_string(),
+            ));
+        }
+        let directory_count = reader.read_u32::<LittleEndian>()?;
+        let mut directories = Vec::new();
+        let mut skipped_directories = Vec::new();
+        for _ in 0..directory_count {
+            let content_key = Self::read_content_key(&mut reader)?;
+            let name = Self::read_string(&mut reader)?;
+            match read_content(&content_key).and_then(|data| Self::parse_directory(&data)) {
+                Ok(entries) => directories.push((name, entries)),
+                Err(_) => skipped_directories.push(name),
+            }
+        }
+
+        // The name tables are only stored in the base directory
+        let find_named = |file_name: &str| {
+            directories
+                .iter()
+                .filter(|(name, _)| name == "Base")
+                .flat_map(|(_, entries)| entries)
+                .find_map(|entry| match entry {
+                    Diablo3DirEntry::Named { content_key, name } if name == file_name => {
+                        Some(read_content(content_key))
+                    }
+                    _ => None,
+                })
+        };
+        let core_toc = find_named(Self::CORE_TOC_NAME).ok_or_else(|| {
+            CascError::FileNotFound(format!("{} not found in Base", Self::CORE_TOC_NAME))
+        })??;
+        let snos = Self::parse_core_toc(&core_toc)?;
+        // Packages.dat only adds the extensions of numbered files, so it is optional
+        let packages = match find_named(Self::PACKAGES_NAME) {
+            Some(packages) => Self::parse_packages(&packages?)?,
+            None => HashMap::new(),
+        };
+
+        let mut handler = Diablo3RootHandler {
+            file_entries: HashMap::new(),
+            snos,
+            skipped_directories,
+        };
+        for (directory, entries) in &directories {
+            for entry in entries {
+                let (content_key, name) = match entry {
+                    Diablo3DirEntry::Asset {
+                        content_key,
+                        sno_id,
+                    } => (content_key, handler.asset_name(*sno_id, None, &packages)),
+                    Diablo3DirEntry::AssetIndex {
+                        content_key,
+                        sno_id,
+                        file_index,
+                    } => (
+                        content_key,
+                        handler.asset_name(*sno_id, Some(*file_index), &packages),
+                    ),
+                    Diablo3DirEntry::Named { content_key, name } => (content_key, name.clone()),
+                };
+                let Some(encoding_entry) = encoding.get_content_entry(content_key) else {
+                    continue;
+                };
+                let Some(encoding_key) = encoding_entry.encoding_keys.first() else {
+                    continue;
+                };
+                let name = format!("{directory}\\{name}");
+                let span = SpanInfo::new_with_content_key(
+                    content_key.to_vec(),
+                    encoding_key.clone(),
+                    encoding_entry.content_size as usize,
+                );
+                handler
+                    .file_entries
+                    .insert(name.clone(), Entry::new_with_spans(name, vec![span]));
+            }
+        }
+        Ok(handler)
+    }
+
+    /// Parses a directory file into its asset, numbered asset and named entries.
+    fn parse_directory(data: &[u8]) -> Result<Vec<Diablo3DirEntry>, CascError> {
+        let mut reader = Cursor::new(data);
+        if reader.read_u32::<LittleEndian>()? != Self::DIABLO3_MAGIC {
+            return Err(CascError::InvalidData(
+                "Invalid Diablo III directory header".to_string(),
+            ));
+        }
+        let mut entries = Vec::new();
+        let asset_count = reader.read_u32::<LittleEndian>()?;
+        for _ in 0..asset_count {
+            entries.push(Diablo3DirEntry::Asset {
+                content_key: Self::read_content_key(&mut reader)?,
+                sno_id: reader.read_u32::<LittleEndian>()?,
+            });
+        }
+        let asset_index_count = reader.read_u32::<LittleEndian>()?;
+        for _ in 0..asset_index_count {
+            entries.push(Diablo3DirEntry::AssetIndex {
+                content_key: Self::read_content_key(&mut reader)?,
+                sno_id: reader.read_u32::<LittleEndian>()?,
+                file_index: reader.read_u32::<LittleEndian>()?,
+            });
+        }
+        let named_count = reader.read_u32::<LittleEndian>()?;
+        for _ in 0..named_count {
+            entries.push(Diablo3DirEntry::Named {
+                content_key: Self::read_content_key(&mut reader)?,
+                name: Self::read_string(&mut reader)?,
+            });
+        }
+        Ok(entries)
+    }
+
+    /// Parses `CoreTOC.dat`, the table of SNO names for each SNO group.
+    ///
+    /// Builds differ in the number of groups and in whether the header carries a magic and
+    /// an extra count per group, so each known layout is tried until one fits the data.
+    fn parse_core_toc(data: &[u8]) -> Result<HashMap<u32, Diablo3Sno>, CascError> {
+        let start = match data.get(..4) {
+            Some(magic)
+                if u32::from_le_bytes([magic[0], magic[1], magic[2], magic[3]])
+                    == Self::CORE_TOC_MAGIC =>
+            {
+                4
+            }
+            _ => 0,
+        };
+        // The group count, and the size of the header in bytes
+        const LAYOUTS: [(usize, usize); 3] = [(70, 70 * 12 + 4), (70, 70 * 8), (60, 60 * 8)];
+        for (group_count, header_size) in LAYOUTS {
+            if let Some(snos) = Self::parse_core_toc_layout(data, start, group_count, header_size) {
+                return Ok(snos);
+            }
+        }
+        Err(CascError::InvalidData("Invalid CoreTOC.dat".to_string()))
+    }
+
+    fn parse_core_toc_layout(
+        data: &[u8],
+        start: usize,
+        group_count: usize,
+        header_size: usize,
+    ) -> Option<HashMap<u32, Diablo3Sno>> {
+        let read_u32 = |offset: usize| -> Option<u32> {
+            let bytes = data.get(offset..offset.checked_add(4)?)?;
+            Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        };
+        let base = start + header_size;
+        if base > data.len() {
+            return None;
+        }
+
+        let mut snos = HashMap::new();
+        for group in 0..group_count {
+            let count = read_u32(start + group * 4)? as usize;
+            let offset = read_u32(start + (group_count + group) * 4)? as usize;
+            if count == 0 {
+                continue;
+            }
+            let entries = base.checked_add(offset)?;
+            let names = entries.checked_add(count.checked_mul(12)?)?;
+            if names > data.len() {
+                return None;
+            }
+            for i in 0..count {
+                let entry = entries + i * 12;
+                // Every entry of a group repeats the group, which tells the layouts apart
+                let sno_group = read_u32(entry)? as i32;
+                if sno_group != group as i32 {
+                    return None;
+                }
+                let sno_id = read_u32(entry + 4)?;
+                let name_offset = names.checked_add(read_u32(entry + 8)? as usize)?;
+                let name = data.get(name_offset..)?;
+                let name = &name[..name.iter().position(|&b| b == 0)?];
+                snos.insert(
+                    sno_id,
+                    Diablo3Sno {
+                        group: sno_group,
+                        name: String::from_utf8_lossy(name).into_owned(),
+                    },
+                );
+            }
+        }
+        Some(snos)
+    }
+
+    /// Parses `Packages.dat`, mapping numbered file names to their extension.
+    ///
+    /// Names are keyed without their extension and in lowercase, such as `texture\foo\0001`.
+    fn parse_packages(data: &[u8]) -> Result<HashMap<String, String>, CascError> {
+        let mut reader = Cursor::new(data);
+        if reader.read_u32::<LittleEndian>()? != Self::PACKAGES_MAGIC {
+            return Err(CascError::InvalidData("Invalid Packages.dat".to_string()));
+        }
+        let count = reader.read_u32::<LittleEndian>()?;
+        let mut packages = HashMap::new();
+        for _ in 0..count {
+            let name = Self::read_string(&mut reader)?;
+            let stem_end = name
+                .rfind('.')
+                .filter(|&dot| name[dot..].find(['\\', '/']).is_none());
+            if let Some(dot) = stem_end {
+                packages.insert(name[..dot].to_ascii_lowercase(), name[dot..].to_string());
+            }
+        }
+        Ok(packages)
+    }
+
+    /// Builds the name of an SNO's file, relative to its directory.
+    ///
+    /// SNOs missing from `CoreTOC.dat` are named by ID in an `Unknown` folder.
+    fn asset_name(
+        &self,
+        sno_id: u32,
+        file_index: Option<u32>,
+        packages: &HashMap<String, String>,
+    ) -> String {
+        let Some(sno) = self.snos.get(&sno_id) else {
+            return match file_index {
+                Some(file_index) => format!("Unknown\\{sno_id}\\{file_index:04}"),
+                None => format!("Unknown\\{sno_id}.dat"),
+            };
+        };
+        match file_index {
+            Some(file_index) => {
+                let name = format!("{}\\{}\\{file_index:04}", sno.group_name(), sno.name);
+                match packages.get(&name.to_ascii_lowercase()) {
+                    Some(extension) => name + extension,
+                    None => name,
+                }
+            }
+            None => format!("{}\\{}{}", sno.group_name(), sno.name, sno.extension()),
+        }
+    }
+
+    fn read_content_key(reader: &mut Cursor<&[u8]>) -> Result<[u8; 16], CascError> {
+        let mut content_key = [0u8; 16];
+        reader.read_exact(&mut content_key)?;
+        Ok(content_key)
+    }
+
+    /// Reads a NUL-terminated string.
+    fn read_string(reader: &mut Cursor<&[u8]>) -> Result<String, CascError> {
+        let mut bytes = Vec::new();
+        reader.read_until(0, &mut bytes)?;
+        if bytes.pop() != Some(0) {
+            return Err(CascError::InvalidData("Unterminated string".to_string()));
+        }
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Serialises a `CoreTOC.dat` in the given layout, from SNOs given as group, ID and name.
+    fn core_toc(
+        magic: bool,
+        group_count: usize,
+        header_size: usize,
+        snos: &[(i32, u32, &str)],
+    ) -> Vec<u8> {
+        let start = if magic { 4 } else { 0 };
+        let mut data = vec![0u8; start + header_size];
+        if magic {
+            data[..4].copy_from_slice(&Diablo3RootHandler::CORE_TOC_MAGIC.to_le_bytes());
+        }
+        for group in 0..group_count {
+            let group_snos = snos
+                .iter()
+                .filter(|sno| sno.0 == group as i32)
+                .collect::<Vec<_>>();
+            if group_snos.is_empty() {
+                continue;
+            }
+            let offset = data.len() - start - header_size;
+            data[start + group * 4..][..4]
+                .copy_from_slice(&(group_snos.len() as u32).to_le_bytes());
+            data[start + (group_count + group) * 4..][..4]
+                .copy_from_slice(&(offset as u32).to_le_bytes());
+
+            let mut names = Vec::new();
+            for (sno_group, sno_id, name) in group_snos {
+                data.extend_from_slice(&sno_group.to_le_bytes());
+                data.extend_from_slice(&sno_id.to_le_bytes());
+                data.extend_from_slice(&(names.len() as u32).to_le_bytes());
+                names.extend_from_slice(name.as_bytes());
+                names.push(0);
+            }
+            data.extend(names);
+        }
+        data
+    }
+
+    fn packages(names: &[&str]) -> Vec<u8> {
+        let mut data = Diablo3RootHandler::PACKAGES_MAGIC.to_le_bytes().to_vec();
+        data.extend_from_slice(&(names.len() as u32).to_le_bytes());
+        for name in names {
+            data.extend_from_slice(name.as_bytes());
+            data.push(0);
+        }
+        data
+    }
+
+    fn directory(
+        assets: &[([u8; 16], u32)],
+        indexed: &[([u8; 16], u32, u32)],
+        named: &[([u8; 16], &str)],
+    ) -> Vec<u8> {
+        let mut data = Diablo3RootHandler::DIABLO3_MAGIC.to_le_bytes().to_vec();
+        data.extend_from_slice(&(assets.len() as u32).to_le_bytes());
+        for (content_key, sno_id) in assets {
+            data.extend_from_slice(content_key);
+            data.extend_from_slice(&sno_id.to_le_bytes());
+        }
+        data.extend_from_slice(&(indexed.len() as u32).to_le_bytes());
+        for (content_key, sno_id, file_index) in indexed {
+            data.extend_from_slice(content_key);
+            data.extend_from_slice(&sno_id.to_le_bytes());
+            data.extend_from_slice(&file_index.to_le_bytes());
+        }
+        data.extend_from_slice(&(named.len() as u32).to_le_bytes());
+        for (content_key, name) in named {
+            data.extend_from_slice(content_key);
+            data.extend_from_slice(name.as_bytes());
+            data.push(0);
+        }
+        data
+    }
+
+    const SNOS: &[(i32, u32, &str)] =
+        &[(1, 100, "Barbarian"), (44, 200, "Sky"), (44, 201, "Ground")];
+
+    #[test]
+    fn parses_each_core_toc_layout() {
+        for (magic, group_count, header_size) in [
+            (true, 70, 70 * 12 + 4),
+            (false, 70, 70 * 8),
+            (false, 60, 60 * 8),
+        ] {
+            let snos = Diablo3RootHandler::parse_core_toc(&core_toc(
+                magic,
+                group_count,
+                header_size,
+                SNOS,
+            ))
+            .unwrap();
+            assert_eq!(snos.len(), 3);
+            let sky = &snos[&200];
+            assert_eq!((sky.group, sky.name.as_str()), (44, "Sky"));
+            assert_eq!(
+                (sky.group_name(), sky.extension()),
+                ("Texture".to_string(), ".tex")
+            );
+            assert_eq!(snos[&100].group_name(), "Actor");
+        }
+        assert!(Diablo3RootHandler::parse_core_toc(&[0; 16]).is_err());
+    }
+
+    #[test]
+    fn names_assets_with_packages_extensions() {
+        let handler = Diablo3RootHandler {
+            file_entries: HashMap::new(),
+            snos: Diablo3RootHandler::parse_core_toc(&core_toc(false, 70, 70 * 8, SNOS)).unwrap(),
+            skipped_directories: Vec::new(),
+        };
+        let packages = Diablo3RootHandler::parse_packages(&packages(&[
+            "Texture\\Sky\\0001.dds",
+            "Actor\\Barbarian\\0002.ogg",
+            "notes.txt",
+        ]))
+        .unwrap();
+        assert_eq!(packages["texture\\sky\\0001"], ".dds");
+        assert_eq!(packages.len(), 3);
+
+        assert_eq!(handler.asset_name(200, None, &packages), "Texture\\Sky.tex");
+        assert_eq!(
+            handler.asset_name(200, Some(1), &packages),
+            "Texture\\Sky\\0001.dds"
+        );
+        assert_eq!(
+            handler.asset_name(201, Some(1), &packages),
+            "Texture\\Ground\\0001"
+        );
+        assert_eq!(
+            handler.asset_name(100, Some(2), &packages),
+            "Actor\\Barbarian\\0002.ogg"
+        );
+        assert_eq!(handler.asset_name(300, None, &packages), "Unknown\\300.dat");
+        assert_eq!(
+            handler.asset_name(300, Some(3), &packages),
+            "Unknown\\300\\0003"
+        );
+    }
+
+    #[test]
+    fn parses_root_and_skips_unreadable_directories() {
+        let mut contents = HashMap::new();
+        contents.insert([0x01; 16], core_toc(true, 70, 70 * 12 + 4, SNOS));
+        contents.insert([0x02; 16], packages(&["Texture\\Sky\\0001.dds"]));
+        contents.insert(
+            [0x0B; 16],
+            directory(
+                &[([0xA0; 16], 200)],
+                &[([0xA1; 16], 200, 1)],
+                &[
+                    ([0x01; 16], Diablo3RootHandler::CORE_TOC_NAME),
+                    ([0x02; 16], Diablo3RootHandler::PACKAGES_NAME),
+                ],
+            ),
+        );
+        // A locale whose directory file is not stored locally, and one that is damaged
+        let mut root = Diablo3RootHandler::DIABLO3_MAGIC.to_le_bytes().to_vec();
+        root.extend_from_slice(&3u32.to_le_bytes());
+        for (content_key, name) in [
+            ([0x0B; 16], "Base"),
+            ([0x0C; 16], "enUS"),
+            ([0x0D; 16], "frFR"),
+        ] {
+            root.extend_from_slice(&content_key);
+            root.extend_from_slice(name.as_bytes());
+            root.push(0);
+        }
+        contents.insert([0x0D; 16], b"damaged".to_vec());
+        let read_content = |content_key: &[u8; 16]| {
+            contents
+                .get(content_key)
+                .cloned()
+                .ok_or_else(|| CascError::FileNotFound(hex::encode(content_key)))
+        };
+        let encoding = CascEncodingTable::from_entries(&[
+            ([0xA0; 16], [0xE0; 16], 10),
+            ([0xA1; 16], [0xE1; 16], 11),
+        ]);
+
+        let handler =
+            Diablo3RootHandler::new(&mut Cursor::new(&root), &encoding, read_content).unwrap();
+        assert_eq!(handler.skipped_directories, ["enUS", "frFR"]);
+        let mut names = handler.file_entries.keys().collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(
+            names,
+            ["Base\\Texture\\Sky.tex", "Base\\Texture\\Sky\\0001.dds"]
+        );
+
+        // Without CoreTOC.dat, no SNO could be named
+        contents.remove(&[0x01; 16]);
+        let read_content = |content_key: &[u8; 16]| {
+            contents
+                .get(content_key)
+                .cloned()
+                .ok_or_else(|| CascError::FileNotFound(hex::encode(content_key)))
+        };
+        assert!(Diablo3RootHandler::new(&mut Cursor::new(&root), &encoding, read_content).is_err());
+    }
+}
@@ -0,0 +1,90 @@
+use crate::casc_encoding_table::CascEncodingTable;
+use crate::entry::Entry;
+use crate::install_manifest::InstallManifest;
+use crate::span_info::SpanInfo;
+use std::collections::HashMap;
+
+/// Lists the files of the INSTALL manifest, used when the root is in an unsupported format.
+///
+/// Only the loose files of the game's install are listed, not the data they reference.
+#[derive(Debug)]
+pub struct InstallRootHandler {
+    pub file_entries: HashMap<String, Entry>,
+    /// The manifest the files were listed from.
+    pub manifest: InstallManifest,
+}
+
+impl InstallRootHandler {
+    /// Lists the files of the manifest, resolving their content keys through ENCODING.
+    ///
+    /// Files whose content key is missing from ENCODING are skipped. The tags of the manifest
+    /// are not used to pick a platform or locale: when a name is listed more than once, such
+    /// as once per platform, the first listed entry is kept whatever its tags.
+    pub fn new(manifest: InstallManifest, encoding: &CascEncodingTable) -> Self {
+        let mut file_entries = HashMap::new();
+        for entry in &manifest.entries {
+            if file_entries.contains_key(&entry.name) {
+                continue;
+            }
+            let Some(encoding_entry) = encoding.get_content_entry(&entry.content_key) else {
+                continue;
+            };
+            let Some(encoding_key) = encoding_entry.encoding_keys.first() else {
+                continue;
+            };
+            let span = SpanInfo::new_with_content_key(
+                entry.content_key.clone(),
+                encoding_key.clone(),
+                entry.size as usize,
+            );
+            file_entries.insert(
+                entry.name.clone(),
+                Entry::new_with_spans(entry.name.clone(), vec![span]),
+            );
+        }
+        InstallRootHandler {
+            file_entries,
+            manifest,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn skips_unresolved_keys_and_duplicate_names() {
+        // No tags, and four entries: two of the same name, and one missing from ENCODING
+        let mut data = b"IN\x01\x10\x00\x00\x00\x00\x00\x04".to_vec();
+        for (name, content_key, size) in [
+            ("Game.exe", [0x11; 16], 42u32),
+            ("Game.exe", [0x12; 16], 43),
+            ("missing.dll", [0x13; 16], 44),
+            ("readme.txt", [0x14; 16], 5),
+        ] {
+            data.extend_from_slice(name.as_bytes());
+            data.push(0);
+            data.extend_from_slice(&content_key);
+            data.extend_from_slice(&size.to_be_bytes());
+        }
+        let manifest = InstallManifest::new(&mut Cursor::new(data)).unwrap();
+        let encoding = CascEncodingTable::from_entries(&[
+            ([0x11; 16], [0xE1; 16], 42),
+            ([0x12; 16], [0xE2; 16], 43),
+            ([0x14; 16], [0xE4; 16], 5),
+        ]);
+
+        let handler = InstallRootHandler::new(manifest, &encoding);
+        assert_eq!(handler.manifest.entries.len(), 4);
+        assert_eq!(handler.file_entries.len(), 2);
+        let span = &handler.file_entries["Game.exe"].spans[0];
+        assert_eq!(span.encoding_key, [0xE1; 16]);
+        assert_eq!(span.size, Some(42));
+        assert_eq!(
+            handler.file_entries["readme.txt"].spans[0].encoding_key,
+            [0xE4; 16]
+        );
+    }
+}
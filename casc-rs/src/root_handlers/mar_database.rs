@@ -0,0 +1,539 @@
+//! Module for reading the MAR file name databases stored in MNDX roots.
+//!
+//! A MAR database is a serialised LOUDS trie (the layout used by marisa-trie). Each node of the
+//! trie carries either a single byte of a name, or a link to a longer name fragment stored in
+//! a tail buffer or in a nested trie. Names are numbered by the order of their terminal nodes,
+//! and the number of a name is what the MNDX root uses to find its content keys.
+use crate::error::CascError;
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::io::{Cursor, Read};
+
+/// Returns the error used for every structural problem in a MAR database.
+fn invalid(message: &str) -> CascError {
+    CascError::InvalidData(format!("Invalid MAR database: {message}"))
+}
+
+/// Reads a serialised vector: its size in bytes, the bytes, and padding up to 8 bytes.
+fn read_bytes(reader: &mut Cursor<&[u8]>) -> Result<Vec<u8>, CascError> {
+    let size = reader.read_u64::<LittleEndian>()?;
+    let remaining = (reader.get_ref().len() as u64).saturating_sub(reader.position());
+    if size > remaining {
+        return Err(invalid("vector runs past the end of the data"));
+    }
+    let mut bytes = vec![0u8; size as usize];
+    reader.read_exact(&mut bytes)?;
+    reader.set_position(reader.position() + (size.wrapping_neg() & 7));
+    Ok(bytes)
+}
+
+/// Reads a serialised vector of little-endian 32-bit units.
+fn read_units(reader: &mut Cursor<&[u8]>) -> Result<Vec<u32>, CascError> {
+    Ok(read_bytes(reader)?
+        .chunks_exact(4)
+        .map(|unit| u32::from_le_bytes([unit[0], unit[1], unit[2], unit[3]]))
+        .collect())
+}
+
+/// A bit vector with rank and select support.
+///
+/// The rank and select indexes stored alongside the bits are skipped, and an index of set bits
+/// per unit is built on load instead.
+#[derive(Debug, Default)]
+struct MarBitVector {
+    /// The bits, least significant bit first.
+    units: Vec<u32>,
+    /// The number of bits in the vector.
+    size: usize,
+    /// The number of set bits before each unit, with the total last.
+    ranks: Vec<usize>,
+}
+
+impl MarBitVector {
+    fn read(reader: &mut Cursor<&[u8]>) -> Result<Self, CascError> {
+        let units = read_units(reader)?;
+        let size = reader.read_u32::<LittleEndian>()? as usize;
+        let num_1s = reader.read_u32::<LittleEndian>()? as usize;
+        // Rank index, then select indexes for unset and set bits
+        read_bytes(reader)?;
+        read_bytes(reader)?;
+        read_bytes(reader)?;
+
+        if size > units.len() * 32 {
+            return Err(invalid("bit vector is larger than its units"));
+        }
+        let mut ranks = Vec::with_capacity(units.len() + 1);
+        let mut rank = 0;
+        ranks.push(rank);
+        for unit in &units {
+            rank += unit.count_ones() as usize;
+            ranks.push(rank);
+        }
+        if rank != num_1s {
+            return Err(invalid("bit vector has the wrong number of set bits"));
+        }
+        Ok(Self { units, size, ranks })
+    }
+
+    /// Returns whether the vector holds no bits.
+    fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Returns the number of set bits.
+    fn num_1s(&self) -> usize {
+        self.ranks.last().copied().unwrap_or(0)
+    }
+
+    /// Returns the bit at the given index.
+    fn get(&self, index: usize) -> Result<bool, CascError> {
+        if index >= self.size {
+            return Err(invalid("bit index out of range"));
+        }
+        Ok(self.units[index / 32] & (1 << (index % 32)) != 0)
+    }
+
+    /// Returns the number of set bits before the given index.
+    fn rank1(&self, index: usize) -> Result<usize, CascError> {
+        if index >= self.size {
+            return Err(invalid("bit index out of range"));
+        }
+        let mask = (1u32 << (index % 32)) - 1;
+        Ok(self.ranks[index / 32] + (self.units[index / 32] & mask).count_ones() as usize)
+    }
+
+    /// Returns the index of the given set bit, counting from zero.
+    fn select1(&self, rank: usize) -> Result<usize, CascError> {
+        if rank >= self.num_1s() {
+            return Err(invalid("bit rank out of range"));
+        }
+        // The last unit with fewer set bits before it than the rank holds the bit
+        let unit_index = self.ranks.partition_point(|&count| count <= rank) - 1;
+        let mut unit = self.units[unit_index];
+        for _ in 0..rank - self.ranks[unit_index] {
+            unit &= unit - 1;
+        }
+        Ok(unit_index * 32 + unit.trailing_zeros() as usize)
+    }
+}
+
+/// A vector of fixed-width integers packed into 32-bit units.
+#[derive(Debug, Default)]
+struct MarFlatVector {
+    /// The packed values, least significant bit first.
+    units: Vec<u32>,
+    /// The width of each value in bits.
+    value_size: u32,
+    /// The mask applied to each value.
+    mask: u32,
+    /// The number of values in the vector.
+    size: usize,
+}
+
+impl MarFlatVector {
+    fn read(reader: &mut Cursor<&[u8]>) -> Result<Self, CascError> {
+        let units = read_units(reader)?;
+        let value_size = reader.read_u32::<LittleEndian>()?;
+        let mask = reader.read_u32::<LittleEndian>()?;
+        let size = reader.read_u64::<LittleEndian>()? as usize;
+        if value_size > 32 || (size as u128 * value_size as u128) > units.len() as u128 * 32 {
+            return Err(invalid("flat vector is larger than its units"));
+        }
+        Ok(Self {
+            units,
+            value_size,
+            mask,
+            size,
+        })
+    }
+
+    /// Returns the value at the given index.
+    fn get(&self, index: usize) -> Result<u32, CascError> {
+        if index >= self.size {
+            return Err(invalid("value index out of range"));
+        }
+        let bit = index * self.value_size as usize;
+        let low = self.units.get(bit / 32).copied().unwrap_or(0) as u64;
+        let high = self.units.get(bit / 32 + 1).copied().unwrap_or(0) as u64;
+        Ok(((low | (high << 32)) >> (bit % 32)) as u32 & self.mask)
+    }
+}
+
+/// The buffer holding name fragments too long to store one byte per node.
+#[derive(Debug, Default)]
+struct MarTail {
+    /// The fragments, one after another.
+    buffer: Vec<u8>,
+    /// Marks the last byte of each fragment. When empty, fragments are NUL-terminated instead.
+    end_flags: MarBitVector,
+}
+
+impl MarTail {
+    fn read(reader: &mut Cursor<&[u8]>) -> Result<Self, CascError> {
+        Ok(Self {
+            buffer: read_bytes(reader)?,
+            end_flags: MarBitVector::read(reader)?,
+        })
+    }
+
+    /// Appends the fragment at the given offset to the name.
+    fn restore(&self, mut offset: usize, name: &mut Vec<u8>) -> Result<(), CascError> {
+        loop {
+            let byte = *self
+                .buffer
+                .get(offset)
+                .ok_or_else(|| invalid("fragment runs past the end of the tail"))?;
+            if self.end_flags.is_empty() {
+                if byte == 0 {
+                    return Ok(());
+                }
+                name.push(byte);
+            } else {
+                name.push(byte);
+                if self.end_flags.get(offset)? {
+                    return Ok(());
+                }
+            }
+            offset += 1;
+        }
+    }
+}
+
+/// A MAR file name database.
+#[derive(Debug)]
+pub(crate) struct MarDatabase {
+    /// The shape of the trie, in level order.
+    louds: MarBitVector,
+    /// Marks the nodes that end a name.
+    terminal_flags: MarBitVector,
+    /// Marks the nodes whose label is a link to a longer fragment.
+    link_flags: MarBitVector,
+    /// The label of each node, or the low 8 bits of its link.
+    bases: Vec<u8>,
+    /// The remaining bits of each link, in node order.
+    extras: MarFlatVector,
+    /// The fragments that links point to, when there is no nested trie.
+    tail: MarTail,
+    /// The nested trie holding the fragments that links point to, reversed.
+    next: Option<Box<MarDatabase>>,
+    /// The number of children of the root node.
+    num_l1_nodes: usize,
+}
+
+impl MarDatabase {
+    /// The magic at the start of a MAR database (`MAR\0`).
+    pub(crate) const MAR_MAGIC: u32 = 0x0052414D;
+    /// The deepest nesting of tries that is accepted.
+    const MAX_DEPTH: usize = 8;
+
+    /// Parses a MAR database.
+    pub(crate) fn new(data: &[u8]) -> Result<Self, CascError> {
+        let mut reader = Cursor::new(data);
+        if reader.read_u32::<LittleEndian>()? != Self::MAR_MAGIC {
+            return Err(invalid("bad signature"));
+        }
+        Self::read(&mut reader, 0)
+    }
+
+    fn read(reader: &mut Cursor<&[u8]>, depth: usize) -> Result<Self, CascError> {
+        if depth > Self::MAX_DEPTH {
+            return Err(invalid("tries are nested too deeply"));
+        }
+        let louds = MarBitVector::read(reader)?;
+        let terminal_flags = MarBitVector::read(reader)?;
+        let link_flags = MarBitVector::read(reader)?;
+        let bases = read_bytes(reader)?;
+        let extras = MarFlatVector::read(reader)?;
+        let tail = MarTail::read(reader)?;
+        let next = if link_flags.num_1s() != 0 && tail.buffer.is_empty() {
+            Some(Box::new(Self::read(reader, depth + 1)?))
+        } else {
+            None
+        };
+        // Lookup cache, which is only needed to speed up searches
+        read_bytes(reader)?;
+        let num_l1_nodes = reader.read_u32::<LittleEndian>()? as usize;
+        let _config = reader.read_u32::<LittleEndian>()?;
+
+        if terminal_flags.size > bases.len() || link_flags.size > bases.len() {
+            return Err(invalid("node flags do not match the node labels"));
+        }
+        Ok(Self {
+            louds,
+            terminal_flags,
+            link_flags,
+            bases,
+            extras,
+            tail,
+            next,
+            num_l1_nodes,
+        })
+    }
+
+    /// Returns the number of names in the database.
+    pub(crate) fn len(&self) -> usize {
+        self.terminal_flags.num_1s()
+    }
+
+    /// Returns every name in the database, in order of their index.
+    pub(crate) fn names(&self) -> Result<Vec<String>, CascError> {
+        (0..self.len()).map(|index| self.name(index)).collect()
+    }
+
+    /// Returns the name with the given index.
+    pub(crate) fn name(&self, index: usize) -> Result<String, CascError> {
+        let mut name = Vec::new();
+        let mut node_id = self.terminal_flags.select1(index)?;
+        if node_id == 0 {
+            return Ok(String::new());
+        }
+        // Labels are collected from the node up to the root, so the name is built reversed
+        loop {
+            if self.link_flags.get(node_id)? {
+                let start = name.len();
+                self.restore_link(self.get_link(node_id)?, &mut name)?;
+                name[start..].reverse();
+            } else {
+                name.push(self.get_base(node_id)?);
+            }
+            if node_id <= self.num_l1_nodes {
+                break;
+            }
+            node_id = self.get_parent(node_id)?;
+        }
+        name.reverse();
+        String::from_utf8(name).map_err(|_| invalid("name is not valid UTF-8"))
+    }
+
+    /// Appends the fragment a link points to, from the nested trie or the tail.
+    fn restore_link(&self, link: usize, name: &mut Vec<u8>) -> Result<(), CascError> {
+        match &self.next {
+            Some(next) => next.restore_node(link, name),
+            None => self.tail.restore(link, name),
+        }
+    }
+
+    /// Appends the labels from the given node up to the root, which is the fragment in order
+    /// as nested tries store their fragments reversed.
+    fn restore_node(&self, mut node_id: usize, name: &mut Vec<u8>) -> Result<(), CascError> {
+        loop {
+            if node_id == 0 {
+                return Err(invalid("link points to the root node"));
+            }
+            if self.link_flags.get(node_id)? {
+                self.restore_link(self.get_link(node_id)?, name)?;
+            } else {
+                name.push(self.get_base(node_id)?);
+            }
+            if node_id <= self.num_l1_nodes {
+                return Ok(());
+            }
+            node_id = self.get_parent(node_id)?;
+        }
+    }
+
+    fn get_base(&self, node_id: usize) -> Result<u8, CascError> {
+        self.bases
+            .get(node_id)
+            .copied()
+            .ok_or_else(|| invalid("node index out of range"))
+    }
+
+    fn get_link(&self, node_id: usize) -> Result<usize, CascError> {
+        let extra = self.extras.get(self.link_flags.rank1(node_id)?)? as usize;
+        Ok(self.get_base(node_id)? as usize | (extra << 8))
+    }
+
+    fn get_parent(&self, node_id: usize) -> Result<usize, CascError> {
+        self.louds
+            .select1(node_id)?
+            .checked_sub(node_id + 1)
+            .filter(|&parent| parent < node_id)
+            .ok_or_else(|| invalid("node has no parent"))
+    }
+}
+
+#[cfg(test)]
+impl MarDatabase {
+    /// Serialises a database holding the given names, in order, with each name stored in the
+    /// tail.
+    pub(crate) fn serialize_names(names: &[&str]) -> Vec<u8> {
+        use tests::Label;
+
+        let mut nodes = vec![(Label::Byte(0), names.len(), false)];
+        let mut tail = Vec::new();
+        for name in names {
+            nodes.push((Label::Link(tail.len()), 0, true));
+            tail.extend_from_slice(name.as_bytes());
+            tail.push(0);
+        }
+        let mut data = Self::MAR_MAGIC.to_le_bytes().to_vec();
+        data.extend(tests::trie(&nodes, &tail, &[], None));
+        data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The label of a node in a serialised trie.
+    pub(super) enum Label {
+        Byte(u8),
+        Link(usize),
+    }
+
+    fn write_bytes(data: &mut Vec<u8>, bytes: &[u8]) {
+        data.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+        data.extend_from_slice(bytes);
+        data.resize(data.len() + (bytes.len().wrapping_neg() & 7), 0);
+    }
+
+    fn write_units(data: &mut Vec<u8>, units: &[u32]) {
+        let bytes = units
+            .iter()
+            .flat_map(|unit| unit.to_le_bytes())
+            .collect::<Vec<_>>();
+        write_bytes(data, &bytes);
+    }
+
+    fn write_bits(data: &mut Vec<u8>, bits: &[bool]) {
+        let mut units = vec![0u32; bits.len().div_ceil(32)];
+        for (index, _) in bits.iter().enumerate().filter(|(_, bit)| **bit) {
+            units[index / 32] |= 1 << (index % 32);
+        }
+        write_units(data, &units);
+        data.extend_from_slice(&(bits.len() as u32).to_le_bytes());
+        data.extend_from_slice(&(bits.iter().filter(|bit| **bit).count() as u32).to_le_bytes());
+        for _ in 0..3 {
+            write_bytes(data, &[]);
+        }
+    }
+
+    /// Serialises a trie without its signature, from its nodes in level order.
+    ///
+    /// Each node is its label, its number of children and whether it ends a name. Links are
+    /// split into a base byte and an 8-bit extra.
+    pub(super) fn trie(
+        nodes: &[(Label, usize, bool)],
+        tail: &[u8],
+        end_flags: &[bool],
+        next: Option<Vec<u8>>,
+    ) -> Vec<u8> {
+        let mut louds = vec![true, false];
+        for (_, children, _) in nodes {
+            louds.extend(std::iter::repeat_n(true, *children));
+            louds.push(false);
+        }
+        let mut bases = Vec::new();
+        let mut extras = Vec::new();
+        for (label, _, _) in nodes {
+            match label {
+                Label::Byte(byte) => bases.push(*byte),
+                Label::Link(link) => {
+                    bases.push(*link as u8);
+                    extras.push((*link >> 8) as u8);
+                }
+            }
+        }
+
+        let mut data = Vec::new();
+        write_bits(&mut data, &louds);
+        write_bits(
+            &mut data,
+            &nodes.iter().map(|node| node.2).collect::<Vec<_>>(),
+        );
+        let links = nodes
+            .iter()
+            .map(|node| matches!(node.0, Label::Link(_)))
+            .collect::<Vec<_>>();
+        write_bits(&mut data, &links);
+        write_bytes(&mut data, &bases);
+        let mut units = vec![0u32; extras.len().div_ceil(4)];
+        for (index, extra) in extras.iter().enumerate() {
+            units[index / 4] |= (*extra as u32) << (index % 4 * 8);
+        }
+        write_units(&mut data, &units);
+        data.extend_from_slice(&8u32.to_le_bytes());
+        data.extend_from_slice(&0xFFu32.to_le_bytes());
+        data.extend_from_slice(&(extras.len() as u64).to_le_bytes());
+        write_bytes(&mut data, tail);
+        write_bits(&mut data, end_flags);
+        if let Some(next) = next {
+            data.extend(next);
+        }
+        // Lookup cache, number of level 1 nodes and config
+        write_bytes(&mut data, &[]);
+        data.extend_from_slice(&(nodes[0].1 as u32).to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data
+    }
+
+    fn database(trie: Vec<u8>) -> Result<MarDatabase, CascError> {
+        let mut data = MarDatabase::MAR_MAGIC.to_le_bytes().to_vec();
+        data.extend(trie);
+        MarDatabase::new(&data)
+    }
+
+    #[test]
+    fn restores_names_from_the_tail() {
+        // "a" and "xyz" below the root, and "bcd" below "a", with "bcd" past the first 256
+        // bytes of the tail so that its link needs an extra
+        let nodes = [
+            (Label::Byte(0), 2, false),
+            (Label::Byte(b'a'), 1, true),
+            (Label::Link(0), 0, true),
+            (Label::Link(0x104), 0, true),
+        ];
+        let mut tail = b"xyz\0".to_vec();
+        tail.resize(0x104, 0);
+        tail.extend_from_slice(b"bcd\0");
+        let mar = database(trie(&nodes, &tail, &[], None)).unwrap();
+        assert_eq!(mar.len(), 3);
+        assert_eq!(mar.names().unwrap(), ["a", "xyz", "abcd"]);
+
+        // The same fragments, ended by flags rather than NUL bytes
+        let nodes = [
+            (Label::Byte(0), 2, false),
+            (Label::Byte(b'a'), 1, true),
+            (Label::Link(0), 0, true),
+            (Label::Link(3), 0, true),
+        ];
+        let end_flags = [false, false, true, false, false, true];
+        let mar = database(trie(&nodes, b"xyzbcd", &end_flags, None)).unwrap();
+        assert_eq!(mar.names().unwrap(), ["a", "xyz", "abcd"]);
+    }
+
+    #[test]
+    fn restores_names_from_nested_tries() {
+        // The nested trie stores "long" reversed, as the path g, n, o, l
+        let next = trie(
+            &[
+                (Label::Byte(0), 1, false),
+                (Label::Byte(b'g'), 1, false),
+                (Label::Byte(b'n'), 1, false),
+                (Label::Byte(b'o'), 1, false),
+                (Label::Byte(b'l'), 0, true),
+            ],
+            &[],
+            &[],
+            None,
+        );
+        let nodes = [
+            (Label::Byte(0), 2, false),
+            (Label::Byte(b'a'), 1, false),
+            (Label::Link(4), 0, true),
+            (Label::Link(4), 0, true),
+        ];
+        let mar = database(trie(&nodes, &[], &[], Some(next))).unwrap();
+        assert_eq!(mar.names().unwrap(), ["long", "along"]);
+    }
+
+    #[test]
+    fn rejects_malformed_databases() {
+        assert!(MarDatabase::new(b"MAR\x01").is_err());
+        let mut data = MarDatabase::serialize_names(&["name"]);
+        assert_eq!(MarDatabase::new(&data).unwrap().names().unwrap(), ["name"]);
+        data.truncate(data.len() - 8);
+        assert!(MarDatabase::new(&data).is_err());
+    }
+}
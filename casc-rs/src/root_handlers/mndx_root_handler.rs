@@ -0,0 +1,301 @@
+use crate::casc_encoding_table::CascEncodingTable;
+use crate::entry::Entry;
+use crate::error::CascError;
+use crate::root_handlers::mar_database::MarDatabase;
+use crate::span_info::SpanInfo;
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Seek, SeekFrom};
+
+/// Represents a content key entry of an MNDX root.
+#[derive(Debug, Clone, Copy)]
+pub struct MndxRootEntry {
+    /// The package index in the low 24 bits, and flags in the high 8 bits.
+    pub flags: u32,
+    /// The content key of the file.
+    pub content_key: [u8; 16],
+    /// The decoded size of the file in bytes.
+    pub content_size: u32,
+}
+
+impl MndxRootEntry {
+    /// Set on the last entry of each file's group of entries.
+    pub const LAST_ENTRY: u32 = 0x8000_0000;
+
+    /// Returns the index of the package the entry belongs to.
+    pub fn package_index(&self) -> usize {
+        (self.flags & 0x00FF_FFFF) as usize
+    }
+
+    /// Returns whether this is the last entry of its file's group.
+    pub fn is_last(&self) -> bool {
+        self.flags & Self::LAST_ENTRY != 0
+    }
+}
+
+/// Handles the MNDX root structure used by Heroes of the Storm and StarCraft II.
+///
+/// File names are stored in three MAR databases: the package names, the file names with
+/// their package stripped, and the full file names. Each stripped name has a group of
+/// content key entries, one per package that contains a file of that name.
+#[derive(Debug)]
+pub struct MndxRootHandler {
+    pub file_entries: HashMap<String, Entry>,
+    /// The names of the packages files are grouped into, by package index.
+    pub packages: Vec<String>,
+    /// The names of files whose content key is missing from ENCODING, which cannot be opened.
+    pub unresolved_files: Vec<String>,
+}
+
+impl MndxRootHandler {
+    /// The magic of the MNDX root format (`MNDX` on disk).
+    pub const MNDX_MAGIC: u32 = 0x58444E4D;
+    /// The number of MAR databases in an MNDX root.
+    const MAR_COUNT: u32 = 3;
+    /// The size of a MAR info record.
+    const MAR_INFO_SIZE: u32 = 0x14;
+    /// The size of a content key entry.
+    const ENTRY_SIZE: u32 = 0x18;
+
+    pub fn new<R: Read + Seek>(
+        stream: &mut R,
+        encoding: &CascEncodingTable,
+    ) -> Result<Self, CascError> {
+        stream.seek(SeekFrom::Start(0))?;
+        let mut data = Vec::new();
+        stream.read_to_end(&mut data)?;
+        let mut reader = Cursor::new(data.as_slice());
+
+        let magic = reader.read_u32::<LittleEndian>()?;
+        let header_version = reader.read_u32::<LittleEndian>()?;
+        let format_version = reader.read_u32::<LittleEndian>()?;
+        if magic != Self::MNDX_MAGIC
+            || !(1..=2).contains(&header_version)
+            || !(1..=2).contains(&format_version)
+        {
+            return Err(CascError::InvalidData("Invalid MNDX header".to_string()));
+        }
+        if header_version == 2 {
+            // Build numbers
+            reader.read_u32::<LittleEndian>()?;
+            reader.read_u32::<LittleEndian>()?;
+        }
+        let mar_info_offset = reader.read_u32::<LittleEndian>()?;
+        let mar_info_count = reader.read_u32::<LittleEndian>()?;
+        let mar_info_size = reader.read_u32::<LittleEndian>()?;
+        let entries_offset = reader.read_u32::<LittleEndian>()?;
+        let entry_count = reader.read_u32::<LittleEndian>()?;
+        let file_name_count = reader.read_u32::<LittleEndian>()?;
+        let entry_size = reader.read_u32::<LittleEndian>()?;
+        if mar_info_count != Self::MAR_COUNT
+            || mar_info_size != Self::MAR_INFO_SIZE
+            || entry_size != Self::ENTRY_SIZE
+            || file_name_count > entry_count
+        {
+            return Err(CascError::InvalidData("Invalid MNDX header".to_string()));
+        }
+
+        let mut databases = Vec::with_capacity(mar_info_count as usize);
+        for i in 0..mar_info_count {
+            reader.set_position(mar_info_offset as u64 + (i * mar_info_size) as u64);
+            let _mar_index = reader.read_u32::<LittleEndian>()?;
+            let mar_size = reader.read_u64::<LittleEndian>()?;
+            let mar_offset = reader.read_u64::<LittleEndian>()?;
+            let mar_data = mar_offset
+                .checked_add(mar_size)
+                .and_then(|end| data.get(mar_offset as usize..end as usize))
+                .ok_or_else(|| {
+                    CascError::InvalidData(format!("MAR database {i} is out of range"))
+                })?;
+            databases.push(MarDatabase::new(mar_data)?);
+        }
+
+        reader.set_position(entries_offset as u64);
+        let mut entries = Vec::with_capacity(entry_count as usize);
+        for _ in 0..entry_count {
+            let flags = reader.read_u32::<LittleEndian>()?;
+            let mut content_key = [0u8; 16];
+            reader.read_exact(&mut content_key)?;
+            let content_size = reader.read_u32::<LittleEndian>()?;
+            entries.push(MndxRootEntry {
+                flags,
+                content_key,
+                content_size,
+            });
+        }
+
+        // Each stripped file name owns a run of entries, ending with the last entry flag
+        let mut groups = Vec::with_capacity(file_name_count as usize);
+        let mut start = 0;
+        for (i, entry) in entries.iter().enumerate() {
+            if entry.is_last() {
+                groups.push(&entries[start..=i]);
+                start = i + 1;
+            }
+        }
+        if groups.len() != file_name_count as usize || databases[1].len() > groups.len() {
+            return Err(CascError::InvalidData(
+                "MNDX entry count does not match the file names".to_string(),
+            ));
+        }
+
+        let packages = databases[0].names()?;
+        let stripped_names = databases[1]
+            .names()?
+            .into_iter()
+            .enumerate()
+            .map(|(index, name)| (name, index))
+            .collect::<HashMap<_, _>>();
+
+        let mut handler = MndxRootHandler {
+            file_entries: HashMap::new(),
+            packages,
+            unresolved_files: Vec::new(),
+        };
+        for name in databases[2].names()? {
+            let Some((package_index, stripped_name)) = handler.split_package(&name) else {
+                continue;
+            };
+            let Some(group) = stripped_names
+                .get(stripped_name)
+                .and_then(|&index| groups.get(index))
+            else {
+                continue;
+            };
+            let Some(entry) = group
+                .iter()
+                .find(|entry| entry.package_index() == package_index)
+            else {
+                continue;
+            };
+            let Some(encoding_key) = encoding
+                .get_content_entry(&entry.content_key)
+                .and_then(|encoding_entry| encoding_entry.encoding_keys.first())
+            else {
+                handler.unresolved_files.push(name);
+                continue;
+            };
+            let span = SpanInfo::new_with_content_key(
+                entry.content_key.to_vec(),
+                encoding_key.clone(),
+                entry.content_size as usize,
+            );
+            handler
+                .file_entries
+                .insert(name.clone(), Entry::new_with_spans(name, vec![span]));
+        }
+        Ok(handler)
+    }
+
+    /// Splits a full file name into the index of its package and the name within the package.
+    ///
+    /// The package is the longest package name that prefixes the file name, ignoring case.
+    fn split_package<'a>(&self, name: &'a str) -> Option<(usize, &'a str)> {
+        let (index, package) = self
+            .packages
+            .iter()
+            .enumerate()
+            .filter(|(_, package)| {
+                package.len() < name.len()
+                    && name.as_bytes()[..package.len()].eq_ignore_ascii_case(package.as_bytes())
+            })
+            .max_by_key(|(_, package)| package.len())?;
+        Some((index, name[package.len()..].trim_start_matches('/')))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Serialises a version 1 MNDX root from its three MAR databases and its entries.
+    fn mndx_root(databases: [&[&str]; 3], entries: &[(u32, [u8; 16], u32)]) -> Vec<u8> {
+        let databases = databases.map(MarDatabase::serialize_names);
+        let file_name_count = entries
+            .iter()
+            .filter(|entry| entry.0 & MndxRootEntry::LAST_ENTRY != 0)
+            .count() as u32;
+        let mar_info_offset = 40;
+        let mut mar_offset = mar_info_offset + 3 * MndxRootHandler::MAR_INFO_SIZE;
+        let entries_offset = mar_offset + databases.iter().map(Vec::len).sum::<usize>() as u32;
+
+        let mut data = Vec::new();
+        for value in [
+            MndxRootHandler::MNDX_MAGIC,
+            1,
+            1,
+            mar_info_offset,
+            3,
+            MndxRootHandler::MAR_INFO_SIZE,
+            entries_offset,
+            entries.len() as u32,
+            file_name_count,
+            MndxRootHandler::ENTRY_SIZE,
+        ] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        for (index, database) in databases.iter().enumerate() {
+            data.extend_from_slice(&(index as u32).to_le_bytes());
+            data.extend_from_slice(&(database.len() as u64).to_le_bytes());
+            data.extend_from_slice(&(mar_offset as u64).to_le_bytes());
+            mar_offset += database.len() as u32;
+        }
+        data.extend(databases.concat());
+        for (flags, content_key, content_size) in entries {
+            data.extend_from_slice(&flags.to_le_bytes());
+            data.extend_from_slice(content_key);
+            data.extend_from_slice(&content_size.to_le_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn resolves_files_through_their_packages() {
+        const LAST: u32 = MndxRootEntry::LAST_ENTRY;
+        // "a.txt" is in both packages, and "b.txt" only in the longer one, with a content key
+        // that is missing from ENCODING
+        let data = mndx_root(
+            [
+                &["mods", "mods/core"],
+                &["a.txt", "b.txt"],
+                &[
+                    "mods/a.txt",
+                    "MODS/CORE/a.txt",
+                    "mods/core/b.txt",
+                    "mods/b.txt",
+                    "other/a.txt",
+                ],
+            ],
+            &[
+                (0, [0xA0; 16], 10),
+                (1 | LAST, [0xA1; 16], 11),
+                (1 | LAST, [0xB1; 16], 12),
+            ],
+        );
+        let encoding = CascEncodingTable::from_entries(&[
+            ([0xA0; 16], [0xE0; 16], 10),
+            ([0xA1; 16], [0xE1; 16], 11),
+        ]);
+
+        let handler = MndxRootHandler::new(&mut Cursor::new(data), &encoding).unwrap();
+        assert_eq!(handler.packages, ["mods", "mods/core"]);
+        assert_eq!(handler.file_entries.len(), 2);
+        for (name, encoding_key, size) in [("mods/a.txt", 0xE0, 10), ("MODS/CORE/a.txt", 0xE1, 11)]
+        {
+            let span = &handler.file_entries[name].spans[0];
+            assert_eq!(span.encoding_key, [encoding_key; 16]);
+            assert_eq!(span.size, Some(size));
+        }
+        assert_eq!(handler.unresolved_files, ["mods/core/b.txt"]);
+    }
+
+    #[test]
+    fn rejects_entry_groups_that_do_not_match_the_names() {
+        let data = mndx_root(
+            [&["mods"], &["a.txt", "b.txt"], &["mods/a.txt"]],
+            &[(MndxRootEntry::LAST_ENTRY, [0xA0; 16], 10)],
+        );
+        let encoding = CascEncodingTable::from_entries(&[]);
+        assert!(MndxRootHandler::new(&mut Cursor::new(data), &encoding).is_err());
+    }
+}
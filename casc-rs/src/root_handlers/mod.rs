@@ -1 +1,8 @@
+pub(crate) mod diablo3_root_handler;
+pub(crate) mod install_root_handler;
+pub(crate) mod mar_database;
+pub(crate) mod mndx_root_handler;
+pub(crate) mod overwatch_root_handler;
+pub(crate) mod text_root_handler;
 pub(crate) mod tvfs_root_handler;
+pub(crate) mod wow_root_handler;
@@ -0,0 +1,571 @@
+use crate::casc_encoding_table::CascEncodingTable;
+use crate::cmf_key_provider::{CmfHeader, CmfKeyProvider};
+use crate::entry::Entry;
+use crate::error::CascError;
+use crate::span_info::SpanInfo;
+use aes::Aes256;
+use byteorder::{LittleEndian, ReadBytesExt};
+use cbc::cipher::generic_array::GenericArray;
+use cbc::cipher::{BlockDecryptMut, KeyIvInit};
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::sync::Arc;
+
+/// Represents an asset record of a CMF manifest.
+#[derive(Debug, Clone, Copy)]
+pub struct CmfAssetRecord {
+    /// The GUID of the asset, which encodes its type.
+    pub guid: u64,
+    /// The decoded size of the asset in bytes.
+    pub size: u32,
+    /// The content key of the asset.
+    pub content_key: [u8; 16],
+}
+
+impl CmfAssetRecord {
+    /// Returns the type of the asset, such as `0x004` for textures.
+    ///
+    /// The type is stored in bits 48 to 59 of the GUID, bit-reversed and off by one.
+    pub fn asset_type(&self) -> u16 {
+        let bits = ((self.guid >> 48) & 0xFFF) as u32;
+        (bits.reverse_bits() >> 20) as u16 + 1
+    }
+
+    /// Returns the name the asset is listed under, such as `004\0000000000001234.004`.
+    pub fn file_name(&self) -> String {
+        let asset_type = self.asset_type();
+        format!("{asset_type:03X}\\{:016X}.{asset_type:03X}", self.guid)
+    }
+}
+
+/// Represents the header of an APM manifest, which lists the packages of one locale and
+/// platform.
+///
+/// The package records that follow the header change layout with almost every build, so only
+/// the header is read.
+#[derive(Debug, Clone)]
+pub struct ApmHeader {
+    /// The name of the manifest in the root.
+    pub name: String,
+    /// The build of the game the manifest belongs to.
+    pub build_version: u32,
+    /// The magic, `apm` followed by the format version in the low byte.
+    pub magic: u32,
+    /// Every field of the header, in order, including those above.
+    pub fields: Vec<u32>,
+}
+
+impl ApmHeader {
+    /// Returns the format version of the manifest.
+    pub fn version(&self) -> u8 {
+        self.magic as u8
+    }
+}
+
+/// Handles the Overwatch root structure.
+///
+/// The root is a text file listing the game's named files, including the APM and CMF
+/// manifests. The CMF manifests list every asset by GUID, and the assets are named after
+/// their type and GUID. Manifests of newer builds are encrypted, and are only read when a
+/// [`CmfKeyProvider`] for their build is given.
+#[derive(Debug)]
+pub struct OverwatchRootHandler {
+    pub file_entries: HashMap<String, Entry>,
+    /// The names of asset files in `file_entries`, by GUID.
+    pub guids: HashMap<u64, String>,
+    /// The headers of the APM manifests, in the order listed by the root.
+    pub apm_headers: Vec<ApmHeader>,
+    /// The APM and CMF manifests that could not be read, such as encrypted CMF manifests of
+    /// a build with no key provider. Assets only listed in a skipped CMF manifest are missing
+    /// from `guids`.
+    pub skipped_manifests: Vec<String>,
+}
+
+impl OverwatchRootHandler {
+    /// The magic of a CMF manifest, `cmf` above the format version byte.
+    const CMF_MAGIC: u32 = 0x636D66;
+    /// The offsets, from the start of the file, the CMF magic is found at across builds.
+    const CMF_MAGIC_OFFSETS: [usize; 3] = [24, 28, 32];
+    /// The magic of an APM manifest, `apm` above the format version byte.
+    const APM_MAGIC: u32 = 0x61706D;
+    /// The offsets, from the start of the file, the APM magic is searched for at.
+    const APM_MAGIC_OFFSETS: [usize; 8] = [12, 16, 20, 24, 28, 32, 36, 40];
+    /// The size of a CMF entry.
+    const CMF_ENTRY_SIZE: usize = 20;
+    /// The size of a CMF asset record.
+    const CMF_RECORD_SIZE: usize = 29;
+    /// The extension of CMF manifests.
+    const CMF_EXTENSION: &'static str = ".cmf";
+    /// The extension of APM manifests.
+    const APM_EXTENSION: &'static str = ".apm";
+
+    /// Returns whether the root data looks like an Overwatch root: text with a header line
+    /// naming `MD5` and `FILENAME` columns.
+    pub fn is_overwatch_root(data: &[u8]) -> bool {
+        let header = data.split(|&b| b == b'\n').next().unwrap_or_default();
+        let Some(header) = header.strip_prefix(b"#") else {
+            return false;
+        };
+        let header = String::from_utf8_lossy(header);
+        let has_column = |column: &str| {
+            header
+                .split('|')
+                .any(|name| name.trim().eq_ignore_ascii_case(column))
+        };
+        has_column("MD5") && has_column("FILENAME")
+    }
+
+    /// Parses the root, reading each CMF manifest with `read_content`, which returns the
+    /// decoded contents of a file by content key.
+    ///
+    /// Encrypted manifests are decrypted with the provider matching their build. When several
+    /// manifests list the same GUID, such as localized assets, the first listed in the root
+    /// is kept.
+    pub fn new<R, F>(
+        stream: &mut R,
+        encoding: &CascEncodingTable,
+        read_content: F,
+        key_providers: &[Arc<dyn CmfKeyProvider>],
+    ) -> Result<Self, CascError>
+    where
+        R: Read + Seek,
+        F: Fn(&[u8; 16]) -> Result<Vec<u8>, CascError>,
+    {
+        stream.seek(SeekFrom::Start(0))?;
+        let mut data = Vec::new();
+        stream.read_to_end(&mut data)?;
+        if !Self::is_overwatch_root(&data) {
+            return Err(CascError::InvalidData(
+                "Invalid Overwatch root header".to_string(),
+            ));
+        }
+
+        let mut handler = OverwatchRootHandler {
+            file_entries: HashMap::new(),
+            guids: HashMap::new(),
+            apm_headers: Vec::new(),
+            skipped_manifests: Vec::new(),
+        };
+        let files = Self::parse_root(&data)?;
+        for (name, content_key) in &files {
+            handler.insert(encoding, name.clone(), content_key, None);
+        }
+
+        for (name, content_key) in &files {
+            let lowercase_name = name.to_ascii_lowercase();
+            if lowercase_name.ends_with(Self::APM_EXTENSION) {
+                match read_content(content_key).and_then(|apm| Self::parse_apm(name, &apm)) {
+                    Ok(header) => handler.apm_headers.push(header),
+                    Err(_) => handler.skipped_manifests.push(name.clone()),
+                }
+                continue;
+            }
+            if !lowercase_name.ends_with(Self::CMF_EXTENSION) {
+                continue;
+            }
+            let records = read_content(content_key)
+                .and_then(|manifest| Self::parse_cmf(name, &manifest, key_providers));
+            let Ok(records) = records else {
+                handler.skipped_manifests.push(name.clone());
+                continue;
+            };
+            for record in records {
+                if handler.guids.contains_key(&record.guid) {
+                    continue;
+                }
+                let file_name = record.file_name();
+                if handler.insert(
+                    encoding,
+                    file_name.clone(),
+                    &record.content_key,
+                    Some(record.size),
+                ) {
+                    handler.guids.insert(record.guid, file_name);
+                }
+            }
+        }
+        Ok(handler)
+    }
+
+    /// Parses the text of the root into file names and content keys.
+    ///
+    /// The header line names the columns, such as `#MD5|CHUNK_ID|PRIORITY|MPRIORITY|FILENAME`,
+    /// so they are looked up by name.
+    fn parse_root(data: &[u8]) -> Result<Vec<(String, [u8; 16])>, CascError> {
+        let text = String::from_utf8_lossy(data);
+        let mut lines = text.lines();
+        let header = lines
+            .next()
+            .and_then(|line| line.strip_prefix('#'))
+            .unwrap_or_default();
+        let column = |column: &str| {
+            header
+                .split('|')
+                .position(|name| name.trim().eq_ignore_ascii_case(column))
+                .ok_or_else(|| {
+                    CascError::InvalidData(format!("Overwatch root has no {column} column"))
+                })
+        };
+        let md5_column = column("MD5")?;
+        let name_column = column("FILENAME")?;
+
+        let mut files = Vec::new();
+        for line in lines {
+            let line = line.trim_end_matches('\r');
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields = line.split('|').collect::<Vec<_>>();
+            let (Some(md5), Some(name)) = (fields.get(md5_column), fields.get(name_column)) else {
+                return Err(CascError::InvalidData(format!(
+                    "Invalid Overwatch root line: {line}"
+                )));
+            };
+            let mut content_key = [0u8; 16];
+            hex::decode_to_slice(md5.trim(), &mut content_key).map_err(|_| {
+                CascError::InvalidData(format!("Invalid content key in Overwatch root: {md5}"))
+            })?;
+            files.push((name.trim().to_string(), content_key));
+        }
+        Ok(files)
+    }
+
+    /// Reads the fields of a manifest header, which grew between builds but always ends with
+    /// the magic, found at one of the given offsets.
+    fn read_header_fields(data: &[u8], magic: u32, offsets: &[usize]) -> Option<Vec<u32>> {
+        let read_u32 = |offset: usize| -> Option<u32> {
+            let bytes = data.get(offset..offset + 4)?;
+            Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        };
+        let magic_offset = offsets
+            .iter()
+            .copied()
+            .find(|&offset| read_u32(offset).is_some_and(|value| value >> 8 == magic))?;
+        (0..=magic_offset / 4)
+            .map(|index| read_u32(index * 4))
+            .collect()
+    }
+
+    /// Parses the header of an APM manifest.
+    fn parse_apm(name: &str, data: &[u8]) -> Result<ApmHeader, CascError> {
+        let fields = Self::read_header_fields(data, Self::APM_MAGIC, &Self::APM_MAGIC_OFFSETS)
+            .ok_or_else(|| CascError::InvalidData(format!("Invalid APM header: {name}")))?;
+        Ok(ApmHeader {
+            name: name.to_string(),
+            build_version: fields[0],
+            magic: fields[fields.len() - 1],
+            fields,
+        })
+    }
+
+    /// Parses a CMF manifest into its asset records, decrypting them if needed.
+    fn parse_cmf(
+        name: &str,
+        data: &[u8],
+        key_providers: &[Arc<dyn CmfKeyProvider>],
+    ) -> Result<Vec<CmfAssetRecord>, CascError> {
+        let fields = Self::read_header_fields(data, Self::CMF_MAGIC, &Self::CMF_MAGIC_OFFSETS)
+            .ok_or_else(|| CascError::InvalidData(format!("Invalid CMF header: {name}")))?;
+        let magic_offset = (fields.len() - 1) * 4;
+        let header = CmfHeader {
+            build_version: fields[0],
+            data_count: fields[fields.len() - 4],
+            entry_count: fields[fields.len() - 2],
+            magic: fields[fields.len() - 1],
+            fields,
+        };
+
+        let mut body = data[magic_offset + 4..].to_vec();
+        if header.is_encrypted() {
+            let provider = key_providers
+                .iter()
+                .find(|provider| provider.build_version() == header.build_version)
+                .ok_or_else(|| {
+                    CascError::Other(format!(
+                        "No CMF key provider for build {}",
+                        header.build_version
+                    ))
+                })?;
+            Self::decrypt_cmf(name, &header, provider.as_ref(), &mut body)?;
+        }
+
+        let entries_size = header.entry_count as usize * Self::CMF_ENTRY_SIZE;
+        let records_size = header.data_count as usize * Self::CMF_RECORD_SIZE;
+        if entries_size + records_size > body.len() {
+            return Err(CascError::InvalidData(format!(
+                "CMF records run past the end of the data: {name}"
+            )));
+        }
+        let mut reader = Cursor::new(&body[entries_size..]);
+        let mut records = Vec::with_capacity(header.data_count as usize);
+        for _ in 0..header.data_count {
+            let guid = reader.read_u64::<LittleEndian>()?;
+            let size = reader.read_u32::<LittleEndian>()?;
+            let _unknown = reader.read_u8()?;
+            let mut content_key = [0u8; 16];
+            reader.read_exact(&mut content_key)?;
+            records.push(CmfAssetRecord {
+                guid,
+                size,
+                content_key,
+            });
+        }
+        Ok(records)
+    }
+
+    /// Decrypts the records of a CMF manifest in place with AES-256 in CBC mode.
+    ///
+    /// Only whole blocks are encrypted, so any trailing bytes are left as they are.
+    fn decrypt_cmf(
+        name: &str,
+        header: &CmfHeader,
+        provider: &dyn CmfKeyProvider,
+        body: &mut [u8],
+    ) -> Result<(), CascError> {
+        let stem = name.rsplit(['/', '\\']).next().unwrap_or(name);
+        let stem = stem.rsplit_once('.').map_or(stem, |(stem, _)| stem);
+        let digest: [u8; 20] = Sha1::digest(stem.as_bytes()).into();
+
+        let key = provider.key(header, 32);
+        let iv = provider.iv(header, &digest, 16);
+        let mut decryptor = cbc::Decryptor::<Aes256>::new_from_slices(&key, &iv).map_err(|_| {
+            CascError::InvalidData(format!(
+                "Invalid CMF key or IV length for build {}",
+                header.build_version
+            ))
+        })?;
+        for block in body.chunks_exact_mut(16) {
+            decryptor.decrypt_block_mut(GenericArray::from_mut_slice(block));
+        }
+        Ok(())
+    }
+
+    /// Adds a file by content key, resolved through ENCODING, returning whether it was added.
+    ///
+    /// The decoded size is taken from ENCODING unless given.
+    fn insert(
+        &mut self,
+        encoding: &CascEncodingTable,
+        name: String,
+        content_key: &[u8; 16],
+        size: Option<u32>,
+    ) -> bool {
+        let Some(encoding_entry) = encoding.get_content_entry(content_key) else {
+            return false;
+        };
+        let Some(encoding_key) = encoding_entry.encoding_keys.first() else {
+            return false;
+        };
+        let span = SpanInfo::new_with_content_key(
+            content_key.to_vec(),
+            encoding_key.clone(),
+            size.map_or(encoding_entry.content_size as usize, |size| size as usize),
+        );
+        self.file_entries
+            .insert(name.clone(), Entry::new_with_spans(name, vec![span]));
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmf_key_provider::{procedures, CmfKeyTable};
+
+    const ENCRYPTED_NAME: &str = "TactManifest\\Win_SPWin_RCN_LenUS_EExt.cmf";
+
+    /// The records of the test manifests, as GUID, size and content key.
+    const RECORDS: [(u64, u32, [u8; 16]); 2] = [
+        (0x0C00_0000_0000_1234, 10, [0xA0; 16]),
+        (0x0800_0000_0000_5678, 11, [0xA1; 16]),
+    ];
+
+    /// Serialises a CMF manifest with one entry and the test records, with its magic at the
+    /// given offset.
+    fn cmf(version: u8, magic_offset: usize) -> Vec<u8> {
+        let mut fields = vec![0u32; magic_offset / 4 + 1];
+        let count = fields.len();
+        fields[0] = 50000;
+        fields[count - 4] = RECORDS.len() as u32;
+        fields[count - 2] = 1;
+        fields[count - 1] = (OverwatchRootHandler::CMF_MAGIC << 8) | version as u32;
+        let mut data = fields
+            .iter()
+            .flat_map(|field| field.to_le_bytes())
+            .collect::<Vec<_>>();
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&0x1111u64.to_le_bytes());
+        data.extend_from_slice(&0x2222u64.to_le_bytes());
+        for (guid, size, content_key) in RECORDS {
+            data.extend_from_slice(&guid.to_le_bytes());
+            data.extend_from_slice(&size.to_le_bytes());
+            data.push(0);
+            data.extend_from_slice(&content_key);
+        }
+        data
+    }
+
+    /// Returns the test CMF manifest with its records encrypted by [`key_table`], as
+    /// produced by an independent AES-256-CBC implementation.
+    fn encrypted_cmf() -> Vec<u8> {
+        let mut data = cmf(0x14, 24);
+        data.truncate(28);
+        data.extend(
+            hex::decode(
+                "06aecdbf594569f7f3ec75fb140774b94b44fbf7f9be6d78a98d4fed1ffa3245\
+                 287d7146972d2b5bc01d2284bfd12fd33061e0bcdf432b8e22717bee835fc4b4\
+                 a1a1a1a1a1a1a1a1a1a1a1a1a1a1",
+            )
+            .unwrap(),
+        );
+        data
+    }
+
+    fn key_table() -> Arc<dyn CmfKeyProvider> {
+        let table = std::array::from_fn(|i| (i * 167 + 13) as u8);
+        Arc::new(CmfKeyTable::new(
+            50000,
+            table,
+            procedures::key_by_build,
+            procedures::iv_by_digest,
+        ))
+    }
+
+    fn assert_records(records: &[CmfAssetRecord]) {
+        let records = records
+            .iter()
+            .map(|record| (record.guid, record.size, record.content_key))
+            .collect::<Vec<_>>();
+        assert_eq!(records, RECORDS);
+    }
+
+    #[test]
+    fn names_assets_by_type_and_guid() {
+        let record = |guid| CmfAssetRecord {
+            guid,
+            size: 0,
+            content_key: [0; 16],
+        };
+        assert_eq!(record(0x0C00_0000_0000_1234).asset_type(), 0x004);
+        assert_eq!(
+            record(0x0C00_0000_0000_1234).file_name(),
+            "004\\0C00000000001234.004"
+        );
+        assert_eq!(record(0x0000_0000_0000_0001).asset_type(), 0x001);
+        // Bits outside the type, such as the platform and region, do not change it
+        assert_eq!(record(0xFA50_8000_0000_0001).asset_type(), 0x0A6);
+        assert_eq!(
+            record(0xFA50_8000_0000_0001).file_name(),
+            "0A6\\FA50800000000001.0A6"
+        );
+    }
+
+    #[test]
+    fn parses_root_columns_by_name() {
+        let data = b"#FILENAME|CHUNK_ID|md5\r\n\
+            # comment\r\n\
+            \r\n\
+            TactManifest\\Win_SPWin_RCN_EExt.apm|1|00112233445566778899AABBCCDDEEFF\r\n\
+            TactManifest\\Win_SPWin_RCN_EExt.cmf|2|ffeeddccbbaa99887766554433221100\r\n";
+        assert!(OverwatchRootHandler::is_overwatch_root(data));
+        let files = OverwatchRootHandler::parse_root(data).unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].0, "TactManifest\\Win_SPWin_RCN_EExt.apm");
+        assert_eq!(hex::encode(files[0].1), "00112233445566778899aabbccddeeff");
+        assert_eq!(files[1].1[0], 0xFF);
+
+        assert!(!OverwatchRootHandler::is_overwatch_root(
+            b"#FILENAME|SIZE\n"
+        ));
+        assert!(OverwatchRootHandler::parse_root(b"#MD5|FILENAME\nabc\n").is_err());
+        assert!(OverwatchRootHandler::parse_root(b"#MD5|FILENAME\nxyz|a.cmf\n").is_err());
+    }
+
+    #[test]
+    fn parses_plaintext_cmf_manifests() {
+        for magic_offset in OverwatchRootHandler::CMF_MAGIC_OFFSETS {
+            let records =
+                OverwatchRootHandler::parse_cmf("a.cmf", &cmf(0x13, magic_offset), &[]).unwrap();
+            assert_records(&records);
+        }
+        assert!(OverwatchRootHandler::parse_cmf("a.cmf", &[0; 40], &[]).is_err());
+        let mut truncated = cmf(0x13, 24);
+        truncated.truncate(truncated.len() - 1);
+        assert!(OverwatchRootHandler::parse_cmf("a.cmf", &truncated, &[]).is_err());
+    }
+
+    #[test]
+    fn decrypts_cmf_manifests_with_key_tables() {
+        let records =
+            OverwatchRootHandler::parse_cmf(ENCRYPTED_NAME, &encrypted_cmf(), &[key_table()])
+                .unwrap();
+        assert_records(&records);
+
+        // Without a provider for the build, or with the key table of another build
+        assert!(OverwatchRootHandler::parse_cmf(ENCRYPTED_NAME, &encrypted_cmf(), &[]).is_err());
+        let other_table = Arc::new(CmfKeyTable::new(
+            50000,
+            [0x5A; CmfKeyTable::SIZE],
+            procedures::key_by_build,
+            procedures::iv_by_digest,
+        ));
+        let records =
+            OverwatchRootHandler::parse_cmf(ENCRYPTED_NAME, &encrypted_cmf(), &[other_table]);
+        assert!(records.map_or(true, |records| records[0].guid != RECORDS[0].0));
+    }
+
+    #[test]
+    fn lists_assets_and_skips_unreadable_manifests() {
+        let mut contents = HashMap::new();
+        contents.insert([0x01; 16], cmf(0x13, 28));
+        contents.insert([0x02; 16], encrypted_cmf());
+        let mut apm = 50000u32.to_le_bytes().to_vec();
+        apm.extend_from_slice(&[0; 12]);
+        apm.extend_from_slice(&((OverwatchRootHandler::APM_MAGIC << 8) | 0x17).to_le_bytes());
+        contents.insert([0x03; 16], apm);
+        let root = format!(
+            "#MD5|FILENAME\n{}|TactManifest\\Win_SPWin_RCN_EExt.cmf\n{}|{ENCRYPTED_NAME}\n\
+             {}|TactManifest\\Win_SPWin_RCN_EExt.apm\n{}|TactManifest\\Win_SPWin_RCN_LenUS_EExt.apm\n",
+            hex::encode([0x01; 16]),
+            hex::encode([0x02; 16]),
+            hex::encode([0x03; 16]),
+            hex::encode([0x04; 16]),
+        );
+        let encoding = CascEncodingTable::from_entries(&[
+            ([0x01; 16], [0xE1; 16], 0),
+            ([0xA0; 16], [0xEA; 16], 10),
+            ([0xA1; 16], [0xEB; 16], 11),
+        ]);
+        let read_content = |content_key: &[u8; 16]| {
+            contents
+                .get(content_key)
+                .cloned()
+                .ok_or_else(|| CascError::FileNotFound(hex::encode(content_key)))
+        };
+
+        let handler = OverwatchRootHandler::new(
+            &mut Cursor::new(root.as_bytes()),
+            &encoding,
+            read_content,
+            &[],
+        )
+        .unwrap();
+        assert_eq!(handler.guids.len(), 2);
+        assert_eq!(handler.guids[&RECORDS[0].0], "004\\0C00000000001234.004");
+        assert_eq!(
+            handler.file_entries["004\\0C00000000001234.004"].spans[0].encoding_key,
+            [0xEA; 16]
+        );
+        assert!(handler
+            .file_entries
+            .contains_key("TactManifest\\Win_SPWin_RCN_EExt.cmf"));
+        assert_eq!(handler.apm_headers.len(), 1);
+        assert_eq!(handler.apm_headers[0].build_version, 50000);
+        assert_eq!(handler.apm_headers[0].version(), 0x17);
+        assert_eq!(
+            handler.skipped_manifests,
+            [ENCRYPTED_NAME, "TactManifest\\Win_SPWin_RCN_LenUS_EExt.apm"]
+        );
+    }
+}
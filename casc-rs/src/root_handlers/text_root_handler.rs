@@ -0,0 +1,182 @@
+use crate::casc_encoding_table::CascEncodingTable;
+use crate::entry::Entry;
+use crate::error::CascError;
+use crate::span_info::SpanInfo;
+use std::collections::HashMap;
+use std::io::{Read, Seek, SeekFrom};
+
+/// Handles the plain-text root used by StarCraft: Remastered and Warcraft III: Reforged.
+///
+/// Each line is a file name and its content key in hex, separated by `|`, and sometimes
+/// followed by further columns that are ignored. Names are kept as listed, so files in
+/// locale folders such as `enUS\` or `_locales\enus.w3mod:` are listed once per locale.
+#[derive(Debug)]
+pub struct TextRootHandler {
+    pub file_entries: HashMap<String, Entry>,
+}
+
+impl TextRootHandler {
+    /// Returns whether the root data looks like a text root: its first line is a name and
+    /// a content key separated by `|`.
+    pub fn is_text_root(data: &[u8]) -> bool {
+        let text = String::from_utf8_lossy(data);
+        text.lines()
+            .map(|line| line.trim_end_matches('\r'))
+            .find(|line| !line.is_empty() && !line.starts_with('#'))
+            .is_some_and(|line| Self::parse_line(line).is_some())
+    }
+
+    /// Parses the root, resolving each listed content key through ENCODING.
+    ///
+    /// Files whose content key is missing from ENCODING are skipped. When a name is listed
+    /// more than once, the first line is kept.
+    pub fn new<R: Read + Seek>(
+        stream: &mut R,
+        encoding: &CascEncodingTable,
+    ) -> Result<Self, CascError> {
+        stream.seek(SeekFrom::Start(0))?;
+        let mut data = Vec::new();
+        stream.read_to_end(&mut data)?;
+        let text = String::from_utf8_lossy(&data);
+
+        let mut file_entries = HashMap::new();
+        for line in text.lines() {
+            let line = line.trim_end_matches('\r');
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, content_key) = Self::parse_line(line)
+                .ok_or_else(|| CascError::InvalidData(format!("Invalid text root line: {line}")))?;
+            if file_entries.contains_key(name) {
+                continue;
+            }
+            let Some(encoding_entry) = encoding.get_content_entry(&content_key) else {
+                continue;
+            };
+            let Some(encoding_key) = encoding_entry.encoding_keys.first() else {
+                continue;
+            };
+            let span = SpanInfo::new_with_content_key(
+                content_key.to_vec(),
+                encoding_key.clone(),
+                encoding_entry.content_size as usize,
+            );
+            file_entries.insert(
+                name.to_string(),
+                Entry::new_with_spans(name.to_string(), vec![span]),
+            );
+        }
+        Ok(TextRootHandler { file_entries })
+    }
+
+    /// Splits a line into its file name and content key.
+    fn parse_line(line: &str) -> Option<(&str, [u8; 16])> {
+        let mut fields = line.split('|');
+        let name = fields.next()?.trim();
+        let mut content_key = [0u8; 16];
+        hex::decode_to_slice(fields.next()?.trim(), &mut content_key).ok()?;
+        (!name.is_empty()).then_some((name, content_key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTENT_KEY: &str = "00112233445566778899aabbccddeeff";
+
+    #[test]
+    fn parses_names_and_content_keys() {
+        for (line, name) in [
+            (
+                format!("enUS\\SD\\map.bin|{CONTENT_KEY}"),
+                "enUS\\SD\\map.bin",
+            ),
+            (
+                format!("_locales\\enus.w3mod:units\\unit.slk|{CONTENT_KEY}|1|extra"),
+                "_locales\\enus.w3mod:units\\unit.slk",
+            ),
+            (
+                format!(" padded.txt | {} ", CONTENT_KEY.to_uppercase()),
+                "padded.txt",
+            ),
+        ] {
+            let (parsed, content_key) = TextRootHandler::parse_line(&line).unwrap();
+            assert_eq!(parsed, name);
+            assert_eq!(hex::encode(content_key), CONTENT_KEY);
+        }
+        for line in [
+            "file.txt".to_string(),
+            format!("|{CONTENT_KEY}"),
+            "file.txt|0011".to_string(),
+            format!("file.txt|{CONTENT_KEY}00"),
+        ] {
+            assert!(TextRootHandler::parse_line(&line).is_none(), "{line}");
+        }
+    }
+
+    #[test]
+    fn detects_text_roots() {
+        let root =
+            format!("# header\r\n\r\nenUS\\a.txt|{CONTENT_KEY}\r\nb.txt|{CONTENT_KEY}|x\r\n");
+        assert!(TextRootHandler::is_text_root(root.as_bytes()));
+        assert!(!TextRootHandler::is_text_root(b""));
+        assert!(!TextRootHandler::is_text_root(
+            b"#MD5|FILENAME\nnot a root line\n"
+        ));
+
+        // A legacy World of Warcraft root: one block of two records, with content keys that
+        // happen to be made of `|` bytes
+        let mut legacy = Vec::new();
+        for value in [2u32, 0, 0xFFFF_FFFF, 3, 0] {
+            legacy.extend_from_slice(&value.to_le_bytes());
+        }
+        for name_hash in [0x1000u64, 0x1001] {
+            legacy.extend_from_slice(&[b'|'; 16]);
+            legacy.extend_from_slice(&name_hash.to_le_bytes());
+        }
+        assert!(!TextRootHandler::is_text_root(&legacy));
+    }
+
+    #[test]
+    fn lists_the_first_line_of_each_name() {
+        let root = format!(
+            "a.txt|{CONTENT_KEY}\na.txt|{}\nmissing.txt|{}\n",
+            hex::encode([0x22; 16]),
+            hex::encode([0x33; 16])
+        );
+        let mut content_key = [0u8; 16];
+        hex::decode_to_slice(CONTENT_KEY, &mut content_key).unwrap();
+        let encoding = CascEncodingTable::from_entries(&[
+            (content_key, [0xE1; 16], 5),
+            ([0x22; 16], [0xE2; 16], 6),
+        ]);
+
+        let handler = TextRootHandler::new(&mut std::io::Cursor::new(root), &encoding).unwrap();
+        assert_eq!(handler.file_entries.len(), 1);
+        assert_eq!(
+            handler.file_entries["a.txt"].spans[0].encoding_key,
+            [0xE1; 16]
+        );
+        assert!(TextRootHandler::new(&mut std::io::Cursor::new("a.txt|xyz\n"), &encoding).is_err());
+    }
+
+    #[test]
+    fn tells_text_roots_and_overwatch_roots_apart() {
+        use crate::root_handlers::overwatch_root_handler::OverwatchRootHandler;
+
+        // Overwatch roots are also `|` separated text, with the content key first, so neither
+        // kind of root may be taken for the other whichever is checked first
+        let overwatch = format!(
+            "#MD5|CHUNK_ID|FILENAME\n{CONTENT_KEY}|1|TactManifest\\Win_SPWin_RCN_EExt.cmf\n"
+        );
+        assert!(OverwatchRootHandler::is_overwatch_root(
+            overwatch.as_bytes()
+        ));
+        assert!(!TextRootHandler::is_text_root(overwatch.as_bytes()));
+
+        let text = format!("# name|md5\nenUS\\a.txt|{CONTENT_KEY}\n");
+        assert!(TextRootHandler::is_text_root(text.as_bytes()));
+        assert!(!OverwatchRootHandler::is_overwatch_root(text.as_bytes()));
+    }
+}
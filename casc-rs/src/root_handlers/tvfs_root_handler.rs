@@ -4,7 +4,7 @@ use crate::ext::io_ext::{ArrayReadExt, ReadExt, SeekExt};
 use crate::path_table_node_flags::PathTableNodeFlags;
 use crate::span_info::SpanInfo;
 use byteorder::{BigEndian, ReadBytesExt};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::io;
 use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
 use std::string::String;
@@ -28,11 +28,59 @@ pub struct TVFSHeader {
     pub cft_table_offset: i32,
     pub cft_table_size: i32,
     pub max_depth: u16,
+    /// The offset of the ESpec table, if the manifest has write support.
+    pub est_table_offset: i32,
+    /// The size of the ESpec table, if the manifest has write support.
+    pub est_table_size: i32,
 }
 
 impl TVFSHeader {
+    /// The content file table records include the content key of each file.
+    pub const FLAG_INCLUDE_CKEY: i32 = 0x0001;
+    /// The manifest has write support, and the content file table records include an ESpec index.
+    pub const FLAG_WRITE_SUPPORT: i32 = 0x0002;
+    /// The manifest has patch support, and the content file table records include patch data.
+    pub const FLAG_PATCH_SUPPORT: i32 = 0x0004;
+    /// All paths in the path table have been converted to lowercase.
+    pub const FLAG_LOWERCASE_MANIFEST: i32 = 0x0008;
+
+    /// Checks if the flag is set.
+    pub fn has_flag(&self, flag: i32) -> bool {
+        (self.flags & flag) == flag
+    }
+
+    /// Returns whether the content file table records end with the decoded size of their
+    /// blob, after the content key.
+    ///
+    /// No flag marks the field, so it is told apart by the size of the table: without patch
+    /// support every record has the same size, so the table holds a whole number of them.
+    /// When both record sizes fit the table, or records vary in size, the field is taken to
+    /// be absent.
+    pub fn has_blob_size(&self) -> bool {
+        if !self.has_flag(Self::FLAG_INCLUDE_CKEY) || self.has_flag(Self::FLAG_PATCH_SUPPORT) {
+            return false;
+        }
+        let mut record_size = self.encoding_key_size as usize + 4 + 16;
+        if self.has_flag(Self::FLAG_WRITE_SUPPORT) {
+            record_size += Self::offset_size(self.est_table_size);
+        }
+        let table_size = self.cft_table_size as usize;
+        table_size.is_multiple_of(record_size + 4) && !table_size.is_multiple_of(record_size)
+    }
+
+    /// Returns the size of the offsets into a table of the given size, as stored in other
+    /// tables.
+    fn offset_size(table_size: i32) -> usize {
+        match table_size {
+            0x100_0000.. => 4,
+            0x1_0000.. => 3,
+            0x100.. => 2,
+            _ => 1,
+        }
+    }
+
     pub fn read<R: Read>(reader: &mut R) -> io::Result<Self> {
-        Ok(Self {
+        let mut header = Self {
             signature: reader.read_u32::<BigEndian>()?,
             format_version: reader.read_u8()?,
             header_size: reader.read_u8()?,
@@ -46,7 +94,15 @@ impl TVFSHeader {
             cft_table_offset: reader.read_i32::<BigEndian>()?,
             cft_table_size: reader.read_i32::<BigEndian>()?,
             max_depth: reader.read_u16::<BigEndian>()?,
-        })
+            est_table_offset: 0,
+            est_table_size: 0,
+        };
+        // Manifests with write support carry an ESpec table
+        if header.has_flag(Self::FLAG_WRITE_SUPPORT) {
+            header.est_table_offset = reader.read_i32::<BigEndian>()?;
+            header.est_table_size = reader.read_i32::<BigEndian>()?;
+        }
+        Ok(header)
     }
 }
 
@@ -60,6 +116,18 @@ pub struct PathTableNode {
     pub value: Option<i32>,
 }
 
+/// A TVFS sub-manifest listed in the build config (`vfs-1`, `vfs-2`, ...), which may be mounted
+/// into another manifest as a directory.
+#[derive(Debug)]
+pub(crate) struct VfsManifest {
+    /// The name of the manifest in the build config.
+    pub(crate) name: String,
+    /// The base64-encoded truncated encoding key of the manifest.
+    pub(crate) base64_encoding_key: String,
+    /// The file entries of the manifest, before any mounts are resolved.
+    pub(crate) file_entries: HashMap<String, Entry>,
+}
+
 /// Handles the TVFS root structure, including path and VFS tables, for a CASC archive.
 ///
 /// Provides access to file entries and table readers for further processing.
@@ -68,11 +136,18 @@ pub struct TVFSRootHandler {
     pub path_table_reader: Cursor<Vec<u8>>,
     pub vfs_table_reader: Cursor<Vec<u8>>,
     pub cft_table_reader: Cursor<Vec<u8>>,
+    /// The ESpec strings of the ESpec table, indexed by the ESpec index of CFT records.
+    pub especs: Vec<String>,
     pub header: TVFSHeader,
     pub file_entries: HashMap<String, Entry>,
+    /// The names of the sub-manifests that could not be read, so whose files are not listed.
+    pub skipped_manifests: Vec<String>,
 }
 
 impl TVFSRootHandler {
+    /// The maximum depth of nested mounts, which guards against manifests that mount each other.
+    const MAX_MOUNT_DEPTH: usize = 8;
+
     pub fn new<R: Read + Seek>(stream: &mut R) -> Result<Self, CascError> {
         stream.seek(SeekFrom::Start(0))?;
         let mut reader = BufReader::new(stream);
@@ -88,12 +163,28 @@ impl TVFSRootHandler {
         reader.seek(SeekFrom::Start(header.cft_table_offset as u64))?;
         let cft_table_buf = reader.read_array::<u8>(header.cft_table_size as usize)?;
 
+        // The ESpec table is a block of null terminated strings
+        let mut especs = Vec::new();
+        if header.est_table_size > 0 {
+            reader.seek(SeekFrom::Start(header.est_table_offset as u64))?;
+            let est_table_buf = reader.read_array::<u8>(header.est_table_size as usize)?;
+            especs = est_table_buf
+                .split(|b| *b == 0)
+                .map(|s| String::from_utf8_lossy(s).into_owned())
+                .collect();
+            if especs.last().is_some_and(|s| s.is_empty()) {
+                especs.pop();
+            }
+        }
+
         let mut handler = TVFSRootHandler {
             path_table_reader: Cursor::new(path_table_buf),
             vfs_table_reader: Cursor::new(vfs_table_buf),
             cft_table_reader: Cursor::new(cft_table_buf),
+            especs,
             header,
             file_entries: HashMap::new(),
+            skipped_manifests: Vec::new(),
         };
 
         let end =
@@ -144,8 +235,8 @@ impl TVFSRootHandler {
         let span_count = self.vfs_table_reader.read_u8()?;
         let mut spans = Vec::new();
         for _ in 0..span_count {
-            let _ref_file_offset = self.vfs_table_reader.read_i32::<BigEndian>()?;
-            let _size_of_span = self.vfs_table_reader.read_i32::<BigEndian>()?;
+            let ref_file_offset = self.vfs_table_reader.read_i32::<BigEndian>()?;
+            let size_of_span = self.vfs_table_reader.read_i32::<BigEndian>()?;
             let cft_offset = Self::read_variable_size_int(
                 &mut self.vfs_table_reader,
                 self.header.cft_table_size as usize,
@@ -153,9 +244,39 @@ impl TVFSRootHandler {
 
             self.cft_table_reader.set_position(cft_offset as u64);
 
+            // CFT records hold the EKey and encoded size, followed by optional fields as
+            // enabled by the header flags
             let mut buf = vec![0u8; self.header.encoding_key_size as usize];
             self.cft_table_reader.read_exact(&mut buf)?;
-            spans.push(SpanInfo::new_with_encoding_key(buf));
+            let _encoded_size = self.cft_table_reader.read_u32::<BigEndian>()?;
+
+            let espec = if self.header.has_flag(TVFSHeader::FLAG_WRITE_SUPPORT) {
+                let espec_index = Self::read_variable_size_int(
+                    &mut self.cft_table_reader,
+                    self.header.est_table_size as usize,
+                )?;
+                self.especs.get(espec_index as usize).cloned()
+            } else {
+                None
+            };
+
+            let mut span = if self.header.has_flag(TVFSHeader::FLAG_INCLUDE_CKEY) {
+                let mut content_key = vec![0u8; 16];
+                self.cft_table_reader.read_exact(&mut content_key)?;
+                let mut span =
+                    SpanInfo::new_with_content_key(content_key, buf, size_of_span as usize);
+                if self.header.has_blob_size() {
+                    span.blob_size = Some(self.cft_table_reader.read_u32::<BigEndian>()? as u64);
+                }
+                span
+            } else {
+                SpanInfo::new_with_encoding_key(buf, Some(size_of_span as usize))
+            };
+            // Patch records may follow, but are not needed to read the file
+            span.espec = espec;
+            // The span references `size_of_span` bytes of the decoded blob, from `ref_file_offset`
+            span.offset = ref_file_offset as u64;
+            spans.push(span);
         }
         let mut entry = Entry::new_with_spans(name, spans);
 
@@ -163,6 +284,79 @@ impl TVFSRootHandler {
         Ok(())
     }
 
+    /// Mounts the given sub-manifests into the file entries.
+    ///
+    /// An entry whose content is one of the manifests is replaced by the manifest's entries,
+    /// as a directory of the same name. Manifests that are not mounted anywhere are listed
+    /// under a directory named after them, so that none of their files are lost.
+    pub(crate) fn mount_manifests(&mut self, manifests: &[VfsManifest]) {
+        let manifests_by_key = manifests
+            .iter()
+            .map(|manifest| (manifest.base64_encoding_key.as_str(), manifest))
+            .collect::<HashMap<_, _>>();
+        let mut mounted = HashSet::new();
+
+        let file_entries = std::mem::take(&mut self.file_entries);
+        self.file_entries = Self::resolve_mounts(file_entries, &manifests_by_key, &mut mounted, 0);
+
+        for manifest in manifests {
+            if mounted.contains(manifest.base64_encoding_key.as_str()) {
+                continue;
+            }
+            let file_entries = Self::resolve_mounts(
+                manifest.file_entries.clone(),
+                &manifests_by_key,
+                &mut mounted,
+                1,
+            );
+            Self::insert_mounted(&mut self.file_entries, &manifest.name, file_entries);
+        }
+    }
+
+    fn resolve_mounts<'a>(
+        file_entries: HashMap<String, Entry>,
+        manifests_by_key: &HashMap<&'a str, &'a VfsManifest>,
+        mounted: &mut HashSet<&'a str>,
+        depth: usize,
+    ) -> HashMap<String, Entry> {
+        let mut resolved = HashMap::with_capacity(file_entries.len());
+        for (name, entry) in file_entries {
+            let manifest = match entry.spans.as_slice() {
+                [span] if depth < Self::MAX_MOUNT_DEPTH => manifests_by_key
+                    .get_key_value(span.base64_encoding_key.as_str())
+                    .map(|(key, manifest)| (*key, *manifest)),
+                _ => None,
+            };
+            match manifest {
+                Some((key, manifest)) => {
+                    mounted.insert(key);
+                    let mounted_entries = Self::resolve_mounts(
+                        manifest.file_entries.clone(),
+                        manifests_by_key,
+                        mounted,
+                        depth + 1,
+                    );
+                    Self::insert_mounted(&mut resolved, &name, mounted_entries);
+                }
+                None => {
+                    resolved.insert(name, entry);
+                }
+            }
+        }
+        resolved
+    }
+
+    fn insert_mounted(
+        file_entries: &mut HashMap<String, Entry>,
+        mount_point: &str,
+        mounted_entries: HashMap<String, Entry>,
+    ) {
+        for (name, mut entry) in mounted_entries {
+            entry.name = format!("{mount_point}\\{name}");
+            file_entries.insert(entry.name.clone(), entry);
+        }
+    }
+
     fn read_variable_size_int<R: Read + Seek>(
         reader: &mut R,
         data_size: usize,
@@ -221,3 +415,130 @@ impl TVFSRootHandler {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Serializes a manifest listing `file0`, `file1`, ... at the top level, each a span of
+    /// 10 bytes at offset 2 of the blob of its CFT record. Each record is given as the bytes
+    /// following its encoding key and encoded size.
+    fn manifest(flags: i32, records: &[Vec<u8>], especs: &[&str]) -> Vec<u8> {
+        let has_est = flags & TVFSHeader::FLAG_WRITE_SUPPORT != 0;
+        let mut path = Vec::new();
+        let mut vfs = Vec::new();
+        let mut cft = Vec::new();
+        for (index, record) in records.iter().enumerate() {
+            let name = format!("file{index}");
+            path.push(name.len() as u8);
+            path.extend_from_slice(name.as_bytes());
+            path.push(0xFF);
+            path.extend_from_slice(&(vfs.len() as i32).to_be_bytes());
+
+            vfs.push(1);
+            vfs.extend_from_slice(&2i32.to_be_bytes());
+            vfs.extend_from_slice(&10i32.to_be_bytes());
+            vfs.push(cft.len() as u8);
+
+            cft.extend_from_slice(&[index as u8; 9]);
+            cft.extend_from_slice(&0x40u32.to_be_bytes());
+            cft.extend_from_slice(record);
+        }
+        let est = especs
+            .iter()
+            .flat_map(|espec| espec.bytes().chain([0]))
+            .collect::<Vec<_>>();
+
+        let header_size: i32 = if has_est { 0x2E } else { 0x26 };
+        let mut data = b"TVFS".to_vec();
+        data.extend_from_slice(&[1, header_size as u8, 9, 9]);
+        let mut offset = header_size;
+        data.extend_from_slice(&flags.to_be_bytes());
+        for table in [&path, &vfs, &cft] {
+            data.extend_from_slice(&offset.to_be_bytes());
+            data.extend_from_slice(&(table.len() as i32).to_be_bytes());
+            offset += table.len() as i32;
+        }
+        data.extend_from_slice(&1u16.to_be_bytes());
+        if has_est {
+            data.extend_from_slice(&offset.to_be_bytes());
+            data.extend_from_slice(&(est.len() as i32).to_be_bytes());
+        }
+        for table in [path, vfs, cft, est] {
+            data.extend(table);
+        }
+        data
+    }
+
+    /// Returns a CFT record tail of a content key, optionally followed by a blob size.
+    fn with_content_key(index: u8, blob_size: Option<u32>) -> Vec<u8> {
+        let mut record = vec![0xC0 | index; 16];
+        record.extend(blob_size.into_iter().flat_map(u32::to_be_bytes));
+        record
+    }
+
+    fn parse(data: Vec<u8>) -> TVFSRootHandler {
+        TVFSRootHandler::new(&mut Cursor::new(data)).unwrap()
+    }
+
+    #[test]
+    fn reads_spans_from_cft_records() {
+        let handler = parse(manifest(0, &[vec![], vec![]], &[]));
+        assert_eq!(handler.file_entries.len(), 2);
+        let span = &handler.file_entries["file1"].spans[0];
+        assert_eq!(span.encoding_key, [1; 9]);
+        assert_eq!((span.offset, span.size), (2, Some(10)));
+        assert_eq!(span.content_key, None);
+        assert_eq!(span.blob_size, None);
+    }
+
+    #[test]
+    fn reads_blob_sizes_when_every_record_holds_one() {
+        let records = (0..3)
+            .map(|index| with_content_key(index, Some(100 + index as u32)))
+            .collect::<Vec<_>>();
+        let handler = parse(manifest(TVFSHeader::FLAG_INCLUDE_CKEY, &records, &[]));
+        assert!(handler.header.has_blob_size());
+        for index in 0..3u8 {
+            let span = &handler.file_entries[&format!("file{index}")].spans[0];
+            assert_eq!(span.content_key, Some(vec![0xC0 | index; 16]));
+            assert_eq!(span.blob_size, Some(100 + index as u64));
+        }
+    }
+
+    #[test]
+    fn reads_cft_records_without_blob_sizes() {
+        // Several records, so each but the last is followed by another record rather than
+        // the end of the table
+        let records = (0..3)
+            .map(|index| with_content_key(index, None))
+            .collect::<Vec<_>>();
+        let handler = parse(manifest(TVFSHeader::FLAG_INCLUDE_CKEY, &records, &[]));
+        assert!(!handler.header.has_blob_size());
+        for index in 0..3u8 {
+            let span = &handler.file_entries[&format!("file{index}")].spans[0];
+            assert_eq!(span.content_key, Some(vec![0xC0 | index; 16]));
+            assert_eq!(span.blob_size, None);
+        }
+    }
+
+    #[test]
+    fn reads_especs_of_cft_records() {
+        let records = (0..2)
+            .map(|index| {
+                let mut record = vec![1 - index];
+                record.extend(with_content_key(index, Some(14)));
+                record
+            })
+            .collect::<Vec<_>>();
+        let flags = TVFSHeader::FLAG_INCLUDE_CKEY | TVFSHeader::FLAG_WRITE_SUPPORT;
+        let handler = parse(manifest(flags, &records, &["n", "z"]));
+        assert_eq!(handler.especs, ["n", "z"]);
+        let span = &handler.file_entries["file0"].spans[0];
+        assert_eq!(span.espec.as_deref(), Some("z"));
+        assert_eq!(span.content_key, Some(vec![0xC0; 16]));
+        assert_eq!(span.blob_size, Some(14));
+        let span = &handler.file_entries["file1"].spans[0];
+        assert_eq!(span.espec.as_deref(), Some("n"));
+    }
+}
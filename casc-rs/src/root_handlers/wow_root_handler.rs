@@ -0,0 +1,530 @@
+use crate::casc_encoding_table::CascEncodingTable;
+use crate::entry::Entry;
+use crate::error::CascError;
+use crate::listfile::Listfile;
+use crate::span_info::SpanInfo;
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Seek, SeekFrom};
+
+/// Content flags attached to each block of a World of Warcraft root.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WowContentFlags(pub u32);
+
+impl WowContentFlags {
+    pub const NONE: Self = Self(0x0000_0000);
+    pub const LOAD_ON_WINDOWS: Self = Self(0x0000_0008);
+    pub const LOAD_ON_MAC: Self = Self(0x0000_0010);
+    pub const LOW_VIOLENCE: Self = Self(0x0000_0080);
+    pub const DO_NOT_LOAD: Self = Self(0x0000_0100);
+    pub const UPDATE_PLUGIN: Self = Self(0x0000_0800);
+    pub const ENCRYPTED: Self = Self(0x0800_0000);
+    pub const NO_NAME_HASH: Self = Self(0x1000_0000);
+    pub const UNCOMMON_RESOLUTION: Self = Self(0x2000_0000);
+    pub const BUNDLE: Self = Self(0x4000_0000);
+    pub const NO_COMPRESSION: Self = Self(0x8000_0000);
+
+    /// Checks if the flag is set.
+    pub fn has_flag(self, other: Self) -> bool {
+        (self.0 & other.0) == other.0
+    }
+}
+
+/// Locale flags attached to each block of a World of Warcraft root.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WowLocaleFlags(pub u32);
+
+impl WowLocaleFlags {
+    pub const EN_US: Self = Self(0x0000_0002);
+    pub const KO_KR: Self = Self(0x0000_0004);
+    pub const FR_FR: Self = Self(0x0000_0010);
+    pub const DE_DE: Self = Self(0x0000_0020);
+    pub const ZH_CN: Self = Self(0x0000_0040);
+    pub const ES_ES: Self = Self(0x0000_0080);
+    pub const ZH_TW: Self = Self(0x0000_0100);
+    pub const EN_GB: Self = Self(0x0000_0200);
+    pub const EN_CN: Self = Self(0x0000_0400);
+    pub const EN_TW: Self = Self(0x0000_0800);
+    pub const ES_MX: Self = Self(0x0000_1000);
+    pub const RU_RU: Self = Self(0x0000_2000);
+    pub const PT_BR: Self = Self(0x0000_4000);
+    pub const IT_IT: Self = Self(0x0000_8000);
+    pub const PT_PT: Self = Self(0x0001_0000);
+    pub const ALL: Self = Self(0xFFFF_FFFF);
+
+    /// Checks if any of the given locales are set.
+    pub fn has_any(self, other: Self) -> bool {
+        (self.0 & other.0) != 0
+    }
+}
+
+/// Represents a single record of a World of Warcraft root.
+#[derive(Debug, Clone)]
+pub struct WowRootRecord {
+    /// The FileDataID of the file.
+    pub file_data_id: u32,
+    /// The content key of the file.
+    pub content_key: [u8; 16],
+    /// The Jenkins96 hash of the file name, if the root carries one.
+    pub name_hash: Option<u64>,
+    /// The content flags of the block containing the record.
+    pub content_flags: WowContentFlags,
+    /// The locale flags of the block containing the record.
+    pub locale_flags: WowLocaleFlags,
+}
+
+/// Handles the World of Warcraft root structure, in both the legacy and the `MFST` formats.
+///
+/// Files are identified by FileDataID, and optionally by the Jenkins96 hash of their name.
+/// When a FileDataID appears in several blocks (one per locale or content variant), the
+/// variant matching the preferred locale is kept.
+#[derive(Debug)]
+pub struct WowRootHandler {
+    pub file_entries: HashMap<String, Entry>,
+    /// Maps FileDataIDs to their name in `file_entries`.
+    pub file_data_ids: HashMap<u32, String>,
+    /// Maps Jenkins96 name hashes to FileDataIDs.
+    pub name_hashes: HashMap<u64, u32>,
+    /// The records selected for each FileDataID.
+    pub records: HashMap<u32, WowRootRecord>,
+}
+
+impl WowRootHandler {
+    /// The magic of the `MFST` root format (`TSFM` on disk).
+    pub const MFST_MAGIC: u32 = 0x4D465354;
+    /// The locale preferred when a file exists in several locales.
+    const PREFERRED_LOCALE: WowLocaleFlags = WowLocaleFlags::EN_US;
+
+    pub fn new<R: Read + Seek>(
+        stream: &mut R,
+        encoding: &CascEncodingTable,
+    ) -> Result<Self, CascError> {
+        stream.seek(SeekFrom::Start(0))?;
+        let mut data = Vec::new();
+        stream.read_to_end(&mut data)?;
+        let end = data.len() as u64;
+        let mut reader = Cursor::new(data);
+
+        let mut handler = WowRootHandler {
+            file_entries: HashMap::new(),
+            file_data_ids: HashMap::new(),
+            name_hashes: HashMap::new(),
+            records: HashMap::new(),
+        };
+
+        let magic = reader.read_u32::<LittleEndian>()?;
+        let (is_mfst, version, total_file_count, named_file_count) = if magic == Self::MFST_MAGIC {
+            let field1 = reader.read_u32::<LittleEndian>()?;
+            let field2 = reader.read_u32::<LittleEndian>()?;
+            // Newer roots carry a header size and version before the file counts
+            if field1 == 0x18 && field2 < 0x100 {
+                let total = reader.read_u32::<LittleEndian>()?;
+                let named = reader.read_u32::<LittleEndian>()?;
+                reader.seek(SeekFrom::Start(field1 as u64))?;
+                (true, field2, total, named)
+            } else {
+                (true, 1, field1, field2)
+            }
+        } else {
+            reader.set_position(0);
+            (false, 0, 0, 0)
+        };
+        let allow_no_name_hash = is_mfst && total_file_count != named_file_count;
+
+        while reader.position() < end {
+            let record_count = reader.read_u32::<LittleEndian>()?;
+            let (content_flags, locale_flags) = if version >= 2 {
+                let locale_flags = reader.read_u32::<LittleEndian>()?;
+                let flags1 = reader.read_u32::<LittleEndian>()?;
+                let flags2 = reader.read_u32::<LittleEndian>()?;
+                let flags3 = reader.read_u8()? as u32;
+                (flags1 | flags2 | (flags3 << 17), locale_flags)
+            } else {
+                let content_flags = reader.read_u32::<LittleEndian>()?;
+                let locale_flags = reader.read_u32::<LittleEndian>()?;
+                (content_flags, locale_flags)
+            };
+            let content_flags = WowContentFlags(content_flags);
+            let locale_flags = WowLocaleFlags(locale_flags);
+
+            let has_name_hash =
+                !(allow_no_name_hash && content_flags.has_flag(WowContentFlags::NO_NAME_HASH));
+            let record_size = if is_mfst && !has_name_hash {
+                4 + 16
+            } else {
+                4 + 16 + 8
+            };
+            if locale_flags.0 == 0 || reader.position() + record_count as u64 * record_size > end {
+                return Err(CascError::InvalidData(
+                    "Invalid World of Warcraft root block".to_string(),
+                ));
+            }
+
+            let mut file_data_ids = Vec::with_capacity(record_count as usize);
+            let mut file_data_id = -1i64;
+            for _ in 0..record_count {
+                let delta = reader.read_i32::<LittleEndian>()?;
+                file_data_id = file_data_id + 1 + delta as i64;
+                file_data_ids.push(file_data_id as u32);
+            }
+
+            let mut content_keys = Vec::with_capacity(record_count as usize);
+            let mut name_hashes = Vec::with_capacity(record_count as usize);
+            if is_mfst {
+                // Content keys and name hashes are stored in separate arrays
+                for _ in 0..record_count {
+                    let mut content_key = [0u8; 16];
+                    reader.read_exact(&mut content_key)?;
+                    content_keys.push(content_key);
+                }
+                if has_name_hash {
+                    for _ in 0..record_count {
+                        name_hashes.push(reader.read_u64::<LittleEndian>()?);
+                    }
+                }
+            } else {
+                // Content keys and name hashes are interleaved
+                for _ in 0..record_count {
+                    let mut content_key = [0u8; 16];
+                    reader.read_exact(&mut content_key)?;
+                    content_keys.push(content_key);
+                    name_hashes.push(reader.read_u64::<LittleEndian>()?);
+                }
+            }
+
+            for (i, (file_data_id, content_key)) in
+                file_data_ids.into_iter().zip(content_keys).enumerate()
+            {
+                handler.add_record(WowRootRecord {
+                    file_data_id,
+                    content_key,
+                    name_hash: name_hashes.get(i).copied(),
+                    content_flags,
+                    locale_flags,
+                });
+            }
+        }
+
+        handler.build_entries(encoding);
+        Ok(handler)
+    }
+
+    /// Returns whether the data looks like a legacy root, which has no magic to recognise it
+    /// by: blocks that each list at least one record for some locale, with FileDataIDs in
+    /// increasing order, and that end exactly at the end of the data.
+    pub fn is_legacy_root(data: &[u8]) -> bool {
+        !data.is_empty() && Self::check_legacy_blocks(data).is_some()
+    }
+
+    /// Walks the blocks of a legacy root, returning `None` at the first that is not sound.
+    fn check_legacy_blocks(data: &[u8]) -> Option<()> {
+        let end = data.len() as u64;
+        let mut reader = Cursor::new(data);
+        while reader.position() < end {
+            let record_count = reader.read_u32::<LittleEndian>().ok()? as u64;
+            let _content_flags = reader.read_u32::<LittleEndian>().ok()?;
+            let locale_flags = reader.read_u32::<LittleEndian>().ok()?;
+            let block_end = reader.position() + record_count * (4 + 16 + 8);
+            if record_count == 0 || locale_flags == 0 || block_end > end {
+                return None;
+            }
+            // FileDataIDs are stored as the distance from the previous one, less one
+            for _ in 0..record_count {
+                if reader.read_i32::<LittleEndian>().ok()? < 0 {
+                    return None;
+                }
+            }
+            reader.set_position(block_end);
+        }
+        Some(())
+    }
+
+    /// Returns the name given to files that have no known path.
+    pub fn unknown_file_name(file_data_id: u32) -> String {
+        format!("unknown/{file_data_id}.dat")
+    }
+
+    /// Adds a record, replacing any previous record of the same FileDataID that is a worse match.
+    fn add_record(&mut self, record: WowRootRecord) {
+        if let Some(existing) = self.records.get(&record.file_data_id) {
+            if Self::record_score(existing) >= Self::record_score(&record) {
+                return;
+            }
+        }
+        if let Some(name_hash) = record.name_hash {
+            self.name_hashes.insert(name_hash, record.file_data_id);
+        }
+        self.records.insert(record.file_data_id, record);
+    }
+
+    /// Ranks a record against the preferred locale and content variant.
+    fn record_score(record: &WowRootRecord) -> u32 {
+        let mut score = 0;
+        if record.locale_flags.has_any(Self::PREFERRED_LOCALE) {
+            score += 2;
+        }
+        if !record.content_flags.has_flag(WowContentFlags::LOW_VIOLENCE) {
+            score += 1;
+        }
+        score
+    }
+
+    /// Builds the file entries from the selected records, resolving content keys through ENCODING.
+    fn build_entries(&mut self, encoding: &CascEncodingTable) {
+        for record in self.records.values() {
+            let Some(encoding_entry) = encoding.get_content_entry(&record.content_key) else {
+                continue;
+            };
+            let Some(encoding_key) = encoding_entry.encoding_keys.first() else {
+                continue;
+            };
+            let name = Self::unknown_file_name(record.file_data_id);
+            let span = SpanInfo::new_with_content_key(
+                record.content_key.to_vec(),
+                encoding_key.clone(),
+                encoding_entry.content_size as usize,
+            );
+            self.file_data_ids.insert(record.file_data_id, name.clone());
+            self.file_entries.insert(
+                name.clone(),
+                Entry::new_with_file_data_id(name, vec![span], record.file_data_id),
+            );
+        }
+    }
+
+    /// Names the entries using the given listfile, by FileDataID or by name hash.
+    ///
+    /// Entries without a match keep their current name, as do entries whose path is already
+    /// taken, such as when the listfile maps two FileDataIDs to the same path. Entries are
+    /// named in FileDataID order, so the lowest FileDataID gets a shared path.
+    /// Returns the number of entries renamed.
+    pub fn apply_listfile(&mut self, listfile: &Listfile) -> usize {
+        let mut file_data_ids = self.records.keys().copied().collect::<Vec<_>>();
+        file_data_ids.sort_unstable();
+
+        let mut renamed = 0;
+        for file_data_id in file_data_ids {
+            let record = &self.records[&file_data_id];
+            let Some(path) = listfile.get_by_id(record.file_data_id).or_else(|| {
+                record
+                    .name_hash
+                    .and_then(|name_hash| listfile.get_by_name_hash(name_hash))
+            }) else {
+                continue;
+            };
+            let Some(old_name) = self.file_data_ids.get_mut(&record.file_data_id) else {
+                continue;
+            };
+            if old_name == path || self.file_entries.contains_key(path) {
+                continue;
+            }
+            if let Some(mut entry) = self.file_entries.remove(old_name.as_str()) {
+                entry.name = path.to_string();
+                self.file_entries.insert(entry.name.clone(), entry);
+                *old_name = path.to_string();
+                renamed += 1;
+            }
+        }
+        renamed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The content key and encoding key of the file with the given FileDataID.
+    fn keys(file_data_id: u32) -> ([u8; 16], [u8; 16]) {
+        ([file_data_id as u8; 16], [file_data_id as u8 | 0x80; 16])
+    }
+
+    fn encoding(file_data_ids: &[u32]) -> CascEncodingTable {
+        let entries = file_data_ids
+            .iter()
+            .map(|&id| (keys(id).0, keys(id).1, id as u64 * 10))
+            .collect::<Vec<_>>();
+        CascEncodingTable::from_entries(&entries)
+    }
+
+    fn deltas(file_data_ids: &[u32]) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut previous = -1i64;
+        for &id in file_data_ids {
+            data.extend_from_slice(&((id as i64 - previous - 1) as i32).to_le_bytes());
+            previous = id as i64;
+        }
+        data
+    }
+
+    /// Builds an `MFST` block, with content keys and name hashes in separate arrays.
+    fn mfst_block(file_data_ids: &[u32], name_hashes: bool) -> Vec<u8> {
+        let mut data = deltas(file_data_ids);
+        for &id in file_data_ids {
+            data.extend_from_slice(&keys(id).0);
+        }
+        if name_hashes {
+            for &id in file_data_ids {
+                data.extend_from_slice(&(0x1000 + id as u64).to_le_bytes());
+            }
+        }
+        data
+    }
+
+    fn open(data: Vec<u8>, file_data_ids: &[u32]) -> WowRootHandler {
+        WowRootHandler::new(&mut Cursor::new(data), &encoding(file_data_ids)).unwrap()
+    }
+
+    fn assert_listed(handler: &WowRootHandler, file_data_id: u32) {
+        let name = &handler.file_data_ids[&file_data_id];
+        assert_eq!(name, &WowRootHandler::unknown_file_name(file_data_id));
+        let span = &handler.file_entries[name].spans[0];
+        assert_eq!(span.content_key.as_deref(), Some(&keys(file_data_id).0[..]));
+        assert_eq!(span.encoding_key, keys(file_data_id).1);
+        assert_eq!(span.size, Some(file_data_id as usize * 10));
+    }
+
+    /// Builds a legacy root, with no header, and content keys interleaved with name hashes.
+    fn legacy_root() -> Vec<u8> {
+        let mut data = Vec::new();
+        for (ids, locale) in [
+            ([3, 7], WowLocaleFlags::ALL),
+            ([7, 9], WowLocaleFlags::DE_DE),
+        ] {
+            data.extend_from_slice(&(ids.len() as u32).to_le_bytes());
+            data.extend_from_slice(&0u32.to_le_bytes());
+            data.extend_from_slice(&locale.0.to_le_bytes());
+            data.extend(deltas(&ids));
+            for id in ids {
+                data.extend_from_slice(&keys(id).0);
+                data.extend_from_slice(&(0x1000 + id as u64).to_le_bytes());
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn parses_legacy_root() {
+        let handler = open(legacy_root(), &[3, 7, 9]);
+        assert_eq!(handler.file_entries.len(), 3);
+        for id in [3, 7, 9] {
+            assert_listed(&handler, id);
+            assert_eq!(handler.name_hashes[&(0x1000 + id as u64)], id);
+        }
+        // The block that includes the preferred locale wins
+        assert_eq!(handler.records[&7].locale_flags, WowLocaleFlags::ALL);
+    }
+
+    #[test]
+    fn detects_legacy_roots() {
+        let data = legacy_root();
+        assert!(WowRootHandler::is_legacy_root(&data));
+        assert!(!WowRootHandler::is_legacy_root(&[]));
+        assert!(!WowRootHandler::is_legacy_root(&data[..data.len() - 1]));
+        assert!(!WowRootHandler::is_legacy_root(
+            b"\x01\x02\x03\x04 not a root"
+        ));
+
+        // Blocks without records or locales, and FileDataIDs out of order
+        for (offset, value) in [(0, 0u32), (8, 0), (16, -5i32 as u32)] {
+            let mut data = legacy_root();
+            data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+            assert!(!WowRootHandler::is_legacy_root(&data), "{offset}");
+        }
+    }
+
+    #[test]
+    fn parses_mfst_version_1_root() {
+        let mut data = b"TSFM".to_vec();
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(&WowContentFlags::LOAD_ON_WINDOWS.0.to_le_bytes());
+        data.extend_from_slice(&WowLocaleFlags::EN_US.0.to_le_bytes());
+        data.extend(mfst_block(&[10, 12], true));
+
+        let handler = open(data, &[10, 12]);
+        assert_eq!(handler.file_entries.len(), 2);
+        assert_listed(&handler, 10);
+        assert_listed(&handler, 12);
+        assert_eq!(handler.name_hashes[&0x100C], 12);
+        assert_eq!(
+            handler.records[&10].content_flags,
+            WowContentFlags::LOAD_ON_WINDOWS
+        );
+    }
+
+    #[test]
+    fn parses_mfst_version_2_root() {
+        // Header size and version, then the file counts, padded to the header size
+        let mut data = b"TSFM".to_vec();
+        for value in [0x18u32, 2, 3, 3, 0] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.extend_from_slice(&3u32.to_le_bytes());
+        data.extend_from_slice(&WowLocaleFlags::EN_US.0.to_le_bytes());
+        data.extend_from_slice(&0x08u32.to_le_bytes());
+        data.extend_from_slice(&0x80u32.to_le_bytes());
+        data.push(0x01);
+        data.extend(mfst_block(&[20, 21, 25], true));
+
+        let handler = open(data, &[20, 21, 25]);
+        assert_eq!(handler.file_entries.len(), 3);
+        for id in [20, 21, 25] {
+            assert_listed(&handler, id);
+        }
+        // The content flags are merged, with the last byte above bit 17
+        assert_eq!(handler.records[&25].content_flags.0, 0x08 | 0x80 | 0x2_0000);
+        assert_eq!(handler.records[&25].locale_flags, WowLocaleFlags::EN_US);
+    }
+
+    #[test]
+    fn parses_mfst_blocks_without_name_hashes() {
+        // Fewer named files than files allows blocks without name hashes
+        let mut data = b"TSFM".to_vec();
+        data.extend_from_slice(&3u32.to_le_bytes());
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(&WowContentFlags::NO_NAME_HASH.0.to_le_bytes());
+        data.extend_from_slice(&WowLocaleFlags::ALL.0.to_le_bytes());
+        data.extend(mfst_block(&[30, 31], false));
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&WowLocaleFlags::ALL.0.to_le_bytes());
+        data.extend(mfst_block(&[40], true));
+
+        let handler = open(data, &[30, 31, 40]);
+        assert_eq!(handler.file_entries.len(), 3);
+        for id in [30, 31, 40] {
+            assert_listed(&handler, id);
+        }
+        assert_eq!(handler.records[&30].name_hash, None);
+        assert_eq!(handler.records[&40].name_hash, Some(0x1028));
+        assert_eq!(handler.name_hashes.len(), 1);
+    }
+
+    #[test]
+    fn apply_listfile_keeps_duplicate_paths_under_their_ids() {
+        let mut data = b"TSFM".to_vec();
+        data.extend_from_slice(&3u32.to_le_bytes());
+        data.extend_from_slice(&3u32.to_le_bytes());
+        data.extend_from_slice(&3u32.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&WowLocaleFlags::ALL.0.to_le_bytes());
+        data.extend(mfst_block(&[50, 51, 52], true));
+        let mut handler = open(data, &[50, 51, 52]);
+
+        // Two FileDataIDs share a path, and another path is the name of an existing entry
+        let listfile = Listfile::from_reader(
+            "51;world/shared.m2\n50;world/shared.m2\n52;unknown/51.dat\n".as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(handler.apply_listfile(&listfile), 1);
+        assert_eq!(handler.file_data_ids[&50], "world/shared.m2");
+        assert_eq!(handler.file_data_ids[&51], "unknown/51.dat");
+        assert_eq!(handler.file_data_ids[&52], "unknown/52.dat");
+        assert_eq!(handler.file_entries.len(), 3);
+        for (id, name) in &handler.file_data_ids {
+            assert_eq!(handler.file_entries[name].file_data_id, Some(*id));
+        }
+    }
+}
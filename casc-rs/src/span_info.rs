@@ -1,19 +1,27 @@
 /// This module defines the `SpanInfo` struct, which represents information about a span of data
 /// within a CASC archive, including keys, size, and their base64 representations.
+use crate::casc_key_mapping_table::encoding_key_to_base64;
 use base64::prelude::*;
 
 /// Represents information about a span of data in a CASC archive.
 ///
 /// A `SpanInfo` contains the binary and base64 representations of the content and encoding keys,
-/// as well as the size of the span if known.
-#[derive(Debug)]
+/// as well as the range of the decoded blob the span references.
+#[derive(Debug, Clone)]
 pub(crate) struct SpanInfo {
     /// The binary content key, if present.
     pub(crate) content_key: Option<Vec<u8>>,
     /// The binary encoding key.
     pub(crate) encoding_key: Vec<u8>,
-    /// The size of the span, if known.
+    /// The offset within the decoded blob at which the span begins.
+    pub(crate) offset: u64,
+    /// The decoded size of the span, if known. Without a size, the span runs to the end of
+    /// the blob.
     pub(crate) size: Option<usize>,
+    /// The decoded size of the whole blob the span references, if recorded by the root.
+    pub(crate) blob_size: Option<u64>,
+    /// The ESpec string describing how the span is encoded, if recorded by the root.
+    pub(crate) espec: Option<String>,
     /// The base64-encoded content key, if present.
     pub(crate) base64_content_key: Option<String>,
     /// The base64-encoded encoding key.
@@ -21,12 +29,15 @@ pub(crate) struct SpanInfo {
 }
 
 impl SpanInfo {
-    pub(crate) fn new_with_encoding_key(e_key: Vec<u8>) -> Self {
-        let base64_encoding_key = BASE64_STANDARD.encode(&e_key);
+    pub(crate) fn new_with_encoding_key(e_key: Vec<u8>, size: Option<usize>) -> Self {
+        let base64_encoding_key = encoding_key_to_base64(&e_key);
         Self {
             content_key: None,
             encoding_key: e_key,
-            size: None,
+            offset: 0,
+            size,
+            blob_size: None,
+            espec: None,
             base64_content_key: None,
             base64_encoding_key,
         }
@@ -34,11 +45,14 @@ impl SpanInfo {
 
     pub(crate) fn new_with_content_key(c_key: Vec<u8>, e_key: Vec<u8>, size: usize) -> Self {
         let base64_content_key = BASE64_STANDARD.encode(&c_key);
-        let base64_encoding_key = BASE64_STANDARD.encode(&e_key);
+        let base64_encoding_key = encoding_key_to_base64(&e_key);
         Self {
             content_key: Some(c_key),
             encoding_key: e_key,
+            offset: 0,
             size: Some(size),
+            blob_size: None,
+            espec: None,
             base64_content_key: Some(base64_content_key),
             base64_encoding_key,
         }
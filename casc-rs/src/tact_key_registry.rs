@@ -0,0 +1,98 @@
+use crate::error::CascError;
+/// Module for the registry of TACT keys used to decrypt encrypted BLTE frames.
+///
+/// Encrypted frames name the key they were encrypted with by a 64-bit key name. The keys
+/// themselves are not part of the storage and have to be supplied by the caller.
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+/// A registry of TACT decryption keys, keyed by their 64-bit key name.
+///
+/// Keys can be added individually or loaded from the common `keyname keyhex` text format,
+/// with one key per line:
+///
+/// ```text
+/// FA505078126ACB3E BDC51862ABED79B2DE48C8E7E66C6200
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct TactKeyRegistry {
+    keys: HashMap<u64, [u8; 16]>,
+}
+
+impl TactKeyRegistry {
+    /// Creates a new, empty `TactKeyRegistry`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads keys from the specified file.
+    ///
+    /// # Arguments
+    ///
+    /// * `file_name` - The path to the key file.
+    pub fn from_file<P: AsRef<Path>>(file_name: P) -> Result<Self, CascError> {
+        let file = File::open(file_name)?;
+        Self::from_reader(file)
+    }
+
+    /// Loads keys from a reader (e.g., File, BufReader, etc.)
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self, CascError> {
+        let mut registry = Self::new();
+        registry.load(reader)?;
+        Ok(registry)
+    }
+
+    /// Loads keys in the `keyname keyhex` text format from a reader into this registry.
+    ///
+    /// Empty lines and lines starting with `#` or `//` are ignored. Fields may be separated by
+    /// whitespace, `;` or `,`, and any fields after the key are ignored.
+    pub fn load<R: Read>(&mut self, reader: R) -> Result<(), CascError> {
+        for line in BufReader::new(reader).lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with("//") {
+                continue;
+            }
+
+            let mut fields = line
+                .split(|c: char| c.is_whitespace() || c == ';' || c == ',')
+                .filter(|s| !s.is_empty());
+            let (Some(name), Some(key)) = (fields.next(), fields.next()) else {
+                return Err(CascError::InvalidData(format!(
+                    "Invalid TACT key line: {line}"
+                )));
+            };
+
+            let name = u64::from_str_radix(name, 16)
+                .map_err(|_| CascError::InvalidData(format!("Invalid TACT key name: {name}")))?;
+            let key = hex::decode(key)
+                .ok()
+                .and_then(|key| <[u8; 16]>::try_from(key).ok())
+                .ok_or_else(|| CascError::InvalidData(format!("Invalid TACT key: {key}")))?;
+            self.add_key(name, key);
+        }
+        Ok(())
+    }
+
+    /// Adds a key to the registry, replacing any existing key with the same name.
+    pub fn add_key(&mut self, name: u64, key: [u8; 16]) {
+        self.keys.insert(name, key);
+    }
+
+    /// Returns the key with the given name, if present.
+    pub fn get_key(&self, name: u64) -> Option<&[u8; 16]> {
+        self.keys.get(&name)
+    }
+
+    /// Returns the number of keys in the registry.
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Returns whether the registry contains no keys.
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
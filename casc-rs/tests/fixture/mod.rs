@@ -0,0 +1,450 @@
+//! Builds small CASC storages on disk for the tests.
+//!
+//! A storage is made of a `.build.info`, a build config, a single `data.000` and a single
+//! `.idx` key mapping table. Blobs are BLTE encoded, and can be referenced from the config by
+//! their content and encoding keys.
+#![allow(dead_code)]
+
+use casc_rs::casc_open_options::CascOpenOptions;
+use casc_rs::casc_storage::CascStorage;
+use casc_rs::error::CascError;
+use casc_rs::hash::hashlittle2;
+use flate2::{write::ZlibEncoder, Compression};
+use md5::{Digest, Md5};
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+/// The build key the build config is stored under.
+const BUILD_KEY: &str = "00112233445566778899aabbccddeeff";
+/// The size of the span header that precedes each blob in a data file.
+pub const SPAN_HEADER_SIZE: u32 = 30;
+
+/// Returns the MD5 hash of the data.
+pub fn md5(data: &[u8]) -> [u8; 16] {
+    Md5::digest(data).into()
+}
+
+/// Encodes a single BLTE frame with the given mode, `N` (raw) or `Z` (zlib).
+pub fn encode_frame(mode: u8, content: &[u8]) -> Vec<u8> {
+    let mut frame = vec![mode];
+    match mode {
+        b'N' => frame.extend_from_slice(content),
+        b'Z' => {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(content).unwrap();
+            frame.extend(encoder.finish().unwrap());
+        }
+        _ => panic!("Unsupported frame mode: {mode}"),
+    }
+    frame
+}
+
+/// Builds a BLTE blob from already encoded frames, each paired with its decoded size.
+pub fn blte_from_encoded(frames: &[(Vec<u8>, usize)]) -> Vec<u8> {
+    let mut table = Vec::new();
+    for (encoded, content_size) in frames {
+        table.extend_from_slice(&(encoded.len() as u32).to_be_bytes());
+        table.extend_from_slice(&(*content_size as u32).to_be_bytes());
+        table.extend_from_slice(&md5(encoded));
+    }
+    let mut blob = b"BLTE".to_vec();
+    blob.extend_from_slice(&(12 + table.len() as u32).to_be_bytes());
+    blob.push(0x0F);
+    blob.extend_from_slice(&(frames.len() as u32).to_be_bytes()[1..]);
+    blob.extend(table);
+    for (encoded, _) in frames {
+        blob.extend_from_slice(encoded);
+    }
+    blob
+}
+
+/// Builds a BLTE blob with one frame per chunk, each encoded with the given mode.
+pub fn blte(mode: u8, chunks: &[&[u8]]) -> Vec<u8> {
+    let frames = chunks
+        .iter()
+        .map(|chunk| (encode_frame(mode, chunk), chunk.len()))
+        .collect::<Vec<_>>();
+    blte_from_encoded(&frames)
+}
+
+/// An entry of a key mapping table.
+#[derive(Debug, Clone)]
+pub struct IndexEntry {
+    pub encoding_key: [u8; 16],
+    pub offset: u64,
+    pub size: u32,
+}
+
+/// Serializes a key mapping table with valid header and table hashes.
+pub fn idx_bytes(bucket: u8, entries: &[IndexEntry]) -> Vec<u8> {
+    let mut header = vec![7, 0, bucket, 0, 4, 5, 9, 30];
+    header.extend_from_slice(&0x40_0000_0000u64.to_le_bytes());
+    let mut data = (header.len() as u32).to_le_bytes().to_vec();
+    data.extend_from_slice(&hashlittle2(&header, 0, 0).0.to_le_bytes());
+    data.extend(header);
+    data.resize((data.len() + 0x17) & !0xF, 0);
+
+    let mut table = Vec::new();
+    let (mut hash_high, mut hash_low) = (0, 0);
+    for entry in entries {
+        let mut record = entry.encoding_key[..9].to_vec();
+        record.extend_from_slice(&entry.offset.to_be_bytes()[3..]);
+        record.extend_from_slice(&entry.size.to_le_bytes());
+        (hash_high, hash_low) = hashlittle2(&record, hash_high, hash_low);
+        table.extend(record);
+    }
+    data.extend_from_slice(&(table.len() as u32).to_le_bytes());
+    data.extend_from_slice(&hash_high.to_le_bytes());
+    data.extend(table);
+    data
+}
+
+/// Serializes a version 1 ENCODING file with a single page of each kind.
+///
+/// Each entry is a content key, its encoding key, and the decoded and encoded sizes.
+pub fn encoding_file(entries: &[([u8; 16], [u8; 16], u64, u64)], especs: &[&str]) -> Vec<u8> {
+    let mut espec_block = Vec::new();
+    for espec in especs {
+        espec_block.extend_from_slice(espec.as_bytes());
+        espec_block.push(0);
+    }
+
+    let mut data = vec![b'E', b'N', 1, 16, 16];
+    data.extend_from_slice(&1u16.to_be_bytes());
+    data.extend_from_slice(&1u16.to_be_bytes());
+    data.extend_from_slice(&1u32.to_be_bytes());
+    data.extend_from_slice(&1u32.to_be_bytes());
+    data.push(0);
+    data.extend_from_slice(&(espec_block.len() as u32).to_be_bytes());
+    data.extend(espec_block);
+
+    let mut page = Vec::new();
+    for (content_key, encoding_key, content_size, _) in entries {
+        page.push(1);
+        page.extend_from_slice(&content_size.to_be_bytes()[3..]);
+        page.extend_from_slice(content_key);
+        page.extend_from_slice(encoding_key);
+    }
+    page.resize(1024, 0);
+    data.extend_from_slice(&entries[0].0);
+    data.extend_from_slice(&md5(&page));
+    data.extend(page);
+
+    let mut sorted = entries.to_vec();
+    sorted.sort_by_key(|entry| entry.1);
+    let mut page = Vec::new();
+    for (_, encoding_key, _, encoded_size) in &sorted {
+        page.extend_from_slice(encoding_key);
+        page.extend_from_slice(&0u32.to_be_bytes());
+        page.extend_from_slice(&encoded_size.to_be_bytes()[3..]);
+    }
+    page.resize(1024, 0);
+    data.extend_from_slice(&sorted[0].1);
+    data.extend_from_slice(&md5(&page));
+    data.extend(page);
+    data
+}
+
+/// A span of a file in a TVFS manifest.
+#[derive(Debug, Clone)]
+pub struct TvfsSpan {
+    pub encoding_key: [u8; 16],
+    pub encoded_size: u32,
+    pub content_key: [u8; 16],
+    /// The decoded size of the whole blob, stored in the CFT record.
+    pub blob_size: u32,
+    /// The range of the decoded blob the span references.
+    pub offset: u32,
+    pub size: u32,
+    /// The index of the span's ESpec in the ESpec table.
+    pub espec: u8,
+}
+
+impl TvfsSpan {
+    /// A span covering the whole of a blob.
+    pub fn whole(content_key: [u8; 16], encoding_key: [u8; 16], size: u32) -> Self {
+        TvfsSpan {
+            encoding_key,
+            encoded_size: 0,
+            content_key,
+            blob_size: size,
+            offset: 0,
+            size,
+            espec: 0,
+        }
+    }
+}
+
+/// Serializes a TVFS manifest listing the given files at the top level.
+///
+/// Names may contain `\` to place files in folders. `flags` are the header flags, which
+/// select the optional CFT record fields, and the ESpec table is only written with write
+/// support. Tables are small enough for all their offsets to fit in a byte.
+pub fn tvfs_root(files: &[(&str, Vec<TvfsSpan>)], flags: i32, especs: &[&str]) -> Vec<u8> {
+    const FLAG_INCLUDE_CKEY: i32 = 1;
+    const FLAG_WRITE_SUPPORT: i32 = 2;
+
+    let mut cft = Vec::new();
+    let mut vfs = Vec::new();
+    let mut path = Vec::new();
+    for (name, spans) in files {
+        path.push(name.len() as u8);
+        path.extend_from_slice(name.as_bytes());
+        path.push(0xFF);
+        path.extend_from_slice(&(vfs.len() as u32).to_be_bytes());
+
+        vfs.push(spans.len() as u8);
+        for span in spans {
+            vfs.extend_from_slice(&span.offset.to_be_bytes());
+            vfs.extend_from_slice(&span.size.to_be_bytes());
+            vfs.push(cft.len() as u8);
+
+            cft.extend_from_slice(&span.encoding_key[..9]);
+            cft.extend_from_slice(&span.encoded_size.to_be_bytes());
+            if flags & FLAG_WRITE_SUPPORT != 0 {
+                cft.push(span.espec);
+            }
+            if flags & FLAG_INCLUDE_CKEY != 0 {
+                cft.extend_from_slice(&span.content_key);
+                cft.extend_from_slice(&span.blob_size.to_be_bytes());
+            }
+        }
+    }
+    assert!(cft.len() <= 0xFF && vfs.len() <= 0xFF);
+
+    let mut est = Vec::new();
+    for espec in especs {
+        est.extend_from_slice(espec.as_bytes());
+        est.push(0);
+    }
+
+    let header_size = if flags & FLAG_WRITE_SUPPORT != 0 {
+        0x2E
+    } else {
+        0x26
+    };
+    let path_offset = header_size;
+    let vfs_offset = path_offset + path.len() as i32;
+    let cft_offset = vfs_offset + vfs.len() as i32;
+    let est_offset = cft_offset + cft.len() as i32;
+
+    let mut data = b"TVFS".to_vec();
+    data.extend_from_slice(&[1, header_size as u8, 9, 9]);
+    for value in [
+        flags,
+        path_offset,
+        path.len() as i32,
+        vfs_offset,
+        vfs.len() as i32,
+        cft_offset,
+        cft.len() as i32,
+    ] {
+        data.extend_from_slice(&value.to_be_bytes());
+    }
+    data.extend_from_slice(&4u16.to_be_bytes());
+    if flags & FLAG_WRITE_SUPPORT != 0 {
+        data.extend_from_slice(&est_offset.to_be_bytes());
+        data.extend_from_slice(&(est.len() as i32).to_be_bytes());
+    }
+    data.extend(path);
+    data.extend(vfs);
+    data.extend(cft);
+    if flags & FLAG_WRITE_SUPPORT != 0 {
+        data.extend(est);
+    }
+    data
+}
+
+/// Builds a storage from BLTE blobs and build config entries.
+#[derive(Debug, Default)]
+pub struct StorageBuilder {
+    blobs: Vec<([u8; 16], Vec<u8>)>,
+    /// The content key, encoding key and size of each blob added by content.
+    contents: Vec<([u8; 16], [u8; 16], u64)>,
+    config: Vec<(String, String)>,
+}
+
+impl StorageBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a TVFS storage listing each file whole.
+    pub fn tvfs(files: &[(&str, &[u8])]) -> Self {
+        let mut builder = Self::new();
+        let files = files
+            .iter()
+            .map(|(name, content)| {
+                let (content_key, encoding_key) = builder.add(content);
+                let span = TvfsSpan::whole(content_key, encoding_key, content.len() as u32);
+                (*name, vec![span])
+            })
+            .collect::<Vec<_>>();
+        builder.add_tvfs_root(&files, 0, &[]);
+        builder
+    }
+
+    /// Adds the content as a single zlib frame, returning its content and encoding keys.
+    pub fn add(&mut self, content: &[u8]) -> ([u8; 16], [u8; 16]) {
+        let encoding_key = self.add_blte(blte(b'Z', &[content]));
+        let content_key = md5(content);
+        self.contents
+            .push((content_key, encoding_key, content.len() as u64));
+        (content_key, encoding_key)
+    }
+
+    /// Adds a TVFS manifest of the files, see [`tvfs_root`], as the root of the storage.
+    pub fn add_tvfs_root(
+        &mut self,
+        files: &[(&str, Vec<TvfsSpan>)],
+        flags: i32,
+        especs: &[&str],
+    ) -> &mut Self {
+        let root = self.add(&tvfs_root(files, flags, especs));
+        self.config_keys("vfs-root", root)
+    }
+
+    /// Adds the root of a storage in any other format, and an ENCODING file resolving the
+    /// content keys of the root and of everything added by [`Self::add`].
+    pub fn add_root(&mut self, root: &[u8]) -> &mut Self {
+        let root = self.add(root);
+        let entries = self
+            .contents
+            .iter()
+            .map(|&(content_key, encoding_key, size)| (content_key, encoding_key, size, 0))
+            .collect::<Vec<_>>();
+        let encoding = self.add(&encoding_file(&entries, &["z"]));
+        self.config_keys("root", root)
+            .config_keys("encoding", encoding)
+    }
+
+    /// Adds a BLTE blob, returning its encoding key.
+    pub fn add_blte(&mut self, blob: Vec<u8>) -> [u8; 16] {
+        let encoding_key = md5(&blob);
+        self.blobs.push((encoding_key, blob));
+        encoding_key
+    }
+
+    /// Sets a build config entry.
+    pub fn config(&mut self, name: &str, value: &str) -> &mut Self {
+        self.config.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Sets a build config entry to a content key and encoding key pair.
+    pub fn config_keys(&mut self, name: &str, keys: ([u8; 16], [u8; 16])) -> &mut Self {
+        let value = format!("{} {}", hex::encode(keys.0), hex::encode(keys.1));
+        self.config(name, &value)
+    }
+
+    /// Writes the storage to a fresh directory named after the test.
+    pub fn write(&self, name: &str) -> Fixture {
+        let path = std::env::temp_dir()
+            .join("casc-rs-tests")
+            .join(format!("{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        let data_dir = path.join("Data").join("data");
+        fs::create_dir_all(&data_dir).unwrap();
+
+        let config_dir = path.join("Data").join("config").join("00").join("11");
+        fs::create_dir_all(&config_dir).unwrap();
+        let config = self
+            .config
+            .iter()
+            .map(|(name, value)| format!("{name} = {value}\n"))
+            .collect::<String>();
+        fs::write(config_dir.join(BUILD_KEY), config).unwrap();
+        fs::write(
+            path.join(".build.info"),
+            format!("Build Key!HEX:16|Version!STRING:0\n{BUILD_KEY}|1.0\n"),
+        )
+        .unwrap();
+
+        let mut data = Vec::new();
+        let mut index_entries = Vec::new();
+        for (encoding_key, blob) in &self.blobs {
+            let offset = data.len() as u64;
+            let size = blob.len() as u32 + SPAN_HEADER_SIZE;
+            data.extend(encoding_key.iter().rev());
+            data.extend_from_slice(&size.to_le_bytes());
+            data.extend_from_slice(&[0; 10]);
+            data.extend_from_slice(blob);
+            index_entries.push(IndexEntry {
+                encoding_key: *encoding_key,
+                offset,
+                size,
+            });
+        }
+        fs::write(data_dir.join("data.000"), data).unwrap();
+        fs::write(
+            data_dir.join("0000000001.idx"),
+            idx_bytes(0, &index_entries),
+        )
+        .unwrap();
+
+        Fixture {
+            path,
+            index_entries,
+        }
+    }
+}
+
+/// A storage written to disk, removed again when dropped.
+#[derive(Debug)]
+pub struct Fixture {
+    pub path: PathBuf,
+    /// The entries of the storage's key mapping table, in the order the blobs were added.
+    pub index_entries: Vec<IndexEntry>,
+}
+
+impl Fixture {
+    /// Returns the directory holding the data files and key mapping tables.
+    pub fn data_dir(&self) -> PathBuf {
+        self.path.join("Data").join("data")
+    }
+
+    /// Returns the path of the storage's key mapping table.
+    pub fn idx_path(&self) -> PathBuf {
+        self.data_dir().join("0000000001.idx")
+    }
+
+    /// Returns the path of the storage's data file.
+    pub fn data_file_path(&self) -> PathBuf {
+        self.data_dir().join("data.000")
+    }
+
+    /// Opens the storage with the default options.
+    pub fn open(&self) -> Result<CascStorage, CascError> {
+        CascStorage::open(&self.path)
+    }
+
+    /// Opens the storage with the given options.
+    pub fn open_with(&self, options: CascOpenOptions) -> Result<CascStorage, CascError> {
+        CascStorage::open_with_options(&self.path, options)
+    }
+
+    /// Overwrites a single byte of the file at the given path.
+    pub fn patch(path: &Path, offset: usize, patch: impl FnOnce(u8) -> u8) {
+        let mut data = fs::read(path).unwrap();
+        data[offset] = patch(data[offset]);
+        fs::write(path, data).unwrap();
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Reads the whole of a file of the storage by name.
+pub fn read(storage: &CascStorage, name: &str) -> Vec<u8> {
+    let mut data = Vec::new();
+    storage
+        .open_file(name)
+        .unwrap()
+        .read_to_end(&mut data)
+        .unwrap();
+    data
+}
@@ -1,6 +1,9 @@
 const STORAGE: &str = r#"F:\Call of Duty Modern Warfare"#;
 const TEST_FILE: &str = "pak_base_vol021.xpak";
 use casc_rs::casc_storage::CascStorage;
+use fixture::{StorageBuilder, TvfsSpan};
+
+mod fixture;
 
 #[test]
 fn tvfs_load_file_name() {
@@ -34,3 +37,726 @@ fn tvfs_export_file() {
     let bytes_copied = copy(&mut casc_stream, &mut output).expect("Failed to write file");
     println!("Wrote {bytes_copied} bytes to xyz.dat");
 }
+
+#[test]
+fn encoding_table_lookup() {
+    use casc_rs::casc_encoding_table::CascEncodingTable;
+    use std::io::Cursor;
+
+    let content_key = [0x11u8; 16];
+    let encoding_key = [0x22u8; 16];
+    let espec = b"z\0";
+
+    // Header
+    let mut data = vec![b'E', b'N', 1, 16, 16];
+    data.extend_from_slice(&1u16.to_be_bytes());
+    data.extend_from_slice(&1u16.to_be_bytes());
+    data.extend_from_slice(&1u32.to_be_bytes());
+    data.extend_from_slice(&1u32.to_be_bytes());
+    data.push(0);
+    data.extend_from_slice(&(espec.len() as u32).to_be_bytes());
+    data.extend_from_slice(espec);
+
+    // Content key page index and page
+    data.extend_from_slice(&content_key);
+    data.extend_from_slice(&[0u8; 16]);
+    let mut page = vec![1u8, 0, 0, 0, 0x10, 0x00];
+    page.extend_from_slice(&content_key);
+    page.extend_from_slice(&encoding_key);
+    page.resize(1024, 0);
+    data.extend_from_slice(&page);
+
+    // Encoding key page index and page
+    data.extend_from_slice(&encoding_key);
+    data.extend_from_slice(&[0u8; 16]);
+    let mut page = encoding_key.to_vec();
+    page.extend_from_slice(&0u32.to_be_bytes());
+    page.extend_from_slice(&[0, 0, 0, 0x08, 0x00]);
+    page.resize(1024, 0);
+    data.extend_from_slice(&page);
+
+    let table = CascEncodingTable::new(&mut Cursor::new(data)).unwrap();
+    assert_eq!(table.len(), 1);
+
+    let entry = table.get_content_entry(&content_key).unwrap();
+    assert_eq!(entry.content_size, 0x1000);
+    assert_eq!(entry.encoding_keys, vec![encoding_key.to_vec()]);
+
+    let spec = table.get_encoding_spec(&encoding_key).unwrap();
+    assert_eq!(spec.encoded_size, 0x800);
+    assert_eq!(table.get_espec(spec.espec_index), Some("z"));
+}
+
+#[test]
+fn listfile_parse_formats() {
+    use casc_rs::listfile::Listfile;
+
+    let listfile = Listfile::from_reader(
+        "# comment\n53187;sound/music/citymusic/darnassus/darnassus intro.mp3\nInterface/Icons/Foo.blp\n"
+            .as_bytes(),
+    )
+    .unwrap();
+    assert_eq!(listfile.len(), 2);
+    assert_eq!(
+        listfile.get_by_id(53187),
+        Some("sound/music/citymusic/darnassus/darnassus intro.mp3")
+    );
+    assert_eq!(listfile.get_by_id(1), None);
+}
+
+#[test]
+fn jenkins_lookup3_vectors() {
+    use casc_rs::hash::{file_name_hash, hashlittle, hashlittle2};
+
+    assert_eq!(hashlittle(b"", 0), 0xDEADBEEF);
+    assert_eq!(hashlittle(b"Four score and seven years ago", 0), 0x17770551);
+    assert_eq!(hashlittle(b"Four score and seven years ago", 1), 0xCD628161);
+    assert_eq!(hashlittle2(b"", 0, 0), (0xDEADBEEF, 0xDEADBEEF));
+    assert_eq!(hashlittle2(b"", 0, 0xDEADBEEF), (0xBD5B7DDE, 0xDEADBEEF));
+    assert_eq!(
+        file_name_hash("Interface/Icons/Foo.blp"),
+        file_name_hash("INTERFACE\\ICONS\\FOO.BLP")
+    );
+}
+
+#[test]
+fn tact_key_registry_parse() {
+    use casc_rs::tact_key_registry::TactKeyRegistry;
+
+    let registry = TactKeyRegistry::from_reader(
+        "# keys\nFA505078126ACB3E BDC51862ABED79B2DE48C8E7E66C6200\nFF813F7D062AC0BC;AA0B5C77F088CCC2D39049BD267F066D\n"
+            .as_bytes(),
+    )
+    .unwrap();
+    assert_eq!(registry.len(), 2);
+    assert_eq!(
+        registry.get_key(0xFA505078126ACB3E).map(hex::encode),
+        Some("bdc51862abed79b2de48c8e7e66c6200".to_string())
+    );
+    assert!(TactKeyRegistry::from_reader("FA505078126ACB3E XYZ".as_bytes()).is_err());
+}
+
+#[test]
+fn verifying_reader_checks_content_key() {
+    use casc_rs::casc_verifying_reader::CascVerifyingReader;
+    use std::io::Read;
+
+    // MD5 of "hello"
+    let content_key: [u8; 16] = hex::decode("5d41402abc4b2a76b9719d911017c592")
+        .unwrap()
+        .try_into()
+        .unwrap();
+
+    let mut data = Vec::new();
+    let mut reader = CascVerifyingReader::new(&b"hello"[..], content_key);
+    assert!(reader.read_to_end(&mut data).is_ok());
+    assert_eq!(data, b"hello");
+
+    let mut reader = CascVerifyingReader::new(&b"hellO"[..], content_key);
+    assert!(reader.read_to_end(&mut Vec::new()).is_err());
+}
+
+#[test]
+fn path_normalizer_folds_separators_and_case() {
+    use casc_rs::casc_path::PathNormalizer;
+
+    let normalizer = PathNormalizer::new();
+    assert_eq!(normalizer.normalize("Data/foo.bin"), "data\\foo.bin");
+    assert_eq!(normalizer.normalize("DATA\\FOO.BIN"), "data\\foo.bin");
+    assert_eq!(normalizer.normalize("\\data//foo.bin"), "data\\foo.bin");
+
+    let normalizer = PathNormalizer::new().separator('/').case_insensitive(false);
+    assert_eq!(normalizer.normalize("DATA\\Foo.bin"), "DATA/Foo.bin");
+}
+
+#[test]
+fn file_pattern_matches_globs_and_regexes() {
+    use casc_rs::casc_file_pattern::CascFilePattern;
+
+    let pattern = CascFilePattern::glob("**/*.xpak").unwrap();
+    assert!(pattern.matches("pak_base_vol021.xpak"));
+    assert!(pattern.matches("zone\\english\\PAK_BASE.XPAK"));
+    assert!(!pattern.matches("pak_base.xpak.bak"));
+
+    let pattern = CascFilePattern::glob("locale/enUS/*").unwrap();
+    assert!(pattern.matches("Locale\\enUS\\strings.csv"));
+    assert!(!pattern.matches("locale/enUS/sub/strings.csv"));
+    assert!(!pattern
+        .case_insensitive(false)
+        .matches("Locale/enUS/strings.csv"));
+
+    let pattern = CascFilePattern::regex(r"^sound/.+\.(ogg|mp3)$").unwrap();
+    assert!(pattern.matches("Sound/Music/intro.MP3"));
+    assert!(!pattern.matches("music/sound/intro.mp3"));
+    assert!(CascFilePattern::regex("(").is_err());
+}
+
+#[test]
+fn cmf_header_reports_encryption_by_version() {
+    use casc_rs::cmf_key_provider::CmfHeader;
+
+    let header = |magic: u32| CmfHeader {
+        build_version: 50000,
+        data_count: 2,
+        entry_count: 1,
+        magic,
+        fields: vec![50000, 0, 0, 0, 2, 0, 1, magic],
+    };
+    assert_eq!(header(0x636D6613).version(), 0x13);
+    assert!(!header(0x636D6613).is_encrypted());
+    assert!(header(0x636D6614).is_encrypted());
+    assert!(header(0x636D6616).is_encrypted());
+}
+
+#[test]
+fn install_manifest_parses_entries_and_tags() {
+    use casc_rs::install_manifest::InstallManifest;
+    use std::io::Cursor;
+
+    let mut data = b"IN\x01\x10\x00\x02\x00\x00\x00\x02".to_vec();
+    data.extend_from_slice(b"Windows\0\x00\x01\x80");
+    data.extend_from_slice(b"enUS\0\x00\x03\xC0");
+    data.extend_from_slice(b"Game.exe\0");
+    data.extend_from_slice(&[0x11; 16]);
+    data.extend_from_slice(&42u32.to_be_bytes());
+    data.extend_from_slice(b"readme.txt\0");
+    data.extend_from_slice(&[0x22; 16]);
+    data.extend_from_slice(&5u32.to_be_bytes());
+
+    let manifest = InstallManifest::new(&mut Cursor::new(data)).unwrap();
+    assert_eq!(manifest.entries.len(), 2);
+    assert_eq!(manifest.entries[0].name, "Game.exe");
+    assert_eq!(manifest.entries[0].content_key, [0x11; 16]);
+    assert_eq!(manifest.entries[1].size, 5);
+    let tags = |index| {
+        manifest
+            .entry_tags(index)
+            .map(|tag| tag.name.as_str())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(tags(0), ["Windows", "enUS"]);
+    assert_eq!(tags(1), ["enUS"]);
+
+    assert!(InstallManifest::new(&mut Cursor::new(b"EN\x01\x10".to_vec())).is_err());
+}
+
+#[test]
+fn tvfs_opens_without_readable_encoding() {
+    let mut builder = StorageBuilder::tvfs(&[("hello.txt", b"hello tvfs")]);
+    // An ENCODING file in an unsupported version
+    let encoding = builder.add(b"EN\x02\x10\x10");
+    let fixture = builder
+        .config_keys("encoding", encoding)
+        .write("tvfs_opens_without_readable_encoding");
+
+    let storage = fixture.open().unwrap();
+    assert!(storage.encoding_table().is_none());
+    assert_eq!(fixture::read(&storage, "hello.txt"), b"hello tvfs");
+}
+
+/// Writes a TVFS storage whose `b.txt` is indexed by a second bucket, returning it with the
+/// path of that bucket's `.idx` file.
+fn two_bucket_storage(name: &str) -> (fixture::Fixture, std::path::PathBuf) {
+    let fixture =
+        StorageBuilder::tvfs(&[("a.txt", b"first file"), ("b.txt", b"second file")]).write(name);
+
+    let (second, first): (Vec<_>, Vec<_>) = fixture
+        .index_entries
+        .iter()
+        .cloned()
+        .partition(|entry| entry.encoding_key == fixture.index_entries[1].encoding_key);
+    std::fs::write(fixture.idx_path(), fixture::idx_bytes(0, &first)).unwrap();
+    let second_path = fixture.data_dir().join("0100000001.idx");
+    std::fs::write(&second_path, fixture::idx_bytes(1, &second)).unwrap();
+    (fixture, second_path)
+}
+
+/// Asserts that `a.txt` can still be read while `b.txt`, in the skipped bucket, is listed
+/// without local data.
+fn assert_second_bucket_skipped(storage: &CascStorage) {
+    assert!(storage.open_file("a.txt").is_ok());
+    let b = storage
+        .files
+        .iter()
+        .find(|file| file.file_name() == "b.txt");
+    assert!(!b.unwrap().is_local());
+}
+
+#[test]
+fn corrupt_index_policy_handles_flipped_bits() {
+    use casc_rs::casc_open_options::{CascOpenOptions, CorruptIndexPolicy};
+    use casc_rs::error::CascError;
+
+    let (fixture, corrupt) = two_bucket_storage("corrupt_index_policy_handles_flipped_bits");
+    // Flip a bit of the encoding key of the only entry
+    let length = std::fs::metadata(&corrupt).unwrap().len() as usize;
+    fixture::Fixture::patch(&corrupt, length - 18, |byte| byte ^ 0x01);
+
+    match fixture.open() {
+        Err(CascError::IndexHashMismatch(path)) => assert_eq!(path, corrupt),
+        other => panic!("Expected an index hash mismatch, got {other:?}"),
+    }
+
+    let options = CascOpenOptions::new().corrupt_index_policy(CorruptIndexPolicy::Skip);
+    let storage = fixture.open_with(options).unwrap();
+    assert_eq!(storage.corrupt_indexes(), [corrupt]);
+    assert_second_bucket_skipped(&storage);
+}
+
+#[test]
+fn corrupt_index_policy_handles_truncated_indexes() {
+    use casc_rs::casc_open_options::{CascOpenOptions, CorruptIndexPolicy};
+
+    let (fixture, corrupt) = two_bucket_storage("corrupt_index_policy_handles_truncated_indexes");
+    let data = std::fs::read(&corrupt).unwrap();
+    // Cut off within the header, and within the table
+    for length in [20, data.len() - 4] {
+        std::fs::write(&corrupt, &data[..length]).unwrap();
+        assert!(fixture.open().is_err());
+
+        let options = CascOpenOptions::new().corrupt_index_policy(CorruptIndexPolicy::Skip);
+        let storage = fixture.open_with(options).unwrap();
+        assert_eq!(storage.corrupt_indexes(), std::slice::from_ref(&corrupt));
+        assert_second_bucket_skipped(&storage);
+    }
+}
+
+#[test]
+fn index_generations_only_load_the_newest() {
+    use casc_rs::casc_open_options::{CascOpenOptions, CorruptIndexPolicy};
+
+    let (fixture, second) = two_bucket_storage("index_generations_only_load_the_newest");
+    let newest = fixture.data_dir().join("0100000002.idx");
+    std::fs::copy(&second, &newest).unwrap();
+
+    let versions = |files: &[casc_rs::casc_index_file::CascIndexFile]| {
+        files.iter().map(|file| file.version).collect::<Vec<_>>()
+    };
+    let storage = fixture.open().unwrap();
+    assert_eq!(versions(storage.index_files()), [1, 2]);
+    assert_eq!(versions(storage.stale_index_files()), [1]);
+    assert!(storage.corrupt_indexes().is_empty());
+
+    // Skipping a corrupt newest generation skips its whole bucket, rather than falling back
+    // to the older one
+    let assert_bucket_skipped = || {
+        assert!(fixture.open().is_err());
+        let options = CascOpenOptions::new().corrupt_index_policy(CorruptIndexPolicy::Skip);
+        let storage = fixture.open_with(options).unwrap();
+        assert_eq!(versions(storage.index_files()), [1]);
+        assert_eq!(versions(storage.stale_index_files()), [1]);
+        assert_eq!(storage.corrupt_indexes(), std::slice::from_ref(&newest));
+        assert_second_bucket_skipped(&storage);
+    };
+    let length = std::fs::metadata(&newest).unwrap().len() as usize;
+    fixture::Fixture::patch(&newest, length - 1, |byte| byte ^ 0x80);
+    assert_bucket_skipped();
+
+    // So does an index too short to hold its bucket
+    std::fs::write(&newest, [0u8; 4]).unwrap();
+    assert_bucket_skipped();
+}
+
+#[test]
+fn frame_verification_rejects_corrupted_frames() {
+    use casc_rs::casc_open_options::CascOpenOptions;
+    use casc_rs::error::CascError;
+    use std::io::Read;
+
+    let mut builder = StorageBuilder::new();
+    let encoding_key = builder.add_blte(fixture::blte(b'N', &[b"first frame", b"second frame"]));
+    let content_key = fixture::md5(b"first framesecond frame");
+    let fixture = builder
+        .add_tvfs_root(
+            &[(
+                "a.txt",
+                vec![TvfsSpan::whole(content_key, encoding_key, 23)],
+            )],
+            0,
+            &[],
+        )
+        .write("frame_verification_rejects_corrupted_frames");
+
+    // Damage the second frame, after the span header, the frame table and the first frame
+    let frame_offset = fixture.index_entries[0].offset + 30 + 60 + 12;
+    fixture::Fixture::patch(
+        &fixture.data_file_path(),
+        frame_offset as usize + 1,
+        |byte| byte ^ 0x20,
+    );
+
+    let storage = fixture.open().unwrap();
+    assert_eq!(fixture::read(&storage, "a.txt"), b"first frameSecond frame");
+
+    let storage = fixture
+        .open_with(CascOpenOptions::new().verify_frames(true))
+        .unwrap();
+    let error = storage
+        .open_file("a.txt")
+        .unwrap()
+        .read_to_end(&mut Vec::new())
+        .unwrap_err();
+    match error.downcast::<CascError>() {
+        Ok(CascError::FrameHashMismatch {
+            frame_index,
+            offset,
+        }) => {
+            assert_eq!(frame_index, 1);
+            assert_eq!(offset, frame_offset);
+        }
+        other => panic!("Expected a frame hash mismatch, got {other:?}"),
+    }
+}
+
+#[test]
+fn verify_all_reports_damaged_spans() {
+    use casc_rs::casc_verification::VerifyOptions;
+
+    let mut builder = StorageBuilder::new();
+    let good = builder.add(b"good file");
+    let wrong = builder.add(b"actual data");
+    let frame = builder.add_blte(fixture::blte(b'N', &[b"frame data"]));
+    let header = builder.add(b"bad header");
+    let orphan = builder.add(b"orphan");
+    let span = |content: &[u8], encoding_key| {
+        vec![TvfsSpan::whole(
+            fixture::md5(content),
+            encoding_key,
+            content.len() as u32,
+        )]
+    };
+    let fixture = builder
+        .add_tvfs_root(
+            &[
+                ("good.txt", span(b"good file", good.1)),
+                ("wrong.txt", span(b"expected data", wrong.1)),
+                ("frame.txt", span(b"frame data", frame)),
+                ("header.txt", span(b"bad header", header.1)),
+                ("missing.txt", span(b"missing", [0x99; 16])),
+            ],
+            1,
+            &[],
+        )
+        .write("verify_all_reports_damaged_spans");
+
+    // Index a span in a data file that does not exist
+    let mut index_entries = fixture.index_entries.clone();
+    index_entries.push(fixture::IndexEntry {
+        encoding_key: [0x77; 16],
+        offset: 1 << 30,
+        size: 100,
+    });
+    std::fs::write(fixture.idx_path(), fixture::idx_bytes(0, &index_entries)).unwrap();
+    // Damage the data of the frame, and the first byte of the byte reversed encoding key in
+    // the span header of another span
+    let offset = |index: usize| fixture.index_entries[index].offset as usize;
+    fixture::Fixture::patch(&fixture.data_file_path(), offset(2) + 30 + 36 + 1, |byte| {
+        byte ^ 0x20
+    });
+    fixture::Fixture::patch(&fixture.data_file_path(), offset(3) + 15, |byte| {
+        byte ^ 0x01
+    });
+
+    let storage = fixture.open().unwrap();
+    let report = storage.verify_all(&VerifyOptions::new()).unwrap();
+    let keys = |issues: &[casc_rs::casc_verification::SpanIssue]| {
+        issues
+            .iter()
+            .map(|issue| issue.encoding_key.clone())
+            .collect::<Vec<_>>()
+    };
+    assert!(!report.is_ok());
+    assert_eq!(report.checked_spans, 6);
+    assert_eq!(report.missing_data_files, [1]);
+    assert_eq!(keys(&report.unreadable_spans), [header.1[..9].to_vec()]);
+    assert_eq!(report.unreadable_spans[0].offset, offset(3) as u64);
+    assert_eq!(
+        keys(&report.hash_mismatches),
+        [wrong.1[..9].to_vec(), frame[..9].to_vec()]
+    );
+    let mut orphans = vec![orphan.1[..9].to_vec(), vec![0x77; 9]];
+    orphans.sort();
+    assert_eq!(report.orphaned_index_entries, orphans);
+    assert_eq!(report.missing_root_entries, ["missing.txt"]);
+
+    let parallel = storage
+        .verify_all(&VerifyOptions::new().parallel(true))
+        .unwrap();
+    assert_eq!(parallel.unreadable_spans, report.unreadable_spans);
+    assert_eq!(parallel.hash_mismatches, report.hash_mismatches);
+
+    // Without hash checks only the structural problems remain
+    let options = VerifyOptions::new()
+        .frame_hashes(false)
+        .content_hashes(false);
+    let report = storage.verify_all(&options).unwrap();
+    assert!(report.hash_mismatches.is_empty());
+    assert_eq!(keys(&report.unreadable_spans), [header.1[..9].to_vec()]);
+}
+
+#[test]
+fn file_sizes_are_listed_without_reading_the_data_files() {
+    let mut builder = StorageBuilder::new();
+    let a = builder.add(b"first file");
+    let b = builder.add(b"second file");
+    let fixture = builder
+        .add_tvfs_root(
+            &[(
+                "ab.txt",
+                vec![TvfsSpan::whole(a.0, a.1, 10), TvfsSpan::whole(b.0, b.1, 11)],
+            )],
+            0,
+            &[],
+        )
+        .write("file_sizes_are_listed_without_reading_the_data_files");
+
+    let storage = fixture.open().unwrap();
+    let info = &storage.files[0];
+    let details = info.details(&storage).unwrap();
+    assert_eq!(details.content_size, 21);
+    assert_eq!(details.encoded_size, info.encoded_size());
+
+    // The listed sizes stay available once the data files are gone
+    std::fs::remove_file(fixture.data_file_path()).unwrap();
+    assert_eq!(info.content_size(), 21);
+    assert!(info.details(&storage).is_err());
+}
+
+#[test]
+fn tvfs_mounts_sub_manifests() {
+    let mut builder = StorageBuilder::new();
+    let inner = builder.add(b"inner file");
+    let extra = builder.add(b"extra file");
+    let base = builder.add(b"base file");
+    let mounted = builder.add(&fixture::tvfs_root(
+        &[("inner.txt", vec![TvfsSpan::whole(inner.0, inner.1, 10)])],
+        0,
+        &[],
+    ));
+    let unmounted = builder.add(&fixture::tvfs_root(
+        &[("extra.txt", vec![TvfsSpan::whole(extra.0, extra.1, 10)])],
+        0,
+        &[],
+    ));
+    let damaged = builder.add(b"TVFS cut short");
+    // The root mounts vfs-1 by referencing its encoding key, and leaves vfs-2 unmounted
+    let fixture = builder
+        .add_tvfs_root(
+            &[
+                ("base.txt", vec![TvfsSpan::whole(base.0, base.1, 9)]),
+                ("mods", vec![TvfsSpan::whole(mounted.0, mounted.1, 0)]),
+            ],
+            0,
+            &[],
+        )
+        .config_keys("vfs-1", mounted)
+        .config_keys("vfs-2", unmounted)
+        .config_keys("vfs-3", damaged)
+        .write("tvfs_mounts_sub_manifests");
+
+    let storage = fixture.open().unwrap();
+    assert_eq!(storage.skipped_manifests(), ["vfs-3"]);
+    let mut names = storage
+        .files
+        .iter()
+        .map(|info| info.file_name().to_string())
+        .collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(names, ["base.txt", "mods\\inner.txt", "vfs-2\\extra.txt"]);
+
+    for (name, expected) in [
+        ("mods\\inner.txt", &b"inner file"[..]),
+        ("vfs-2\\extra.txt", &b"extra file"[..]),
+    ] {
+        assert_eq!(fixture::read(&storage, name), expected);
+    }
+}
+
+#[test]
+fn tvfs_reads_spans_within_blobs() {
+    let mut builder = StorageBuilder::new();
+    let blob = builder.add(b"header:payload");
+    let partial = TvfsSpan {
+        offset: 7,
+        size: 7,
+        ..TvfsSpan::whole(blob.0, blob.1, 14)
+    };
+    let fixture = builder
+        .add_tvfs_root(&[("payload.bin", vec![partial])], 1, &[])
+        .write("tvfs_reads_spans_within_blobs");
+
+    let storage = fixture.open().unwrap();
+    let details = storage.file_details("payload.bin").unwrap();
+    assert_eq!(details.content_size, 7);
+    assert_eq!(details.spans[0].blob_size, Some(14));
+    assert_eq!(fixture::read(&storage, "payload.bin"), b"payload");
+}
+
+#[test]
+fn cmf_key_table_procedures_known_answer() {
+    use casc_rs::cmf_key_provider::{procedures, CmfHeader, CmfKeyProvider, CmfKeyTable};
+    use sha1::{Digest, Sha1};
+
+    let provider = CmfKeyTable::new(
+        50000,
+        std::array::from_fn(|i| (i * 167 + 13) as u8),
+        procedures::key_by_build,
+        procedures::iv_by_digest,
+    );
+    let header = CmfHeader {
+        build_version: 50000,
+        data_count: 2,
+        entry_count: 1,
+        magic: 0x636D6614,
+        fields: vec![50000, 0, 0, 2, 0, 1, 0x636D6614],
+    };
+    let digest: [u8; 20] = Sha1::digest(b"Win_SPWin_RCN_LenUS_EExt").into();
+
+    assert_eq!(provider.build_version(), 50000);
+    assert_eq!(
+        hex::encode(provider.key(&header, 32)),
+        "d8cdc2b7aca1968b80756a5f54493e33281d1207fcf1e6dbd0c5baafa4998e83"
+    );
+    assert_eq!(
+        hex::encode(provider.iv(&header, &digest, 16)),
+        "522e4d9dcc14a5e64fd5d5a6d6125ee0"
+    );
+}
+
+/// Serializes a CMF manifest whose header ends with the magic at offset 24, followed by its
+/// records, each given as GUID, size and content key.
+fn cmf(version: u8, records: &[(u64, u32, [u8; 16])]) -> Vec<u8> {
+    let mut data = Vec::new();
+    for field in [
+        50000,
+        0,
+        0,
+        records.len() as u32,
+        0,
+        0,
+        0x636D6600 | version as u32,
+    ] {
+        data.extend_from_slice(&field.to_le_bytes());
+    }
+    for (guid, size, content_key) in records {
+        data.extend_from_slice(&guid.to_le_bytes());
+        data.extend_from_slice(&size.to_le_bytes());
+        data.push(0);
+        data.extend_from_slice(content_key);
+    }
+    data
+}
+
+#[test]
+fn overwatch_reports_skipped_manifests() {
+    use casc_rs::error::CascError;
+    use std::io::Read;
+
+    const GUID: u64 = 0x0C00_0000_0000_1234;
+    let mut builder = StorageBuilder::new();
+    let asset_data = b"texture data";
+    let asset = builder.add(asset_data);
+    let plain = cmf(0x13, &[(GUID, asset_data.len() as u32, asset.0)]);
+    // Encrypted, for a build with no key provider
+    let encrypted = cmf(0x14, &[(GUID + 1, 4, [0xAA; 16])]);
+    let root = format!(
+        "#MD5|FILENAME\n{}|plain.cmf\n{}|encrypted.cmf\n",
+        hex::encode(fixture::md5(&plain)),
+        hex::encode(fixture::md5(&encrypted)),
+    );
+    builder.add(&plain);
+    builder.add(&encrypted);
+    let fixture = builder
+        .add_root(root.as_bytes())
+        .write("overwatch_reports_skipped_manifests");
+
+    let storage = fixture.open().unwrap();
+    assert_eq!(storage.skipped_manifests(), ["encrypted.cmf"]);
+    let mut data = Vec::new();
+    storage
+        .open_file_by_guid(GUID)
+        .unwrap()
+        .read_to_end(&mut data)
+        .unwrap();
+    assert_eq!(data, asset_data);
+
+    match storage.open_file_by_guid(GUID + 1) {
+        Err(CascError::UnlistedGuid {
+            guid,
+            skipped_manifests,
+        }) => {
+            assert_eq!(guid, GUID + 1);
+            assert_eq!(skipped_manifests, ["encrypted.cmf"]);
+        }
+        result => panic!("unexpected result: {:?}", result.map(|_| ())),
+    }
+}
+
+#[test]
+fn unrecognised_roots_without_encoding_report_their_header() {
+    use casc_rs::error::CascError;
+
+    let mut builder = StorageBuilder::new();
+    let root = builder.add(b"\x01\x02\x03\x04 not a root");
+    let fixture = builder
+        .config_keys("vfs-root", root)
+        .write("unrecognised_roots_without_encoding_report_their_header");
+
+    match fixture.open() {
+        Err(CascError::InvalidData(message)) => {
+            assert!(message.starts_with("Invalid VFS header"), "{message}")
+        }
+        result => panic!("unexpected result: {:?}", result.map(|_| ())),
+    }
+}
+
+#[test]
+fn unrecognised_roots_report_why_install_could_not_be_listed() {
+    use casc_rs::error::CascError;
+
+    let fixture = StorageBuilder::new()
+        .add_root(b"\x01\x02\x03\x04 not a root")
+        .write("unrecognised_roots_report_why_install_could_not_be_listed");
+
+    match fixture.open() {
+        Err(CascError::InvalidData(message)) => {
+            assert!(message.starts_with("Invalid VFS header"), "{message}");
+            assert!(message.contains("INSTALL file not in config"), "{message}");
+        }
+        result => panic!("unexpected result: {:?}", result.map(|_| ())),
+    }
+}
+
+#[test]
+fn legacy_wow_roots_are_only_tried_for_wow_builds_or_sound_blocks() {
+    // A root of a single empty block, which parses as a legacy World of Warcraft root
+    let root = [0u32, 0, 2].map(u32::to_le_bytes).concat();
+    let content = b"game binary";
+    let mut install = b"IN\x01\x10\x00\x00\x00\x00\x00\x01Game.exe\x00".to_vec();
+    install.extend_from_slice(&fixture::md5(content));
+    install.extend_from_slice(&(content.len() as u32).to_be_bytes());
+
+    for is_wow in [false, true] {
+        let mut builder = StorageBuilder::new();
+        builder.add(content);
+        let install = builder.add(&install);
+        builder.config_keys("install", install).add_root(&root);
+        if is_wow {
+            builder.config("build-product", "WoW");
+        }
+        let fixture = builder.write(&format!("legacy_wow_roots_{is_wow}"));
+
+        let storage = fixture.open().unwrap();
+        let names = storage
+            .files
+            .iter()
+            .map(|file| file.file_name())
+            .collect::<Vec<_>>();
+        if is_wow {
+            assert!(names.is_empty(), "{names:?}");
+        } else {
+            assert_eq!(names, ["Game.exe"]);
+            assert_eq!(fixture::read(&storage, "Game.exe"), content);
+        }
+    }
+}
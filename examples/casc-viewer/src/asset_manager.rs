@@ -150,9 +150,18 @@ impl PorterAssetManager for AssetManager {
             *self.loaded_assets.write().unwrap() = Vec::new();
         }
         let file = files.first().unwrap();
-        let storage = CascStorage::open(file.parent().unwrap())
+        let storage_path = file.parent().unwrap();
+        let mut storage = CascStorage::open(storage_path)
             .map_err(|e| format!("Failed to open Casc Storage {e}"))?;
 
+        // Name FileDataID-only entries from a listfile next to the storage, if there is one
+        let listfile_path = storage_path.join("listfile.csv");
+        if listfile_path.is_file() {
+            storage
+                .load_listfile(&listfile_path)
+                .map_err(|e| format!("Failed to load listfile {e}"))?;
+        }
+
         let mut entries = Vec::new();
         for entry in &storage.files {
             if !entry.is_local() {
@@ -171,7 +180,7 @@ impl PorterAssetManager for AssetManager {
                 name: name.to_string(),
                 asset_type: ext.to_string(),
                 status: PorterAssetStatus::loaded(),
-                asset_size: entry.file_size() as u64,
+                asset_size: entry.content_size(),
             };
             entries.push(asset);
         }